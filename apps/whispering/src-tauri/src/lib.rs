@@ -4,12 +4,25 @@ use tauri_plugin_aptabase::EventTracker;
 
 pub mod recorder;
 use recorder::commands::{
-    cancel_recording, close_recording_session, enumerate_recording_devices,
-    get_current_recording_id, init_recording_session, start_recording, stop_recording, AppData,
+    add_recording_marker, add_recording_sink, cancel_recording, clean_up_recordings,
+    close_recording_session, enumerate_recording_devices, export_recording,
+    export_recording_as_opus, get_current_recording_id, get_recording_retention,
+    get_recordings_storage_stats, init_recording_session, list_recording_sinks,
+    migrate_recordings_folder, read_active_recording, read_memory_sink, read_recording,
+    remove_recording_sink, select_recordings_folder, set_recording_retention, start_recording,
+    stop_recording, test_recording_device, AppData,
 };
+use recorder::storage::spawn_retention_task;
 
 pub mod whisper_cpp;
-use whisper_cpp::transcribe_with_whisper_cpp;
+use whisper_cpp::{
+    cancel_transcription, create_transcription_job, detect_language, export_transcript,
+    get_loaded_models, get_model_cache_settings, preload_model, set_model_cache_settings,
+    spawn_model_eviction_task, start_streaming_transcription, stop_streaming_transcription,
+    transcribe_recording_with_whisper_cpp, transcribe_segments_with_whisper_cpp,
+    transcribe_with_whisper_cpp, unload_model, ModelCache, StreamingTranscription,
+    TranscriptionJobs,
+};
 
 pub mod windows_path;
 use windows_path::fix_windows_path;
@@ -54,7 +67,19 @@ pub async fn run() {
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
-        .manage(AppData::new());
+        .manage(AppData::new())
+        .manage(ModelCache::new())
+        .manage(StreamingTranscription::new())
+        .manage(TranscriptionJobs::new())
+        .setup(|app| {
+            // Allow the default recordings locations before any session is created
+            app.state::<AppData>().recordings_scope.init(app.handle());
+            app.state::<AppData>().recording_keys.init(app.handle());
+            app.state::<AppData>().retention.init(app.handle());
+            spawn_retention_task(app.handle().clone());
+            spawn_model_eviction_task(app.handle().clone());
+            Ok(())
+        });
 
     #[cfg(desktop)]
     {
@@ -77,8 +102,37 @@ pub async fn run() {
         start_recording,
         stop_recording,
         cancel_recording,
+        add_recording_marker,
+        add_recording_sink,
+        remove_recording_sink,
+        list_recording_sinks,
+        read_memory_sink,
+        test_recording_device,
+        select_recordings_folder,
+        migrate_recordings_folder,
+        read_recording,
+        read_active_recording,
+        export_recording,
+        export_recording_as_opus,
+        get_recordings_storage_stats,
+        clean_up_recordings,
+        get_recording_retention,
+        set_recording_retention,
         // Whisper transcription
         transcribe_with_whisper_cpp,
+        transcribe_segments_with_whisper_cpp,
+        transcribe_recording_with_whisper_cpp,
+        detect_language,
+        create_transcription_job,
+        cancel_transcription,
+        export_transcript,
+        start_streaming_transcription,
+        stop_streaming_transcription,
+        preload_model,
+        unload_model,
+        get_loaded_models,
+        get_model_cache_settings,
+        set_model_cache_settings,
         send_sigint,
     ]);
 
@@ -1,20 +1,107 @@
-use crate::recorder::recorder::{AudioRecording, RecorderState, Result};
-use std::path::PathBuf;
-use std::sync::Mutex;
-use tauri::State;
-use tracing::{debug, info};
+use crate::recorder::device_test::{test_device, DeviceTestResult};
+use crate::recorder::encryption::RecordingKeyStore;
+use crate::recorder::level_meter::{AudioLevel, LevelHandler, LevelMeter};
+use crate::recorder::live_reader::{LiveAudio, MAX_READ_SECONDS};
+use crate::recorder::markers::RecordingMarker;
+use crate::recorder::network_sink::NetworkSink;
+use crate::recorder::opus::encode_wav_as_opus;
+use crate::recorder::quality::{SignalWarning, SignalWarningHandler};
+use crate::recorder::recorder::{
+    AudioRecording, BufferSizeRequest, RecorderState, RecordingSessionInfo, Result,
+};
+use crate::recorder::scope::{validate_recording_id, RecordingPathError, RecordingsScope};
+use crate::recorder::sink::SinkId;
+use crate::recorder::source::FILE_DEVICE_PREFIX;
+use crate::recorder::storage::{
+    apply_retention, find_recording, parse_recording_file_name, storage_stats, CleanupReport,
+    RecordingRetention, RetentionPolicy, RetentionSettings, StorageStats,
+};
+use crate::recorder::vad::{VoiceActivity, VoiceActivityDetector, VoiceActivityHandler};
+use crate::recorder::writer::{OutputSampleFormat, RecordingFormat, RecordingInfo, WriterOptions};
+use serde::{Deserialize, Serialize};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::ipc::Response;
+use tauri::{Emitter, Manager, Runtime, State};
+use tauri_plugin_dialog::{DialogExt, FileDialogBuilder, FilePath};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Event emitted when the recorder detects a problem with the input signal
+pub const SIGNAL_WARNING_EVENT: &str = "recording-signal-warning";
+
+/// Event emitted with every reading of a level meter sink
+pub const LEVEL_EVENT: &str = "recording-level";
+
+/// Event emitted when a voice activity sink detects speech starting or ending
+pub const VOICE_ACTIVITY_EVENT: &str = "recording-voice-activity";
+
+/// Sink to attach to the open session - sent from frontend
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SinkRequest {
+    /// Input levels every 50 ms, as `recording-level` events
+    LevelMeter,
+    /// Start and end of speech, as `recording-voice-activity` events
+    VoiceActivity,
+    /// 16-bit PCM WAV streamed to a TCP listener, e.g. `127.0.0.1:9000`
+    Network { address: String },
+    /// Audio kept in memory, only the last `max_seconds` if given
+    #[serde(rename_all = "camelCase")]
+    Memory { max_seconds: Option<f32> },
+}
+
+/// Sink attached to the open session - returned to frontend
+#[derive(Debug, Serialize)]
+pub struct SinkInfo {
+    pub id: SinkId,
+    pub name: String,
+}
+
+/// Error of a recorder command that deals with recording paths - returned to frontend.
+/// Path problems keep their type (`{ name, ... }`), anything else is a message string.
+#[derive(Debug, Error, Serialize)]
+#[serde(untagged)]
+pub enum RecorderCommandError {
+    #[error(transparent)]
+    Path(#[from] RecordingPathError),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for RecorderCommandError {
+    fn from(message: String) -> Self {
+        RecorderCommandError::Other(message)
+    }
+}
+
+/// Result of the recorder commands that return typed path errors
+pub type CommandResult<T> = std::result::Result<T, RecorderCommandError>;
 
 /// Application state containing the recorder
 pub struct AppData {
     pub recorder: Mutex<RecorderState>,
+    pub recordings_scope: RecordingsScope,
+    pub recording_keys: RecordingKeyStore,
+    pub retention: RecordingRetention,
 }
 
 impl AppData {
     pub fn new() -> Self {
         Self {
             recorder: Mutex::new(RecorderState::new()),
+            recordings_scope: RecordingsScope::new(),
+            recording_keys: RecordingKeyStore::new(),
+            retention: RecordingRetention::new(),
         }
     }
+
+    /// Recording ID of the open session, which cleanup must leave alone
+    pub fn session_recording_id(&self) -> Option<String> {
+        self.recorder.lock().ok()?.get_session_recording_id()
+    }
 }
 
 #[tauri::command]
@@ -28,21 +115,42 @@ pub async fn enumerate_recording_devices(state: State<'_, AppData>) -> Result<Ve
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn init_recording_session(
     device_identifier: String,
     recording_id: String,
     output_folder: String,
     sample_rate: Option<u32>,
+    buffer_size: Option<u32>,
+    latency_ms: Option<u32>,
+    encrypt: Option<bool>,
+    sample_format: Option<OutputSampleFormat>,
+    format: Option<RecordingFormat>,
+    broadcast_wave: Option<bool>,
     state: State<'_, AppData>,
-    _app_handle: tauri::AppHandle,
-) -> Result<()> {
+    app_handle: tauri::AppHandle,
+) -> CommandResult<RecordingSessionInfo> {
     info!(
-        "Initializing recording session: device={}, id={}, folder={}, sample_rate={:?}",
-        device_identifier, recording_id, output_folder, sample_rate
+        "Initializing recording session: device={}, id={}, folder={}, sample_rate={:?}, buffer_size={:?}, latency_ms={:?}, encrypt={:?}, sample_format={:?}, format={:?}, broadcast_wave={:?}",
+        device_identifier, recording_id, output_folder, sample_rate, buffer_size, latency_ms, encrypt, sample_format, format, broadcast_wave
     );
 
-    // Use the provided output folder
-    let recordings_dir = PathBuf::from(output_folder);
+    // A buffer size and a latency target are two ways to pick the same thing, take one
+    let buffer_size = match (buffer_size, latency_ms) {
+        (Some(_), Some(_)) => {
+            return Err("Specify either a buffer size or a latency target, not both"
+                .to_string()
+                .into())
+        }
+        (Some(frames), None) => BufferSizeRequest::Frames(frames),
+        (None, Some(ms)) => BufferSizeRequest::LatencyMs(ms),
+        (None, None) => BufferSizeRequest::Default,
+    };
+
+    let device_identifier = resolve_device_identifier(&state, &device_identifier)?;
+
+    // Only write inside the allowed recordings locations
+    let recordings_dir = state.recordings_scope.resolve(Path::new(&output_folder))?;
     
     // Create the directory if it doesn't exist
     if !recordings_dir.exists() {
@@ -52,15 +160,419 @@ pub async fn init_recording_session(
     
     // Validate it's a directory (not a file)
     if !recordings_dir.is_dir() {
-        return Err(format!("Output path is not a directory: {:?}", recordings_dir));
+        return Err(format!("Output path is not a directory: {:?}", recordings_dir).into());
     }
 
+    // Encrypted recordings use the app's key, created on first use
+    let encryption_key = if encrypt.unwrap_or(false) {
+        let key = state
+            .recording_keys
+            .key()
+            .map_err(|e| format!("Failed to load recordings key: {}", e))?;
+        Some(key)
+    } else {
+        None
+    };
+
+    // Forward signal warnings (e.g. a muted mic) to the frontend as they happen
+    let on_signal_warning: SignalWarningHandler = Arc::new(move |warning: SignalWarning| {
+        warn!("Recording signal warning: {:?}", warning);
+        let _ = app_handle.emit(SIGNAL_WARNING_EVENT, warning);
+    });
+
     // Initialize the session with optional sample rate
     let mut recorder = state
         .recorder
         .lock()
         .map_err(|e| format!("Failed to lock recorder: {}", e))?;
-    recorder.init_session(device_identifier, recordings_dir, recording_id, sample_rate)
+    let info = recorder.init_session(
+        device_identifier,
+        recordings_dir,
+        recording_id,
+        sample_rate,
+        buffer_size,
+        Some(on_signal_warning),
+        WriterOptions {
+            encryption_key,
+            sample_format: sample_format.unwrap_or_default(),
+            format: format.unwrap_or_default(),
+            info: RecordingInfo {
+                broadcast_extension: broadcast_wave.unwrap_or(false),
+                ..Default::default()
+            },
+        },
+    )?;
+    Ok(info)
+}
+
+/// Check that a file is a recording (named `<id>.<recording extension>`) that lives
+/// inside the allowed recordings locations. Other files in those folders stay off limits.
+pub(crate) fn resolve_recording_path(state: &AppData, file_path: &str) -> CommandResult<PathBuf> {
+    let path = Path::new(file_path);
+    let (Some(folder), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(format!("Invalid recording path: {}", file_path).into());
+    };
+    if file_name
+        .to_str()
+        .and_then(parse_recording_file_name)
+        .is_none()
+    {
+        return Err(RecordingPathError::NotARecording {
+            path: file_path.to_string(),
+        }
+        .into());
+    }
+    let folder = state.recordings_scope.resolve(folder)?;
+    Ok(folder.join(file_name))
+}
+
+/// File sources (`file:<path>`) may only play back WAV files inside the allowed
+/// recordings locations, other device identifiers pass through unchanged
+fn resolve_device_identifier(state: &AppData, device_identifier: &str) -> CommandResult<String> {
+    let Some(file_path) = device_identifier.strip_prefix(FILE_DEVICE_PREFIX) else {
+        return Ok(device_identifier.to_string());
+    };
+    let path = Path::new(file_path);
+    let (Some(folder), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(format!("Invalid file source: {}", file_path).into());
+    };
+    if path.extension().and_then(|ext| ext.to_str()) != Some("wav") {
+        return Err(RecordingPathError::NotARecording {
+            path: file_path.to_string(),
+        }
+        .into());
+    }
+    let folder = state.recordings_scope.resolve(folder)?;
+    Ok(format!(
+        "{}{}",
+        FILE_DEVICE_PREFIX,
+        folder.join(file_name).display()
+    ))
+}
+
+/// Find the file of a recording by id in an output folder within the allowed locations
+pub(crate) fn resolve_recording_id(
+    state: &AppData,
+    output_folder: &Path,
+    recording_id: &str,
+) -> CommandResult<PathBuf> {
+    validate_recording_id(recording_id)?;
+    let folder = state.recordings_scope.resolve(output_folder)?;
+    let path = find_recording(&folder, recording_id)
+        .map_err(|e| format!("Failed to look up recording {}: {}", recording_id, e))?
+        .ok_or_else(|| format!("Recording {} not found in {:?}", recording_id, folder))?;
+    Ok(path)
+}
+
+/// Ask the user where to save a file without blocking the async runtime. None if cancelled.
+pub(crate) async fn save_file<R: Runtime>(dialog: FileDialogBuilder<R>) -> Option<FilePath> {
+    let (picked, result) = tokio::sync::oneshot::channel();
+    dialog.save_file(move |path| {
+        picked.send(path).ok();
+    });
+    result.await.ok().flatten()
+}
+
+/// Let the user pick a folder without blocking the async runtime. None if cancelled.
+async fn pick_folder<R: Runtime>(dialog: FileDialogBuilder<R>) -> Option<FilePath> {
+    let (picked, result) = tokio::sync::oneshot::channel();
+    dialog.pick_folder(move |folder| {
+        picked.send(folder).ok();
+    });
+    result.await.ok().flatten()
+}
+
+/// Read a recording as WAV bytes, decrypting it in memory if it is encrypted
+#[tauri::command]
+pub async fn read_recording(
+    file_path: String,
+    state: State<'_, AppData>,
+) -> CommandResult<Response> {
+    debug!("Reading recording: {}", file_path);
+    let path = resolve_recording_path(&state, &file_path)?;
+    let bytes = state
+        .recording_keys
+        .read_recording(&path)
+        .map_err(|e| format!("Failed to read recording: {}", e))?;
+    Ok(Response::new(bytes))
+}
+
+/// Read audio from the active recording while it is still being written, starting at
+/// `start_frame`. Returns what has reached disk so far, at most `max_frames` frames
+/// (capped at 30 seconds per call). Only sessions recording WAV can be read this way.
+#[tauri::command]
+pub async fn read_active_recording(
+    start_frame: u64,
+    max_frames: Option<u64>,
+    state: State<'_, AppData>,
+) -> Result<LiveAudio> {
+    let live = {
+        let recorder = state
+            .recorder
+            .lock()
+            .map_err(|e| format!("Failed to lock recorder: {}", e))?;
+        recorder
+            .get_live_recording()?
+            .ok_or_else(|| "Only WAV recordings can be read while recording".to_string())?
+    };
+
+    let limit = live.sample_rate() as u64 * MAX_READ_SECONDS;
+    let max_frames = max_frames.map_or(limit, |frames| frames.min(limit));
+    live.read(&state.recording_keys, start_frame, max_frames)
+        .map_err(|e| format!("Failed to read active recording: {}", e))
+}
+
+/// Export a recording unencrypted, in the format it is stored in (WAV, FLAC or Opus),
+/// to a location the user picks. Returns the exported path, or None if the dialog was cancelled.
+#[tauri::command]
+pub async fn export_recording(
+    file_path: String,
+    state: State<'_, AppData>,
+    app_handle: tauri::AppHandle,
+) -> CommandResult<Option<String>> {
+    let path = resolve_recording_path(&state, &file_path)?;
+    let (id, extension) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split_once('.'))
+        .unwrap_or(("recording", "wav"));
+    let extension = extension.trim_end_matches(".enc");
+
+    let dialog = app_handle
+        .dialog()
+        .file()
+        .set_title("Export Recording")
+        .set_file_name(format!("{}.{}", id, extension))
+        .add_filter(format!("{} audio", extension.to_uppercase()), &[extension]);
+    let Some(picked) = save_file(dialog).await else {
+        return Ok(None);
+    };
+    let destination = picked
+        .into_path()
+        .map_err(|e| format!("Invalid export location: {}", e))?;
+
+    // Stream through the decrypting reader so the whole file is never held in memory
+    let mut reader = state
+        .recording_keys
+        .open_recording(&path)
+        .map_err(|e| format!("Failed to open recording: {}", e))?;
+    let mut out = std::fs::File::create(&destination)
+        .map_err(|e| format!("Failed to create export file: {}", e))?;
+
+    // Copying and decrypting a long recording takes a while, keep it off the async runtime
+    tokio::task::spawn_blocking(move || {
+        std::io::copy(&mut reader, &mut out)?;
+        out.sync_all()
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {}", e))?
+    .map_err(|e| {
+        std::fs::remove_file(&destination).ok(); // Don't leave a truncated export behind
+        format!("Failed to export recording: {}", e)
+    })?;
+
+    info!("Exported recording {:?} to {:?}", path, destination);
+    Ok(Some(destination.to_string_lossy().to_string()))
+}
+
+/// Convert a WAV recording to Ogg Opus, without FFmpeg, and save it where the user picks.
+/// The result is small enough to upload to cloud transcription APIs.
+/// Returns the exported path, or None if the dialog was cancelled.
+#[tauri::command]
+pub async fn export_recording_as_opus(
+    file_path: String,
+    state: State<'_, AppData>,
+    app_handle: tauri::AppHandle,
+) -> CommandResult<Option<String>> {
+    let path = resolve_recording_path(&state, &file_path)?;
+    let (id, extension) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split_once('.'))
+        .unwrap_or(("recording", "wav"));
+    if extension.trim_end_matches(".enc") != "wav" {
+        return Err("Only WAV recordings can be converted to Opus".to_string().into());
+    }
+
+    let dialog = app_handle
+        .dialog()
+        .file()
+        .set_title("Export Recording as Opus")
+        .set_file_name(format!("{}.opus", id))
+        .add_filter("Opus audio", &["opus"]);
+    let Some(picked) = save_file(dialog).await else {
+        return Ok(None);
+    };
+    let destination = picked
+        .into_path()
+        .map_err(|e| format!("Invalid export location: {}", e))?;
+
+    let reader = state
+        .recording_keys
+        .open_recording(&path)
+        .map_err(|e| format!("Failed to open recording: {}", e))?;
+    let out = std::fs::File::create(&destination)
+        .map_err(|e| format!("Failed to create export file: {}", e))?;
+
+    // Encoding takes a while for long recordings, keep it off the async runtime
+    let duration = tokio::task::spawn_blocking(move || {
+        let (out, duration) = encode_wav_as_opus(BufReader::new(reader), BufWriter::new(out))?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok::<_, std::io::Error>(duration)
+    })
+    .await
+    .map_err(|e| format!("Opus export task failed: {}", e))?
+    .map_err(|e| {
+        std::fs::remove_file(&destination).ok(); // Don't leave a truncated export behind
+        format!("Failed to convert recording to Opus: {}", e)
+    })?;
+
+    info!(
+        "Exported recording {:?} as Opus to {:?} ({:.2} seconds)",
+        path, destination, duration
+    );
+    Ok(Some(destination.to_string_lossy().to_string()))
+}
+
+/// Count and total size of the recordings in a folder
+#[tauri::command]
+pub async fn get_recordings_storage_stats(
+    output_folder: String,
+    state: State<'_, AppData>,
+) -> CommandResult<StorageStats> {
+    debug!("Getting recordings storage stats: {}", output_folder);
+    let folder = state.recordings_scope.resolve(Path::new(&output_folder))?;
+    let stats = storage_stats(&folder)
+        .map_err(|e| format!("Failed to read recordings folder: {}", e))?;
+    Ok(stats)
+}
+
+/// Apply a retention policy to a recordings folder right away
+#[tauri::command]
+pub async fn clean_up_recordings(
+    output_folder: String,
+    policy: RetentionPolicy,
+    state: State<'_, AppData>,
+    app_handle: tauri::AppHandle,
+) -> CommandResult<CleanupReport> {
+    info!(
+        "Cleaning up recordings: folder={}, policy={:?}",
+        output_folder, policy
+    );
+    let folder = state.recordings_scope.resolve(Path::new(&output_folder))?;
+
+    // Compression reads whole recordings, keep it off the async runtime
+    let report = tokio::task::spawn_blocking(move || {
+        let state = app_handle.state::<AppData>();
+        let active_id = state.session_recording_id();
+        apply_retention(
+            &folder,
+            &policy,
+            &state.recording_keys,
+            active_id.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| format!("Cleanup task failed: {}", e))?
+    .map_err(|e| format!("Failed to clean up recordings: {}", e))?;
+    Ok(report)
+}
+
+/// Retention settings the background cleanup applies, or None if it is off
+#[tauri::command]
+pub async fn get_recording_retention(
+    state: State<'_, AppData>,
+) -> Result<Option<RetentionSettings>> {
+    Ok(state.retention.get())
+}
+
+/// Save the retention settings the background cleanup applies every hour.
+/// Pass None to turn background cleanup off.
+#[tauri::command]
+pub async fn set_recording_retention(
+    settings: Option<RetentionSettings>,
+    state: State<'_, AppData>,
+) -> CommandResult<()> {
+    let settings = match settings {
+        Some(mut settings) => {
+            settings.output_folder = state.recordings_scope.resolve(&settings.output_folder)?;
+            Some(settings)
+        }
+        None => None,
+    };
+    state
+        .retention
+        .set(settings)
+        .map_err(|e| format!("Failed to save retention settings: {}", e).into())
+}
+
+/// Let the user pick a recordings folder with the native dialog and add it to the scope.
+/// Returns None if the dialog was cancelled.
+#[tauri::command]
+pub async fn select_recordings_folder(
+    state: State<'_, AppData>,
+    app_handle: tauri::AppHandle,
+) -> Result<Option<String>> {
+    let dialog = app_handle
+        .dialog()
+        .file()
+        .set_title("Select Recording Output Folder");
+    let Some(picked) = pick_folder(dialog).await else {
+        return Ok(None);
+    };
+
+    let folder = picked
+        .into_path()
+        .map_err(|e| format!("Invalid folder selection: {}", e))?;
+    info!("User selected recordings folder: {:?}", folder);
+
+    state
+        .recordings_scope
+        .allow_user_folder(folder.clone())
+        .map_err(|e| format!("Failed to save recordings folder: {}", e))?;
+
+    Ok(Some(folder.to_string_lossy().to_string()))
+}
+
+/// Allow the output folder saved in settings by earlier versions, which didn't keep
+/// track of picked folders. Only has an effect once; returns whether it was allowed.
+#[tauri::command]
+pub async fn migrate_recordings_folder(
+    saved_folder: Option<String>,
+    state: State<'_, AppData>,
+) -> Result<bool> {
+    state
+        .recordings_scope
+        .migrate_saved_folder(saved_folder.map(PathBuf::from))
+        .map_err(|e| format!("Failed to save recordings folder: {}", e))
+}
+
+#[tauri::command]
+pub async fn test_recording_device(
+    device_identifier: String,
+    duration_seconds: Option<f32>,
+    sample_rate: Option<u32>,
+    include_audio: Option<bool>,
+    state: State<'_, AppData>,
+) -> CommandResult<DeviceTestResult> {
+    info!(
+        "Testing recording device: device={}, duration={:?}, sample_rate={:?}",
+        device_identifier, duration_seconds, sample_rate
+    );
+    let device_identifier = resolve_device_identifier(&state, &device_identifier)?;
+
+    // Capture blocks for the whole test, keep it off the async runtime
+    let result = tokio::task::spawn_blocking(move || {
+        test_device(
+            &device_identifier,
+            duration_seconds.unwrap_or(3.0),
+            sample_rate,
+            include_audio.unwrap_or(false),
+        )
+    })
+    .await
+    .map_err(|e| format!("Device test task failed: {}", e))??;
+    Ok(result)
 }
 
 #[tauri::command]
@@ -83,6 +595,95 @@ pub async fn stop_recording(state: State<'_, AppData>) -> Result<AudioRecording>
     recorder.stop_recording()
 }
 
+#[tauri::command]
+pub async fn add_recording_marker(
+    label: String,
+    state: State<'_, AppData>,
+) -> Result<RecordingMarker> {
+    debug!("Adding recording marker: {}", label);
+    let mut recorder = state
+        .recorder
+        .lock()
+        .map_err(|e| format!("Failed to lock recorder: {}", e))?;
+    recorder.add_marker(label)
+}
+
+/// Attach a sink that receives the session's audio alongside the recording file
+#[tauri::command]
+pub async fn add_recording_sink(
+    sink: SinkRequest,
+    state: State<'_, AppData>,
+    app_handle: tauri::AppHandle,
+) -> Result<SinkId> {
+    info!("Adding recording sink: {:?}", sink);
+    let lock_recorder = || {
+        state
+            .recorder
+            .lock()
+            .map_err(|e| format!("Failed to lock recorder: {}", e))
+    };
+
+    match sink {
+        SinkRequest::LevelMeter => {
+            let on_level: LevelHandler = Arc::new(move |level: AudioLevel| {
+                let _ = app_handle.emit(LEVEL_EVENT, level);
+            });
+            lock_recorder()?.add_sink(Box::new(LevelMeter::new(Some(on_level))))
+        }
+        SinkRequest::VoiceActivity => {
+            let on_activity: VoiceActivityHandler = Arc::new(move |activity: VoiceActivity| {
+                let _ = app_handle.emit(VOICE_ACTIVITY_EVENT, activity);
+            });
+            lock_recorder()?.add_sink(Box::new(VoiceActivityDetector::new(Some(on_activity))))
+        }
+        SinkRequest::Network { address } => {
+            // Don't connect at all for an encrypted session, the recorder refuses it anyway
+            if lock_recorder()?.is_encrypted() {
+                return Err("Encrypted recordings can't be streamed over the network".to_string());
+            }
+            let sink = NetworkSink::connect(&address)
+                .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+            lock_recorder()?.add_network_sink(sink)
+        }
+        SinkRequest::Memory { max_seconds } => lock_recorder()?.add_memory_sink(max_seconds),
+    }
+}
+
+/// Detach a sink added with `add_recording_sink`
+#[tauri::command]
+pub async fn remove_recording_sink(id: SinkId, state: State<'_, AppData>) -> Result<()> {
+    info!("Removing recording sink {}", id);
+    let mut recorder = state
+        .recorder
+        .lock()
+        .map_err(|e| format!("Failed to lock recorder: {}", e))?;
+    recorder.remove_sink(id)
+}
+
+/// Sinks attached to the open session, the recording file included
+#[tauri::command]
+pub async fn list_recording_sinks(state: State<'_, AppData>) -> Result<Vec<SinkInfo>> {
+    let recorder = state
+        .recorder
+        .lock()
+        .map_err(|e| format!("Failed to lock recorder: {}", e))?;
+    Ok(recorder
+        .list_sinks()
+        .into_iter()
+        .map(|(id, name)| SinkInfo { id, name })
+        .collect())
+}
+
+/// Interleaved samples collected by a memory sink so far
+#[tauri::command]
+pub async fn read_memory_sink(id: SinkId, state: State<'_, AppData>) -> Result<Vec<f32>> {
+    let recorder = state
+        .recorder
+        .lock()
+        .map_err(|e| format!("Failed to lock recorder: {}", e))?;
+    recorder.memory_sink_samples(id)
+}
+
 #[tauri::command]
 pub async fn cancel_recording(state: State<'_, AppData>) -> Result<()> {
     info!("Cancelling recording");
@@ -0,0 +1,320 @@
+use crate::recorder::recorder::{BufferSizeRequest, Result};
+use crate::recorder::source::{resolve_buffer_size, AudioSource, SampleCallback, SourceFormat};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, SampleFormat, SizedSample, Stream, SupportedBufferSize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use tracing::{error, info};
+
+/// Audio input device opened through CPAL
+pub struct CpalSource {
+    name: String,
+    device: Device,
+    stream_config: cpal::StreamConfig,
+    sample_format: SampleFormat,
+    format: SourceFormat,
+    stream_holder: Option<StreamHolder>,
+}
+
+impl CpalSource {
+    /// Find the device and negotiate the best configuration for voice recording
+    pub fn open(
+        device_name: &str,
+        preferred_sample_rate: Option<u32>,
+        buffer_size: BufferSizeRequest,
+    ) -> Result<Self> {
+        let host = cpal::default_host();
+        let device = find_device(&host, device_name)?;
+        let name = device.name().unwrap_or_else(|_| device_name.to_string());
+
+        // Get optimal config for voice with optional preferred sample rate
+        let config = get_optimal_config(&device, preferred_sample_rate)?;
+        let sample_format = config.sample_format();
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels();
+
+        // Validate the requested buffer size against what the device supports
+        let supported_buffer_range = match config.buffer_size() {
+            SupportedBufferSize::Range { min, max } => Some((*min, *max)),
+            SupportedBufferSize::Unknown => None,
+        };
+        let buffer_frames = resolve_buffer_size(buffer_size, supported_buffer_range, sample_rate)?;
+
+        let stream_config = cpal::StreamConfig {
+            channels,
+            sample_rate: cpal::SampleRate(sample_rate),
+            buffer_size: match buffer_frames {
+                Some(frames) => cpal::BufferSize::Fixed(frames),
+                None => cpal::BufferSize::Default,
+            },
+        };
+
+        info!(
+            "Opened input device '{}': {} Hz, {} channels, {:?}, buffer: {:?} frames",
+            name, sample_rate, channels, sample_format, buffer_frames
+        );
+
+        Ok(Self {
+            name,
+            device,
+            stream_config,
+            sample_format,
+            format: SourceFormat {
+                sample_rate,
+                channels,
+                sample_format: sample_format.to_string(),
+                buffer_frames,
+                supported_buffer_range,
+            },
+            stream_holder: None,
+        })
+    }
+}
+
+impl AudioSource for CpalSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn format(&self) -> &SourceFormat {
+        &self.format
+    }
+
+    fn start(&mut self, on_samples: SampleCallback) -> Result<()> {
+        self.stop();
+
+        let device = self.device.clone();
+        let stream_config = self.stream_config.clone();
+        let sample_format = self.sample_format;
+
+        // Create the stream holder with a closure that builds the stream.
+        // Integer samples are scaled by 2^15 so they stay exact 16-bit values as floats.
+        let stream_holder = StreamHolder::new(move || match sample_format {
+            SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, on_samples, |s| s),
+            SampleFormat::I16 => {
+                build_stream::<i16>(&device, &stream_config, on_samples, |s| s as f32 / 32768.0)
+            }
+            SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, on_samples, |s| {
+                (s as f32 - 32768.0) / 32768.0
+            }),
+            _ => Err("Unsupported sample format".to_string()),
+        })?;
+
+        self.stream_holder = Some(stream_holder);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        // Stop and drop the stream holder
+        if let Some(mut holder) = self.stream_holder.take() {
+            holder.stop();
+        }
+    }
+}
+
+impl Drop for CpalSource {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Minimal wrapper to handle the Stream in its own thread
+/// This is necessary because CPAL streams aren't Send+Sync on macOS
+struct StreamHolder {
+    thread: Option<JoinHandle<()>>,
+    should_stop: Arc<AtomicBool>,
+}
+
+impl StreamHolder {
+    fn new<F>(create_stream: F) -> Result<Self>
+    where
+        F: FnOnce() -> Result<Stream> + Send + 'static,
+    {
+        let should_stop = Arc::new(AtomicBool::new(false));
+        let should_stop_clone = should_stop.clone();
+        let (ready_tx, ready_rx) = mpsc::channel::<Result<()>>();
+
+        // Create and run the stream in its own thread
+        let thread = thread::spawn(move || {
+            // Create the stream in this thread
+            let stream = match create_stream() {
+                Ok(s) => {
+                    let _ = ready_tx.send(Ok(()));
+                    s
+                }
+                Err(e) => {
+                    error!("Failed to create stream in thread: {}", e);
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            // Keep the stream alive until told to stop
+            while !should_stop_clone.load(Ordering::Acquire) {
+                thread::sleep(std::time::Duration::from_millis(100));
+            }
+            // Stream drops here, which stops it
+            drop(stream);
+        });
+
+        // Wait for the stream to be built so configuration errors (e.g. an
+        // unsupported fixed buffer size) reach the caller instead of the log
+        match ready_rx.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                return Err(e);
+            }
+            Err(_) => {
+                let _ = thread.join();
+                return Err("Stream thread exited before the stream was created".to_string());
+            }
+        }
+
+        Ok(Self {
+            thread: Some(thread),
+            should_stop,
+        })
+    }
+
+    fn stop(&mut self) {
+        self.should_stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for StreamHolder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Find a recording device by name
+fn find_device(host: &cpal::Host, device_name: &str) -> Result<Device> {
+    // Handle "default" device
+    if device_name.to_lowercase() == "default" {
+        return host
+            .default_input_device()
+            .ok_or_else(|| "No default input device available".to_string());
+    }
+
+    // Find specific device
+    let devices: Vec<_> = host.input_devices().map_err(|e| e.to_string())?.collect();
+
+    for device in devices {
+        if let Ok(name) = device.name() {
+            if name == device_name {
+                return Ok(device);
+            }
+        }
+    }
+
+    Err(format!("Device '{}' not found", device_name))
+}
+
+/// Get optimal configuration for voice recording
+fn get_optimal_config(
+    device: &Device,
+    preferred_sample_rate: Option<u32>,
+) -> Result<cpal::SupportedStreamConfig> {
+    // Use preferred sample rate or default to 16kHz for voice
+    let target_sample_rate = preferred_sample_rate.unwrap_or(16000);
+
+    let configs: Vec<_> = device
+        .supported_input_configs()
+        .map_err(|e| e.to_string())?
+        .collect();
+
+    if configs.is_empty() {
+        return Err("No supported input configurations".to_string());
+    }
+
+    // Try to find mono config with target sample rate
+    for config in &configs {
+        if config.channels() == 1 {
+            let min_rate = config.min_sample_rate().0;
+            let max_rate = config.max_sample_rate().0;
+            if min_rate <= target_sample_rate && max_rate >= target_sample_rate {
+                return Ok(config.with_sample_rate(cpal::SampleRate(target_sample_rate)));
+            }
+        }
+    }
+
+    // Try stereo with target sample rate if mono not available
+    for config in &configs {
+        let min_rate = config.min_sample_rate().0;
+        let max_rate = config.max_sample_rate().0;
+        if min_rate <= target_sample_rate && max_rate >= target_sample_rate {
+            return Ok(config.with_sample_rate(cpal::SampleRate(target_sample_rate)));
+        }
+    }
+
+    // If target rate not supported, try to find closest rate
+    let mut best_config = None;
+    let mut best_diff = u32::MAX;
+
+    for config in &configs {
+        // Prefer mono
+        if config.channels() == 1 {
+            let min_rate = config.min_sample_rate().0;
+            let max_rate = config.max_sample_rate().0;
+
+            // Find closest supported rate
+            let closest_rate = if target_sample_rate < min_rate {
+                min_rate
+            } else if target_sample_rate > max_rate {
+                max_rate
+            } else {
+                target_sample_rate
+            };
+
+            let diff = (closest_rate as i32 - target_sample_rate as i32).abs() as u32;
+            if diff < best_diff {
+                best_diff = diff;
+                best_config = Some(config.with_sample_rate(cpal::SampleRate(closest_rate)));
+            }
+        }
+    }
+
+    // Return best config or fall back to default
+    best_config
+        .or_else(|| device.default_input_config().ok())
+        .ok_or_else(|| "Failed to find suitable audio configuration".to_string())
+}
+
+/// Build and start an input stream that converts samples to f32 for the callback
+fn build_stream<T>(
+    device: &Device,
+    config: &cpal::StreamConfig,
+    mut on_samples: SampleCallback,
+    to_f32: fn(T) -> f32,
+) -> Result<Stream>
+where
+    T: SizedSample + 'static,
+{
+    let err_fn = |err| error!("Audio stream error: {}", err);
+    let mut converted = Vec::new();
+
+    let stream = device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &_| {
+                converted.clear();
+                converted.extend(data.iter().map(|&s| to_f32(s)));
+                on_samples(&converted);
+            },
+            err_fn,
+            None,
+        )
+        .map_err(|e| format!("Failed to build stream: {}", e))?;
+
+    // Start the stream immediately
+    stream
+        .play()
+        .map_err(|e| format!("Failed to start stream: {}", e))?;
+
+    Ok(stream)
+}
@@ -0,0 +1,173 @@
+use crate::recorder::quality::{SignalAnalyzer, SignalQuality};
+use crate::recorder::recorder::{BufferSizeRequest, Result};
+use crate::recorder::source::open_source;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::info;
+
+/// Sample rate requested when the caller doesn't specify one (same as recording sessions)
+const DEFAULT_TEST_SAMPLE_RATE: u32 = 16000;
+
+/// Longest capture allowed for a self-test
+const MAX_TEST_SECONDS: f32 = 10.0;
+
+/// Fraction of clipped samples above which clipping is reported
+const CLIPPING_RATIO_THRESHOLD: f32 = 0.001;
+
+/// Peak level below which the input is considered silent
+const SILENT_PEAK_DBFS: f32 = -60.0;
+
+/// Problems found while testing a device
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum DeviceProblem {
+    /// The stream delivered no samples at all
+    NoAudioReceived,
+    /// Every sample was digital zero (muted device or denied permission)
+    DeadInput,
+    /// Input is present but far too quiet to be speech
+    #[serde(rename_all = "camelCase")]
+    Silent { peak_dbfs: f32 },
+    /// Too many samples hit full scale
+    #[serde(rename_all = "camelCase")]
+    Clipping { clipping_ratio: f32 },
+    /// The device could not be opened at the requested sample rate
+    #[serde(rename_all = "camelCase")]
+    SampleRateMismatch { requested: u32, actual: u32 },
+}
+
+/// Result of a microphone self-test - returned to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceTestResult {
+    pub device_name: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: String,
+    pub duration_seconds: f32,
+    pub signal_quality: SignalQuality,
+    pub problems: Vec<DeviceProblem>,
+    pub audio_data: Vec<f32>, // Interleaved samples, empty unless requested
+}
+
+/// Samples and statistics collected by the capture callback
+struct Capture {
+    analyzer: SignalAnalyzer,
+    samples: Vec<f32>,
+    keep_audio: bool,
+}
+
+impl Capture {
+    fn push(&mut self, sample: f32) {
+        self.analyzer.push(sample);
+        if self.keep_audio {
+            self.samples.push(sample);
+        }
+    }
+}
+
+/// Open a device, capture a few seconds into memory and report what was heard.
+/// Blocks for the duration of the capture; no recording session or file is created.
+pub fn test_device(
+    device_name: &str,
+    duration_seconds: f32,
+    preferred_sample_rate: Option<u32>,
+    include_audio: bool,
+) -> Result<DeviceTestResult> {
+    let duration_seconds = duration_seconds.clamp(0.1, MAX_TEST_SECONDS);
+
+    let mut source = open_source(
+        device_name,
+        preferred_sample_rate,
+        BufferSizeRequest::Default,
+    )?;
+    let resolved_name = source.name().to_string();
+    let format = source.format().clone();
+    let sample_rate = format.sample_rate;
+    let channels = format.channels;
+
+    let expected_samples = (sample_rate as f32 * duration_seconds) as usize * channels as usize;
+    let capture = Arc::new(Mutex::new(Capture {
+        analyzer: SignalAnalyzer::new(sample_rate, channels),
+        samples: Vec::with_capacity(if include_audio { expected_samples } else { 0 }),
+        keep_audio: include_audio,
+    }));
+
+    info!(
+        "Testing device '{}': {} Hz, {} channels, {}, {:.1}s",
+        resolved_name, sample_rate, channels, format.sample_format, duration_seconds
+    );
+
+    let capture_clone = capture.clone();
+    source.start(Box::new(move |data: &[f32]| {
+        if let Ok(mut c) = capture_clone.lock() {
+            for &sample in data {
+                c.push(sample);
+            }
+        }
+    }))?;
+    thread::sleep(Duration::from_secs_f32(duration_seconds));
+    source.stop();
+
+    let mut capture = capture
+        .lock()
+        .map_err(|e| format!("Failed to lock capture buffer: {}", e))?;
+    let signal_quality = capture.analyzer.quality();
+    let audio_data = std::mem::take(&mut capture.samples);
+
+    let requested_sample_rate = preferred_sample_rate.unwrap_or(DEFAULT_TEST_SAMPLE_RATE);
+    let problems = detect_problems(&signal_quality, requested_sample_rate, sample_rate);
+
+    info!(
+        "Device test finished for '{}': {} samples, problems: {:?}",
+        resolved_name, signal_quality.total_samples, problems
+    );
+
+    Ok(DeviceTestResult {
+        device_name: resolved_name,
+        sample_rate,
+        channels,
+        sample_format: format.sample_format,
+        duration_seconds: signal_quality.total_samples as f32
+            / (sample_rate as f32 * channels as f32),
+        signal_quality,
+        problems,
+        audio_data,
+    })
+}
+
+/// Turn signal statistics into a list of user-facing problems
+fn detect_problems(
+    quality: &SignalQuality,
+    requested_sample_rate: u32,
+    actual_sample_rate: u32,
+) -> Vec<DeviceProblem> {
+    let mut problems = Vec::new();
+
+    if quality.total_samples == 0 {
+        problems.push(DeviceProblem::NoAudioReceived);
+    } else if quality.dead_input {
+        problems.push(DeviceProblem::DeadInput);
+    } else if quality.peak_dbfs < SILENT_PEAK_DBFS {
+        problems.push(DeviceProblem::Silent {
+            peak_dbfs: quality.peak_dbfs,
+        });
+    }
+
+    if quality.clipping_ratio > CLIPPING_RATIO_THRESHOLD {
+        problems.push(DeviceProblem::Clipping {
+            clipping_ratio: quality.clipping_ratio,
+        });
+    }
+
+    if requested_sample_rate != actual_sample_rate {
+        problems.push(DeviceProblem::SampleRateMismatch {
+            requested: requested_sample_rate,
+            actual: actual_sample_rate,
+        });
+    }
+
+    problems
+}
@@ -0,0 +1,942 @@
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{info, warn};
+
+/// Extension used for encrypted recordings instead of `wav`
+pub const ENCRYPTED_EXTENSION: &str = "wav.enc";
+
+/// Keychain service and account the recordings key is stored under
+const KEYCHAIN_SERVICE: &str = "com.bradenwong.whispering";
+const KEYCHAIN_ACCOUNT: &str = "recordings-key";
+
+/// File in the app data dir that held the recordings key before it moved to the keychain
+const LEGACY_KEY_FILE: &str = "recordings.key";
+
+/// Magic bytes at the start of an encrypted recording
+const MAGIC: &[u8; 8] = b"WSPRENC1";
+
+const KEY_LEN: usize = 32;
+const FILE_ID_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+
+/// Plaintext bytes per encrypted segment
+const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Plaintext of the header block: content length (u64), segment size (u32), reserved (u32)
+const HEADER_PLAINTEXT_LEN: usize = 16;
+
+const PREFIX_LEN: u64 = (MAGIC.len() + FILE_ID_LEN) as u64;
+const HEADER_LEN: u64 = PREFIX_LEN + (NONCE_LEN + HEADER_PLAINTEXT_LEN + TAG_LEN) as u64;
+const SEGMENT_SLOT_LEN: u64 = (NONCE_LEN + SEGMENT_SIZE + TAG_LEN) as u64;
+
+/// Symmetric key used to encrypt recordings at rest
+#[derive(Clone)]
+pub struct RecordingKey([u8; KEY_LEN]);
+
+impl RecordingKey {
+    /// Generate a new random key
+    pub fn generate() -> Self {
+        let mut key = [0u8; KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+}
+
+// Never print key material
+impl fmt::Debug for RecordingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecordingKey(..)")
+    }
+}
+
+/// Holds the app's recordings key, created on first use in the OS keychain
+pub struct RecordingKeyStore {
+    legacy_key_file: RwLock<Option<PathBuf>>,
+    key: Mutex<Option<RecordingKey>>,
+}
+
+impl RecordingKeyStore {
+    pub fn new() -> Self {
+        Self {
+            legacy_key_file: RwLock::new(None),
+            key: Mutex::new(None),
+        }
+    }
+
+    /// A store holding `key`, without touching the keychain
+    #[cfg(test)]
+    pub(crate) fn with_key(key: RecordingKey) -> Self {
+        let store = Self::new();
+        *store.key.lock().unwrap() = Some(key);
+        store
+    }
+
+    /// Point the store at the key file older versions kept in the app data dir
+    pub fn init<R: Runtime>(&self, app: &AppHandle<R>) {
+        match app.path().app_data_dir() {
+            Ok(dir) => {
+                if let Ok(mut file) = self.legacy_key_file.write() {
+                    *file = Some(dir.join(LEGACY_KEY_FILE));
+                }
+            }
+            Err(e) => warn!("Failed to resolve app data dir for recordings key: {}", e),
+        }
+    }
+
+    /// Get the recordings key, loading it or creating a new one if none exists yet
+    pub fn key(&self) -> io::Result<RecordingKey> {
+        let mut cached = self
+            .key
+            .lock()
+            .map_err(|_| io::Error::other("Recordings key lock poisoned"))?;
+        if let Some(key) = cached.as_ref() {
+            return Ok(key.clone());
+        }
+
+        let file = self
+            .legacy_key_file
+            .read()
+            .ok()
+            .and_then(|f| f.clone())
+            .ok_or_else(|| io::Error::other("Recordings key store is not initialized"))?;
+        let key = load_or_create_key(&file)?;
+        *cached = Some(key.clone());
+        Ok(key)
+    }
+
+    /// Open a recording for reading, decrypting it if it is an encrypted container
+    pub fn open_recording(&self, path: &Path) -> io::Result<RecordingFile> {
+        let mut file = File::open(path)?;
+        if !starts_with_magic(&mut file)? {
+            return Ok(RecordingFile::Plain(file));
+        }
+        let encrypted = EncryptedFile::open(file, &self.key()?)?;
+        Ok(RecordingFile::Encrypted(Box::new(encrypted)))
+    }
+
+    /// Read a whole recording into memory, decrypting it if needed
+    pub fn read_recording(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.open_recording(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Decrypt an in-memory container; other data is returned unchanged
+    pub fn decrypt_bytes(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        if !is_encrypted_container(&bytes) {
+            return Ok(bytes);
+        }
+        let mut plaintext = Vec::new();
+        EncryptedFile::open(io::Cursor::new(bytes), &self.key()?)?.read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+}
+
+/// Check whether bytes start with the encrypted recording magic
+pub fn is_encrypted_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn starts_with_magic(file: &mut File) -> io::Result<bool> {
+    let mut magic = [0u8; MAGIC.len()];
+    let is_encrypted = match file.read_exact(&mut magic) {
+        Ok(()) => &magic == MAGIC,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => false,
+        Err(e) => return Err(e),
+    };
+    file.seek(SeekFrom::Start(0))?;
+    Ok(is_encrypted)
+}
+
+/// Load the key from the OS keychain (macOS Keychain, Windows Credential Manager or the
+/// Secret Service on Linux), creating it there on first use. A key file left by an older
+/// version is moved into the keychain. Without a keychain an existing key file is still
+/// used so its recordings stay readable, but no new key is ever written to disk.
+fn load_or_create_key(legacy_file: &Path) -> io::Result<RecordingKey> {
+    load_or_create_key_with(
+        legacy_file,
+        || keychain(|entry| entry.get_secret()),
+        |secret| keychain(move |entry| entry.set_secret(&secret)),
+    )
+}
+
+/// `load_or_create_key` against any keychain, given as its get and set operations
+fn load_or_create_key_with(
+    legacy_file: &Path,
+    get_secret: impl FnOnce() -> io::Result<keyring::Result<Vec<u8>>>,
+    set_secret: impl FnOnce([u8; KEY_LEN]) -> io::Result<keyring::Result<()>>,
+) -> io::Result<RecordingKey> {
+    match get_secret()? {
+        Ok(secret) => {
+            let key = key_from_bytes(secret, "Recordings key in the keychain")?;
+            // Finish a migration that stopped before the file was deleted
+            if read_legacy_key(legacy_file)?.is_some_and(|legacy| legacy.0 == key.0) {
+                remove_legacy_key(legacy_file);
+            }
+            Ok(key)
+        }
+        Err(keyring::Error::NoEntry) => {
+            let legacy = read_legacy_key(legacy_file)?;
+            let migrated = legacy.is_some();
+            let key = legacy.unwrap_or_else(RecordingKey::generate);
+
+            set_secret(key.0)?.map_err(|e| {
+                io::Error::other(format!(
+                    "Failed to store recordings key in the keychain: {}",
+                    e
+                ))
+            })?;
+
+            if migrated {
+                remove_legacy_key(legacy_file);
+                info!(
+                    "Moved recordings key from {:?} to the keychain",
+                    legacy_file
+                );
+            } else {
+                info!("Created recordings key in the keychain");
+            }
+            Ok(key)
+        }
+        Err(e) => match read_legacy_key(legacy_file)? {
+            Some(key) => {
+                warn!(
+                    "Keychain unavailable ({}), using recordings key file {:?}",
+                    e, legacy_file
+                );
+                Ok(key)
+            }
+            None => Err(io::Error::other(format!(
+                "Can't create a recordings key, the keychain is unavailable: {}",
+                e
+            ))),
+        },
+    }
+}
+
+/// Run a keychain operation on its own thread. The Linux backend blocks on an internal
+/// async runtime, which panics when called from a tokio worker.
+fn keychain<T: Send + 'static>(
+    op: impl FnOnce(&keyring::Entry) -> keyring::Result<T> + Send + 'static,
+) -> io::Result<keyring::Result<T>> {
+    std::thread::spawn(move || {
+        keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT).and_then(|entry| op(&entry))
+    })
+    .join()
+    .map_err(|_| io::Error::other("Keychain access panicked"))
+}
+
+fn read_legacy_key(file: &Path) -> io::Result<Option<RecordingKey>> {
+    match fs::read(file) {
+        Ok(bytes) => key_from_bytes(bytes, &format!("Recordings key file {:?}", file)).map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn remove_legacy_key(file: &Path) {
+    if let Err(e) = fs::remove_file(file) {
+        warn!("Failed to remove recordings key file {:?}: {}", file, e);
+    }
+}
+
+fn key_from_bytes(bytes: Vec<u8>, source: &str) -> io::Result<RecordingKey> {
+    let key: [u8; KEY_LEN] = bytes
+        .try_into()
+        .map_err(|_| invalid_data(format!("{} is corrupted", source)))?;
+    Ok(RecordingKey(key))
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Random-access authenticated encryption over a file.
+///
+/// Layout: magic, random file id, sealed header (content length), then fixed-size
+/// segments of `SEGMENT_SIZE` plaintext bytes, each sealed with its own random
+/// XChaCha20-Poly1305 nonce. The file id and segment index are authenticated with
+/// every block, so segments can't be swapped between positions or files. Only the
+/// segment being read or written is held in memory as plaintext.
+pub struct EncryptedFile<F: Read + Write + Seek> {
+    inner: F,
+    cipher: XChaCha20Poly1305,
+    file_id: [u8; FILE_ID_LEN],
+    len: u64,
+    pos: u64,
+    stored_segments: u64,
+    segment: Vec<u8>,
+    segment_index: Option<u64>,
+    segment_dirty: bool,
+    header_dirty: bool,
+}
+
+impl<F: Read + Write + Seek> EncryptedFile<F> {
+    /// Start a new, empty container (overwrites whatever `inner` holds)
+    pub fn create(mut inner: F, key: &RecordingKey) -> io::Result<Self> {
+        let mut file_id = [0u8; FILE_ID_LEN];
+        OsRng.fill_bytes(&mut file_id);
+
+        inner.seek(SeekFrom::Start(0))?;
+        inner.write_all(MAGIC)?;
+        inner.write_all(&file_id)?;
+
+        let mut file = Self::from_parts(inner, key, file_id, 0);
+        file.write_header()?;
+        Ok(file)
+    }
+
+    /// Open an existing container, verifying its header
+    pub fn open(mut inner: F, key: &RecordingKey) -> io::Result<Self> {
+        let total_len = inner.seek(SeekFrom::End(0))?;
+        if total_len < HEADER_LEN {
+            return Err(invalid_data(
+                "File is too short to be an encrypted recording",
+            ));
+        }
+
+        inner.seek(SeekFrom::Start(0))?;
+        let mut prefix = [0u8; PREFIX_LEN as usize];
+        inner.read_exact(&mut prefix)?;
+        if !is_encrypted_container(&prefix) {
+            return Err(invalid_data("Not an encrypted recording"));
+        }
+        let mut file_id = [0u8; FILE_ID_LEN];
+        file_id.copy_from_slice(&prefix[MAGIC.len()..]);
+
+        let mut sealed = vec![0u8; (HEADER_LEN - PREFIX_LEN) as usize];
+        inner.read_exact(&mut sealed)?;
+
+        let stored_segments = (total_len - HEADER_LEN) / SEGMENT_SLOT_LEN;
+        let mut file = Self::from_parts(inner, key, file_id, stored_segments);
+        file.header_dirty = false; // Nothing to write back for a file that was only opened
+        let header = file.unseal(&sealed, None)?;
+
+        let len = u64::from_le_bytes(header[0..8].try_into().unwrap());
+        let segment_size = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if segment_size as usize != SEGMENT_SIZE {
+            return Err(invalid_data(format!(
+                "Unsupported encrypted segment size {}",
+                segment_size
+            )));
+        }
+        if len > stored_segments * SEGMENT_SIZE as u64 {
+            return Err(invalid_data("Encrypted recording is truncated"));
+        }
+
+        file.len = len;
+        Ok(file)
+    }
+
+    fn from_parts(
+        inner: F,
+        key: &RecordingKey,
+        file_id: [u8; FILE_ID_LEN],
+        stored_segments: u64,
+    ) -> Self {
+        Self {
+            inner,
+            cipher: key.cipher(),
+            file_id,
+            len: 0,
+            pos: 0,
+            stored_segments,
+            segment: Vec::new(),
+            segment_index: None,
+            segment_dirty: false,
+            header_dirty: true,
+        }
+    }
+
+    /// Length of the decrypted content in bytes
+    pub fn plaintext_len(&self) -> u64 {
+        self.len
+    }
+
+    /// Associated data binding a block to this file and its position (None = header)
+    fn aad(&self, segment: Option<u64>) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(PREFIX_LEN as usize + 8);
+        aad.extend_from_slice(MAGIC);
+        aad.extend_from_slice(&self.file_id);
+        // The header uses an index no segment can reach
+        aad.extend_from_slice(&segment.unwrap_or(u64::MAX).to_le_bytes());
+        aad
+    }
+
+    fn seal(&self, plaintext: &[u8], segment: Option<u64>) -> io::Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = self.aad(segment);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| io::Error::other("Failed to encrypt recording"))?;
+
+        let mut block = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        block.extend_from_slice(&nonce);
+        block.extend_from_slice(&ciphertext);
+        Ok(block)
+    }
+
+    fn unseal(&self, block: &[u8], segment: Option<u64>) -> io::Result<Vec<u8>> {
+        let (nonce, ciphertext) = block.split_at(NONCE_LEN);
+        let aad = self.aad(segment);
+        self.cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| invalid_data("Recording failed authentication (wrong key or corrupted)"))
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut header = [0u8; HEADER_PLAINTEXT_LEN];
+        header[0..8].copy_from_slice(&self.len.to_le_bytes());
+        header[8..12].copy_from_slice(&(SEGMENT_SIZE as u32).to_le_bytes());
+
+        let block = self.seal(&header, None)?;
+        self.inner.seek(SeekFrom::Start(PREFIX_LEN))?;
+        self.inner.write_all(&block)?;
+        self.header_dirty = false;
+        Ok(())
+    }
+
+    fn write_segment(&mut self, index: u64, plaintext: &[u8]) -> io::Result<()> {
+        let block = self.seal(plaintext, Some(index))?;
+        self.inner
+            .seek(SeekFrom::Start(HEADER_LEN + index * SEGMENT_SLOT_LEN))?;
+        self.inner.write_all(&block)
+    }
+
+    /// Write empty segments until `count` segments exist on disk
+    fn fill_segments(&mut self, count: u64) -> io::Result<()> {
+        if self.stored_segments >= count {
+            return Ok(());
+        }
+        let zeros = vec![0u8; SEGMENT_SIZE];
+        while self.stored_segments < count {
+            self.write_segment(self.stored_segments, &zeros)?;
+            self.stored_segments += 1;
+        }
+        Ok(())
+    }
+
+    /// Re-encrypt the buffered segment if it was modified
+    fn flush_segment(&mut self) -> io::Result<()> {
+        let Some(index) = self.segment_index else {
+            return Ok(());
+        };
+        if !self.segment_dirty {
+            return Ok(());
+        }
+
+        // Fill any gap left by seeking past the end
+        self.fill_segments(index)?;
+
+        let segment = std::mem::take(&mut self.segment);
+        let result = self.write_segment(index, &segment);
+        self.segment = segment;
+        result?;
+
+        self.stored_segments = self.stored_segments.max(index + 1);
+        self.segment_dirty = false;
+        Ok(())
+    }
+
+    /// Make `index` the buffered segment, decrypting it if it exists on disk
+    fn load_segment(&mut self, index: u64) -> io::Result<()> {
+        if self.segment_index == Some(index) {
+            return Ok(());
+        }
+        self.flush_segment()?;
+
+        if index < self.stored_segments {
+            let mut block = vec![0u8; SEGMENT_SLOT_LEN as usize];
+            self.inner
+                .seek(SeekFrom::Start(HEADER_LEN + index * SEGMENT_SLOT_LEN))?;
+            self.inner.read_exact(&mut block)?;
+            self.segment = self.unseal(&block, Some(index))?;
+        } else {
+            self.segment.clear();
+            self.segment.resize(SEGMENT_SIZE, 0);
+        }
+        self.segment_index = Some(index);
+        Ok(())
+    }
+}
+
+impl EncryptedFile<File> {
+    /// Truncate or extend the decrypted content
+    pub fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.flush_segment()?;
+
+        let segments = len.div_ceil(SEGMENT_SIZE as u64);
+        if len < self.len {
+            // Don't keep stale content past the new end in the last segment
+            let tail = (len % SEGMENT_SIZE as u64) as usize;
+            if tail != 0 {
+                self.load_segment(segments - 1)?;
+                self.segment[tail..].fill(0);
+                self.segment_dirty = true;
+                self.flush_segment()?;
+            }
+            if self.segment_index.is_some_and(|index| index >= segments) {
+                self.segment_index = None;
+            }
+            self.stored_segments = self.stored_segments.min(segments);
+            self.inner
+                .set_len(HEADER_LEN + segments * SEGMENT_SLOT_LEN)?;
+        }
+
+        self.len = len;
+        self.header_dirty = true;
+        self.flush()
+    }
+
+    /// Flush pending writes and sync the container to disk
+    pub fn sync_all(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.inner.sync_all()
+    }
+}
+
+impl<F: Read + Write + Seek> Read for EncryptedFile<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+
+        let index = self.pos / SEGMENT_SIZE as u64;
+        let offset = (self.pos % SEGMENT_SIZE as u64) as usize;
+        self.load_segment(index)?;
+
+        let remaining = (self.len - self.pos).min(buf.len() as u64) as usize;
+        let n = remaining.min(SEGMENT_SIZE - offset);
+        buf[..n].copy_from_slice(&self.segment[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<F: Read + Write + Seek> Write for EncryptedFile<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let index = self.pos / SEGMENT_SIZE as u64;
+        let offset = (self.pos % SEGMENT_SIZE as u64) as usize;
+        self.load_segment(index)?;
+
+        let n = buf.len().min(SEGMENT_SIZE - offset);
+        self.segment[offset..offset + n].copy_from_slice(&buf[..n]);
+        self.segment_dirty = true;
+        self.pos += n as u64;
+        if self.pos > self.len {
+            self.len = self.pos;
+            self.header_dirty = true;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_segment()?;
+        if self.header_dirty {
+            self.fill_segments(self.len.div_ceil(SEGMENT_SIZE as u64))?;
+            self.write_header()?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<F: Read + Write + Seek> Seek for EncryptedFile<F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative position",
+            )
+        })?;
+
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+impl<F: Read + Write + Seek> Drop for EncryptedFile<F> {
+    fn drop(&mut self) {
+        if self.segment_dirty || self.header_dirty {
+            if let Err(e) = self.flush() {
+                tracing::error!("Failed to flush encrypted recording on drop: {}", e);
+            }
+        }
+    }
+}
+
+/// A recording on disk, either a plain WAV file or an encrypted container
+pub enum RecordingFile {
+    Plain(File),
+    Encrypted(Box<EncryptedFile<File>>),
+}
+
+impl RecordingFile {
+    /// Create a new recording file, encrypted when a key is given
+    pub fn create(path: &Path, key: Option<&RecordingKey>) -> io::Result<Self> {
+        match key {
+            None => Ok(Self::Plain(File::create(path)?)),
+            Some(key) => {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)?;
+                Ok(Self::Encrypted(Box::new(EncryptedFile::create(file, key)?)))
+            }
+        }
+    }
+
+    /// Whether the file is an encrypted container
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self, Self::Encrypted(_))
+    }
+
+    /// Truncate or extend the (decrypted) content
+    pub fn set_len(&mut self, len: u64) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.set_len(len),
+            Self::Encrypted(file) => file.set_len(len),
+        }
+    }
+
+    /// Flush pending writes and sync the file to disk
+    pub fn sync_all(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.sync_all(),
+            Self::Encrypted(file) => file.sync_all(),
+        }
+    }
+}
+
+impl Read for RecordingFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.read(buf),
+            Self::Encrypted(file) => file.read(buf),
+        }
+    }
+}
+
+impl Write for RecordingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.write(buf),
+            Self::Encrypted(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.flush(),
+            Self::Encrypted(file) => file.flush(),
+        }
+    }
+}
+
+impl Seek for RecordingFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Plain(file) => file.seek(pos),
+            Self::Encrypted(file) => file.seek(pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+
+    /// Bytes that differ at every offset, so misplaced data can't pass for the original
+    fn content(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+    }
+
+    /// Encrypt `plaintext` into a new in-memory container, writing it in uneven pieces
+    fn seal_container(plaintext: &[u8], key: &RecordingKey) -> Vec<u8> {
+        let mut container = Vec::new();
+        let mut file = EncryptedFile::create(Cursor::new(&mut container), key).unwrap();
+        for piece in plaintext.chunks(10_007) {
+            file.write_all(piece).unwrap();
+        }
+        drop(file);
+        container
+    }
+
+    fn open_container(container: Vec<u8>, key: &RecordingKey) -> io::Result<Vec<u8>> {
+        let mut plaintext = Vec::new();
+        EncryptedFile::open(Cursor::new(container), key)?.read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    fn slot(index: u64) -> std::ops::Range<usize> {
+        let start = (HEADER_LEN + index * SEGMENT_SLOT_LEN) as usize;
+        start..start + SEGMENT_SLOT_LEN as usize
+    }
+
+    #[test]
+    fn round_trips_across_segment_boundaries() {
+        let key = RecordingKey::generate();
+        for len in [
+            0,
+            1,
+            SEGMENT_SIZE - 1,
+            SEGMENT_SIZE,
+            SEGMENT_SIZE * 2 + 12_345,
+        ] {
+            let plaintext = content(len);
+            let container = seal_container(&plaintext, &key);
+            assert_eq!(
+                container.len() as u64,
+                HEADER_LEN + len.div_ceil(SEGMENT_SIZE) as u64 * SEGMENT_SLOT_LEN
+            );
+            assert_eq!(
+                open_container(container, &key).unwrap(),
+                plaintext,
+                "{}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn reads_and_writes_at_any_offset() {
+        let key = RecordingKey::generate();
+        let mut plaintext = content(SEGMENT_SIZE * 3);
+        let mut container = seal_container(&plaintext, &key);
+
+        // Overwrite a range straddling the first segment boundary
+        let patch = [0xAB; 100];
+        let at = SEGMENT_SIZE - 50;
+        {
+            let mut file = EncryptedFile::open(Cursor::new(&mut container), &key).unwrap();
+            file.seek(SeekFrom::Start(at as u64)).unwrap();
+            file.write_all(&patch).unwrap();
+        }
+        plaintext[at..at + patch.len()].copy_from_slice(&patch);
+
+        let mut file = EncryptedFile::open(Cursor::new(container), &key).unwrap();
+        let mut read = vec![0u8; 200];
+        file.seek(SeekFrom::End(-(SEGMENT_SIZE as i64) * 2 - 100))
+            .unwrap();
+        file.read_exact(&mut read).unwrap();
+        assert_eq!(read, plaintext[at - 50..at + 150]);
+    }
+
+    #[test]
+    fn rejects_tampered_segments() {
+        let key = RecordingKey::generate();
+        let container = seal_container(&content(SEGMENT_SIZE * 3), &key);
+
+        let mut tampered = container.clone();
+        tampered[slot(1).start + NONCE_LEN + 10] ^= 1;
+        let error = open_container(tampered, &key).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut tampered_header = container.clone();
+        tampered_header[PREFIX_LEN as usize + NONCE_LEN] ^= 1;
+        assert!(open_container(tampered_header, &key).is_err());
+
+        let mut other_id = container.clone();
+        other_id[MAGIC.len()] ^= 1;
+        assert!(open_container(other_id, &key).is_err());
+
+        assert!(open_container(container, &RecordingKey::generate()).is_err());
+    }
+
+    #[test]
+    fn rejects_swapped_and_foreign_segments() {
+        let key = RecordingKey::generate();
+        let container = seal_container(&content(SEGMENT_SIZE * 3), &key);
+
+        let mut swapped = container.clone();
+        let first = container[slot(0)].to_vec();
+        swapped.copy_within(slot(1), slot(0).start);
+        swapped[slot(1)].copy_from_slice(&first);
+        assert!(open_container(swapped, &key).is_err());
+
+        // The same content at the same position in another file
+        let other = seal_container(&content(SEGMENT_SIZE * 3), &key);
+        let mut foreign = container.clone();
+        foreign[slot(2)].copy_from_slice(&other[slot(2)]);
+        assert!(open_container(foreign, &key).is_err());
+
+        let mut moved = container;
+        let last = moved[slot(2)].to_vec();
+        moved[slot(1)].copy_from_slice(&last);
+        assert!(open_container(moved, &key).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let key = RecordingKey::generate();
+        let container = seal_container(&content(SEGMENT_SIZE * 2 + 10), &key);
+
+        for len in [
+            0,
+            MAGIC.len(),
+            HEADER_LEN as usize - 1,
+            slot(1).end,
+            container.len() - 1,
+        ] {
+            let error = open_container(container[..len].to_vec(), &key).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", len);
+        }
+    }
+
+    #[test]
+    fn rewrites_a_file_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.wav.enc");
+        let key = RecordingKey::generate();
+        let mut plaintext = content(SEGMENT_SIZE * 2 + 500);
+
+        let mut file = RecordingFile::create(&path, Some(&key)).unwrap();
+        file.write_all(&plaintext).unwrap();
+        // Patch the start like a WAV writer updating its header sizes
+        file.seek(SeekFrom::Start(4)).unwrap();
+        file.write_all(&[1, 2, 3, 4]).unwrap();
+        plaintext[4..8].copy_from_slice(&[1, 2, 3, 4]);
+
+        // Shrink into the middle of the second segment, then grow again
+        let shrunk = SEGMENT_SIZE + 100;
+        file.set_len(shrunk as u64).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            HEADER_LEN + 2 * SEGMENT_SLOT_LEN
+        );
+        file.set_len(SEGMENT_SIZE as u64 * 3).unwrap();
+        file.sync_all().unwrap();
+        drop(file);
+        plaintext.truncate(shrunk);
+        plaintext.resize(SEGMENT_SIZE * 3, 0);
+
+        let keys = RecordingKeyStore::with_key(key);
+        assert_eq!(keys.read_recording(&path).unwrap(), plaintext);
+    }
+
+    /// Keychain that holds at most one secret, or is unavailable
+    struct FakeKeychain {
+        secret: RefCell<Option<Vec<u8>>>,
+        available: bool,
+    }
+
+    impl FakeKeychain {
+        fn new(secret: Option<[u8; KEY_LEN]>, available: bool) -> Self {
+            Self {
+                secret: RefCell::new(secret.map(Vec::from)),
+                available,
+            }
+        }
+
+        fn load(&self, legacy_file: &Path) -> io::Result<RecordingKey> {
+            load_or_create_key_with(
+                legacy_file,
+                || {
+                    Ok(match (self.available, self.secret.borrow().clone()) {
+                        (false, _) => Err(keyring::Error::NoStorageAccess("locked".into())),
+                        (true, Some(secret)) => Ok(secret),
+                        (true, None) => Err(keyring::Error::NoEntry),
+                    })
+                },
+                |secret| {
+                    *self.secret.borrow_mut() = Some(secret.to_vec());
+                    Ok(Ok(()))
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn moves_a_legacy_key_file_into_the_keychain() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy_file = dir.path().join(LEGACY_KEY_FILE);
+        let legacy = RecordingKey::generate();
+        fs::write(&legacy_file, legacy.0).unwrap();
+        let keychain = FakeKeychain::new(None, true);
+
+        assert_eq!(keychain.load(&legacy_file).unwrap().0, legacy.0);
+        assert_eq!(keychain.secret.borrow().as_deref(), Some(&legacy.0[..]));
+        assert!(!legacy_file.exists());
+        assert_eq!(keychain.load(&legacy_file).unwrap().0, legacy.0);
+    }
+
+    #[test]
+    fn finishes_an_interrupted_key_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy_file = dir.path().join(LEGACY_KEY_FILE);
+        let key = RecordingKey::generate();
+        fs::write(&legacy_file, key.0).unwrap();
+        let keychain = FakeKeychain::new(Some(key.0), true);
+
+        assert_eq!(keychain.load(&legacy_file).unwrap().0, key.0);
+        assert!(!legacy_file.exists());
+    }
+
+    #[test]
+    fn creates_keys_only_in_the_keychain() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy_file = dir.path().join(LEGACY_KEY_FILE);
+
+        let keychain = FakeKeychain::new(None, true);
+        let key = keychain.load(&legacy_file).unwrap();
+        assert_eq!(keychain.secret.borrow().as_deref(), Some(&key.0[..]));
+        assert!(!legacy_file.exists());
+
+        let unavailable = FakeKeychain::new(None, false);
+        assert!(unavailable.load(&legacy_file).is_err());
+        assert!(!legacy_file.exists());
+    }
+
+    #[test]
+    fn keeps_using_a_legacy_key_file_without_a_keychain() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy_file = dir.path().join(LEGACY_KEY_FILE);
+        let legacy = RecordingKey::generate();
+        fs::write(&legacy_file, legacy.0).unwrap();
+        let keychain = FakeKeychain::new(None, false);
+
+        assert_eq!(keychain.load(&legacy_file).unwrap().0, legacy.0);
+        assert!(legacy_file.exists());
+
+        fs::write(&legacy_file, [0u8; KEY_LEN - 1]).unwrap();
+        let error = keychain.load(&legacy_file).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
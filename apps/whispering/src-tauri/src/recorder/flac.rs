@@ -0,0 +1,974 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Frames per FLAC block
+const BLOCK_SIZE: usize = 4096;
+
+/// Highest fixed predictor order FLAC supports
+const MAX_FIXED_ORDER: usize = 4;
+
+/// Highest Rice partition order tried when coding residuals
+const MAX_PARTITION_ORDER: u32 = 8;
+
+/// Largest Rice parameter with 4-bit parameters (15 is the escape code)
+const MAX_RICE_PARAMETER: u32 = 14;
+
+/// Length of the STREAMINFO metadata block body
+const STREAMINFO_LEN: usize = 34;
+
+/// Seek points reserved in the SEEKTABLE block
+const SEEK_POINTS: usize = 512;
+
+/// Length of one seek point: sample number, byte offset, block length
+const SEEK_POINT_LEN: usize = 18;
+
+/// Initial spacing of seek points, doubled whenever the table fills up
+const SEEK_INTERVAL_SECONDS: u64 = 10;
+
+/// Most cue points a CUESHEET can hold: track numbers are 8-bit and 255 is the lead-out
+pub const MAX_CUE_POINTS: usize = 254;
+
+/// Length of the CUESHEET fields before the tracks
+const CUESHEET_HEADER_LEN: usize = 396;
+
+/// Length of one CUESHEET track without its index points
+const CUESHEET_TRACK_LEN: usize = 36;
+
+/// Length of one CUESHEET index point
+const CUESHEET_INDEX_LEN: usize = 12;
+
+/// Space reserved after the seek table by [`FlacEncoder::with_cue_sheet`] for a CUESHEET with [`MAX_CUE_POINTS`] tracks and
+/// the header of the PADDING block that fills the rest
+const CUE_SPACE: usize = 4
+    + CUESHEET_HEADER_LEN
+    + (MAX_CUE_POINTS + 1) * CUESHEET_TRACK_LEN
+    + MAX_CUE_POINTS * CUESHEET_INDEX_LEN
+    + 4;
+
+/// Minimum block size declared in STREAMINFO, the smallest FLAC allows. Decoders only
+/// treat the stream as variable-blocksize when the declared minimum is below the maximum.
+/// Only the last block may be shorter, so a short block written by a flush is rewritten
+/// together with the samples that follow it.
+const MIN_DECLARED_BLOCK_SIZE: usize = 16;
+
+/// Block shorter than [`MIN_DECLARED_BLOCK_SIZE`] written by a flush at the end of the stream
+#[derive(Debug)]
+struct ShortTail {
+    offset: u64, // Position of the frame in the writer
+    bytes: usize,
+    samples: Vec<i32>,
+    frame_bytes: (u32, u32), // Min and max frame sizes before it was written
+}
+
+/// Seek table entry pointing at the block that starts at `sample`
+#[derive(Debug, Clone, Copy)]
+struct SeekPoint {
+    sample: u64,
+    offset: u64, // Bytes from the first frame header
+    block_frames: u16,
+}
+
+/// Lossless FLAC encoder for integer PCM.
+///
+/// Uses fixed predictors with partitioned Rice coding, which gets most of the
+/// gain of full LPC for speech at a fraction of the complexity. Samples are
+/// interleaved integers in the range of `bits_per_sample`.
+///
+/// The stream uses variable block sizes, so [`flush`](Self::flush) can write out
+/// a short block at any time and encoding can continue afterwards. STREAMINFO and
+/// the seek table are rewritten in place on every flush, so the file is a complete
+/// FLAC stream up to the last flush even if it is never finished.
+pub struct FlacEncoder<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    channels: u16,
+    bits_per_sample: u16,
+    pending: Vec<i32>, // Interleaved samples not yet written as a frame
+    frame_number: u64,
+    total_frames: u64,
+    min_frame_bytes: u32,
+    max_frame_bytes: u32,
+    short_tail: Option<ShortTail>,
+    overwrite_bytes: usize, // Length of a rewound block the next frame must cover
+    streaminfo_pos: u64,
+    first_frame_pos: u64,
+    seek_points: Vec<SeekPoint>,
+    seek_interval: u64,   // In frames
+    cue_space: usize,     // Bytes reserved for the CUESHEET, zero without one
+    cue_points: Vec<u64>, // In frames
+}
+
+impl<W: Write + Seek> FlacEncoder<W> {
+    /// Write the stream header and prepare to encode
+    pub fn new(
+        writer: W,
+        sample_rate: u32,
+        channels: u16,
+        bits_per_sample: u16,
+    ) -> io::Result<Self> {
+        Self::create(writer, sample_rate, channels, bits_per_sample, false)
+    }
+
+    /// Like [`new`](Self::new), but also reserve room for a CUESHEET so cue points can
+    /// be set while encoding. Costs about 12 KB of padding.
+    pub fn with_cue_sheet(
+        writer: W,
+        sample_rate: u32,
+        channels: u16,
+        bits_per_sample: u16,
+    ) -> io::Result<Self> {
+        Self::create(writer, sample_rate, channels, bits_per_sample, true)
+    }
+
+    fn create(
+        mut writer: W,
+        sample_rate: u32,
+        channels: u16,
+        bits_per_sample: u16,
+        cue_sheet: bool,
+    ) -> io::Result<Self> {
+        if !(1..=8).contains(&channels) {
+            return Err(invalid_input(format!(
+                "FLAC supports 1-8 channels, got {}",
+                channels
+            )));
+        }
+        if !(4..=24).contains(&bits_per_sample) {
+            return Err(invalid_input(format!(
+                "FLAC encoder supports 4-24 bits per sample, got {}",
+                bits_per_sample
+            )));
+        }
+        if sample_rate == 0 || sample_rate >= 1 << 20 {
+            return Err(invalid_input(format!(
+                "Unsupported FLAC sample rate {}",
+                sample_rate
+            )));
+        }
+
+        writer.write_all(b"fLaC")?;
+        let streaminfo_pos = writer.stream_position()?;
+        let cue_space = if cue_sheet { CUE_SPACE } else { 0 };
+        let first_frame_pos = streaminfo_pos
+            + (4 + STREAMINFO_LEN + 4 + SEEK_POINTS * SEEK_POINT_LEN + cue_space) as u64;
+
+        let mut encoder = Self {
+            writer,
+            sample_rate,
+            channels,
+            bits_per_sample,
+            pending: Vec::with_capacity(BLOCK_SIZE * channels as usize),
+            frame_number: 0,
+            total_frames: 0,
+            min_frame_bytes: 0,
+            max_frame_bytes: 0,
+            short_tail: None,
+            overwrite_bytes: 0,
+            streaminfo_pos,
+            first_frame_pos,
+            seek_points: Vec::with_capacity(SEEK_POINTS),
+            seek_interval: sample_rate as u64 * SEEK_INTERVAL_SECONDS,
+            cue_space,
+            cue_points: Vec::new(),
+        };
+        encoder.write_metadata()?;
+        Ok(encoder)
+    }
+
+    /// Encode interleaved samples, writing a frame for every full block
+    pub fn write_samples(&mut self, samples: &[i32]) -> io::Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        if let Some(tail) = self.short_tail.take() {
+            self.rewind(tail)?;
+        }
+
+        let block_samples = BLOCK_SIZE * self.channels as usize;
+        for chunk in samples.chunks(block_samples) {
+            let take = (block_samples - self.pending.len()).min(chunk.len());
+            self.pending.extend_from_slice(&chunk[..take]);
+            if self.pending.len() == block_samples {
+                self.write_pending_frame()?;
+            }
+            if take < chunk.len() {
+                self.pending.extend_from_slice(&chunk[take..]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of frames (samples per channel) encoded so far, including buffered ones
+    pub fn frames_written(&self) -> u64 {
+        self.total_frames + (self.pending.len() / self.channels as usize) as u64
+    }
+
+    /// Write buffered samples as a short block and update STREAMINFO and the seek table,
+    /// leaving a complete stream on disk. Encoding can continue afterwards, and a block
+    /// shorter than FLAC allows mid-stream is rewritten when the next samples arrive.
+    pub fn flush(&mut self) -> io::Result<()> {
+        // A trailing partial frame can only come from malformed input
+        let whole = self.pending.len() - self.pending.len() % self.channels as usize;
+        self.pending.truncate(whole);
+        if !self.pending.is_empty() {
+            let offset = self.writer.stream_position()?;
+            let short = self.pending.len() < MIN_DECLARED_BLOCK_SIZE * self.channels as usize;
+            let tail = short.then(|| {
+                (
+                    self.pending.clone(),
+                    (self.min_frame_bytes, self.max_frame_bytes),
+                )
+            });
+            self.write_pending_frame()?;
+            if let Some((samples, frame_bytes)) = tail {
+                self.short_tail = Some(ShortTail {
+                    offset,
+                    bytes: (self.writer.stream_position()? - offset) as usize,
+                    samples,
+                    frame_bytes,
+                });
+            }
+        }
+        self.update_header()
+    }
+
+    /// Take back a short block at the end of the stream so its samples go into the next
+    /// block instead. The next frame is written over it.
+    fn rewind(&mut self, tail: ShortTail) -> io::Result<()> {
+        let frames = (tail.samples.len() / self.channels as usize) as u64;
+        self.writer.seek(SeekFrom::Start(tail.offset))?;
+        self.frame_number -= 1;
+        self.total_frames -= frames;
+        (self.min_frame_bytes, self.max_frame_bytes) = tail.frame_bytes;
+        if self
+            .seek_points
+            .last()
+            .is_some_and(|point| point.sample == self.total_frames)
+        {
+            self.seek_points.pop();
+        }
+        self.pending = tail.samples;
+        self.overwrite_bytes = tail.bytes;
+        Ok(())
+    }
+
+    /// Set the frames marked in the CUESHEET, written with the next header update.
+    /// Each cue point becomes a track starting at that frame.
+    pub fn set_cue_points(&mut self, frames: &[u64]) -> io::Result<()> {
+        if self.cue_space == 0 {
+            return Err(io::Error::other("FLAC stream has no room for a cue sheet"));
+        }
+        if frames.len() > MAX_CUE_POINTS {
+            return Err(invalid_input(format!(
+                "A FLAC cue sheet holds at most {} cue points",
+                MAX_CUE_POINTS
+            )));
+        }
+        self.cue_points = frames.to_vec();
+        Ok(())
+    }
+
+    /// Rewrite STREAMINFO and the seek table for the blocks written so far.
+    /// Buffered samples are left alone, so this is cheap enough to call periodically.
+    pub fn update_header(&mut self) -> io::Result<()> {
+        let end = self.writer.stream_position()?;
+        self.write_metadata()?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()
+    }
+
+    /// Flush the last partial block and write the final stream info
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Write STREAMINFO, SEEKTABLE and, if reserved, the CUESHEET with its PADDING
+    /// at the start of the stream
+    fn write_metadata(&mut self) -> io::Result<()> {
+        let mut bits = BitWriter::with_capacity(
+            8 + STREAMINFO_LEN + SEEK_POINTS * SEEK_POINT_LEN + self.cue_space,
+        );
+        bits.write(0, 1); // Not the last metadata block
+        bits.write(0, 7); // STREAMINFO
+        bits.write(STREAMINFO_LEN as u64, 24);
+        bits.write(MIN_DECLARED_BLOCK_SIZE as u64, 16); // Min block size
+        bits.write(BLOCK_SIZE as u64, 16); // Max block size
+        bits.write(self.min_frame_bytes as u64, 24);
+        bits.write(self.max_frame_bytes as u64, 24);
+        bits.write(self.sample_rate as u64, 20);
+        bits.write(self.channels as u64 - 1, 3);
+        bits.write(self.bits_per_sample as u64 - 1, 5);
+        bits.write(self.total_frames, 36);
+        bits.write(0, 64); // MD5 of the audio, zero means unknown
+        bits.write(0, 64);
+
+        bits.write((self.cue_space == 0) as u64, 1); // Last metadata block without a CUESHEET
+        bits.write(3, 7); // SEEKTABLE
+        bits.write((SEEK_POINTS * SEEK_POINT_LEN) as u64, 24);
+        for point in &self.seek_points {
+            bits.write(point.sample, 64);
+            bits.write(point.offset, 64);
+            bits.write(point.block_frames as u64, 16);
+        }
+        for _ in self.seek_points.len()..SEEK_POINTS {
+            bits.write(u64::MAX, 64); // Placeholder point
+            bits.write(0, 64);
+            bits.write(0, 16);
+        }
+
+        if self.cue_space > 0 {
+            self.write_cue_space(&mut bits);
+        }
+
+        self.writer.seek(SeekFrom::Start(self.streaminfo_pos))?;
+        self.writer.write_all(&bits.into_bytes())
+    }
+
+    /// CUESHEET, if there are cue points, and PADDING filling the rest of the reserved space
+    fn write_cue_space(&self, bits: &mut BitWriter) {
+        let mut padding = self.cue_space - 4;
+        if !self.cue_points.is_empty() {
+            let len = CUESHEET_HEADER_LEN
+                + (self.cue_points.len() + 1) * CUESHEET_TRACK_LEN
+                + self.cue_points.len() * CUESHEET_INDEX_LEN;
+            bits.write(0, 1); // Not the last metadata block
+            bits.write(5, 7); // CUESHEET
+            bits.write(len as u64, 24);
+            self.write_cuesheet(bits);
+            padding -= len + 4;
+        }
+        bits.write(1, 1); // Last metadata block
+        bits.write(1, 7); // PADDING
+        bits.write(padding as u64, 24);
+        bits.write_zeros(padding);
+    }
+
+    /// CUESHEET body with a track for every cue point and a lead-out at the end of the stream
+    fn write_cuesheet(&self, bits: &mut BitWriter) {
+        bits.write_zeros(128); // Media catalog number
+        bits.write(0, 64); // Lead-in samples, only used for CDs
+        bits.write(0, 1); // Not a CD
+        bits.write(0, 7); // Reserved
+        bits.write_zeros(258);
+        bits.write(self.cue_points.len() as u64 + 1, 8);
+
+        for (i, &offset) in self.cue_points.iter().enumerate() {
+            bits.write(offset.min(self.total_frames), 64);
+            bits.write(i as u64 + 1, 8); // Track number
+            bits.write_zeros(12); // ISRC
+            bits.write(0, 1); // Audio track
+            bits.write(0, 1); // No pre-emphasis
+            bits.write(0, 6);
+            bits.write_zeros(13);
+            bits.write(1, 8); // One index point, at the start of the track
+            bits.write(0, 64);
+            bits.write(1, 8);
+            bits.write_zeros(3);
+        }
+
+        bits.write(self.total_frames, 64); // Lead-out track
+        bits.write(255, 8);
+        bits.write_zeros(12);
+        bits.write(0, 8);
+        bits.write_zeros(13);
+        bits.write(0, 8); // No index points
+    }
+
+    /// Add a seek point for a block about to be written if it starts a new interval
+    fn add_seek_point(&mut self, block_frames: usize) -> io::Result<()> {
+        let due = self
+            .seek_points
+            .last()
+            .is_none_or(|last| self.total_frames >= last.sample + self.seek_interval);
+        if !due {
+            return Ok(());
+        }
+
+        // Out of room: keep every other point and space new ones twice as far apart
+        if self.seek_points.len() == SEEK_POINTS {
+            let mut index = 0;
+            self.seek_points.retain(|_| {
+                index += 1;
+                index % 2 == 1
+            });
+            self.seek_interval *= 2;
+            let last = self.seek_points.last().map_or(0, |p| p.sample);
+            if self.total_frames < last + self.seek_interval {
+                return Ok(());
+            }
+        }
+
+        let offset = self.writer.stream_position()? - self.first_frame_pos;
+        self.seek_points.push(SeekPoint {
+            sample: self.total_frames,
+            offset,
+            block_frames: block_frames as u16,
+        });
+        Ok(())
+    }
+
+    fn write_pending_frame(&mut self) -> io::Result<()> {
+        let block_frames = self.pending.len() / self.channels as usize;
+        self.add_seek_point(block_frames)?;
+
+        // A frame written over a rewound block must cover all of it, or its last bytes
+        // would be left behind. Verbatim holds more samples than that block had, so it does.
+        let mut bytes = self.encode_pending_frame(false);
+        if bytes.len() < self.overwrite_bytes {
+            bytes = self.encode_pending_frame(true);
+        }
+        self.overwrite_bytes = 0;
+        self.writer.write_all(&bytes)?;
+
+        let frame_len = bytes.len() as u32;
+        self.min_frame_bytes = if self.frame_number == 0 {
+            frame_len
+        } else {
+            self.min_frame_bytes.min(frame_len)
+        };
+        self.max_frame_bytes = self.max_frame_bytes.max(frame_len);
+        self.frame_number += 1;
+        self.total_frames += block_frames as u64;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn encode_pending_frame(&self, verbatim: bool) -> Vec<u8> {
+        let channels = self.channels as usize;
+        let block_frames = self.pending.len() / channels;
+        let mut bits = BitWriter::with_capacity(block_frames * channels * 2);
+
+        // Frame header
+        bits.write(0b11_1111_1111_1110, 14); // Sync code
+        bits.write(0, 1); // Reserved
+        bits.write(1, 1); // Variable block size stream
+        let block_size_code = if block_frames == BLOCK_SIZE {
+            0b1100 // 4096
+        } else {
+            0b0111 // 16-bit (size - 1) follows the header
+        };
+        bits.write(block_size_code, 4);
+        bits.write(0, 4); // Sample rate from STREAMINFO
+        bits.write(channels as u64 - 1, 4); // Independent channels
+        bits.write(0, 3); // Sample size from STREAMINFO
+        bits.write(0, 1); // Reserved
+        write_utf8_number(&mut bits, self.total_frames); // Number of the first sample
+        if block_size_code == 0b0111 {
+            bits.write(block_frames as u64 - 1, 16);
+        }
+        let header_crc = crc8(bits.bytes());
+        bits.write(header_crc as u64, 8);
+
+        // One subframe per channel
+        let mut channel = Vec::with_capacity(block_frames);
+        for c in 0..channels {
+            channel.clear();
+            channel.extend(
+                self.pending
+                    .iter()
+                    .skip(c)
+                    .step_by(channels)
+                    .map(|&s| s as i64),
+            );
+            if verbatim {
+                write_verbatim_subframe(&mut bits, &channel, self.bits_per_sample as u32, 0);
+            } else {
+                write_subframe(&mut bits, &channel, self.bits_per_sample as u32);
+            }
+        }
+
+        bits.align();
+        let frame_crc = crc16(bits.bytes());
+        bits.write(frame_crc as u64, 16);
+        bits.into_bytes()
+    }
+}
+
+/// Encode one channel of a block as the smallest of constant, fixed-predictor or verbatim
+fn write_subframe(bits: &mut BitWriter, samples: &[i64], bits_per_sample: u32) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        write_subframe_header(bits, 0b000000, 0); // CONSTANT
+        bits.write_signed(samples[0], bits_per_sample);
+        return;
+    }
+
+    // Drop low bits that are zero in every sample (e.g. 16-bit audio in a 24-bit stream)
+    let wasted = samples
+        .iter()
+        .fold(0, |acc, &s| acc | s)
+        .trailing_zeros()
+        .min(bits_per_sample - 1);
+    let shifted: Vec<i64>;
+    let (samples, bits_per_sample) = if wasted > 0 {
+        shifted = samples.iter().map(|&s| s >> wasted).collect();
+        (&shifted[..], bits_per_sample - wasted)
+    } else {
+        (samples, bits_per_sample)
+    };
+
+    let verbatim_bits = samples.len() as u64 * bits_per_sample as u64;
+
+    // Pick the fixed predictor order with the smallest residual
+    let max_order = MAX_FIXED_ORDER.min(samples.len() - 1);
+    let (order, residual) = (0..=max_order)
+        .map(|order| (order, fixed_residual(samples, order)))
+        .min_by_key(|(_, residual)| residual.iter().map(|r| r.unsigned_abs()).sum::<u64>())
+        .expect("at least one predictor order");
+
+    match best_rice_coding(&residual, samples.len(), order) {
+        Some(coding) if order as u64 * bits_per_sample as u64 + coding.bits < verbatim_bits => {
+            write_subframe_header(bits, 0b001000 | order as u64, wasted); // FIXED
+            for &warmup in &samples[..order] {
+                bits.write_signed(warmup, bits_per_sample);
+            }
+            write_residual(bits, &residual, &coding, samples.len(), order);
+        }
+        _ => write_verbatim_subframe(bits, samples, bits_per_sample, wasted),
+    }
+}
+
+/// Encode one channel of a block uncompressed, with `wasted_bits` already shifted out
+fn write_verbatim_subframe(
+    bits: &mut BitWriter,
+    samples: &[i64],
+    bits_per_sample: u32,
+    wasted_bits: u32,
+) {
+    write_subframe_header(bits, 0b000001, wasted_bits); // VERBATIM
+    for &sample in samples {
+        bits.write_signed(sample, bits_per_sample);
+    }
+}
+
+fn write_subframe_header(bits: &mut BitWriter, kind: u64, wasted_bits: u32) {
+    bits.write(0, 1); // Padding
+    bits.write(kind, 6);
+    if wasted_bits == 0 {
+        bits.write(0, 1);
+    } else {
+        bits.write(1, 1);
+        bits.write_unary(wasted_bits as u64 - 1);
+    }
+}
+
+/// Residual of the fixed polynomial predictor of the given order (excludes warm-up samples)
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<i64> {
+    samples[order..]
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let i = i + order;
+            let prediction = match order {
+                0 => 0,
+                1 => samples[i - 1],
+                2 => 2 * samples[i - 1] - samples[i - 2],
+                3 => 3 * samples[i - 1] - 3 * samples[i - 2] + samples[i - 3],
+                _ => 4 * samples[i - 1] - 6 * samples[i - 2] + 4 * samples[i - 3] - samples[i - 4],
+            };
+            s - prediction
+        })
+        .collect()
+}
+
+struct RiceCoding {
+    partition_order: u32,
+    parameters: Vec<u32>,
+    bits: u64,
+}
+
+#[inline]
+fn zigzag(r: i64) -> u64 {
+    ((r << 1) ^ (r >> 63)) as u64
+}
+
+/// Find the partition order and Rice parameters that code the residual in the fewest bits.
+/// Returns None if a partition would need a parameter beyond what FLAC can express.
+fn best_rice_coding(residual: &[i64], block_size: usize, order: usize) -> Option<RiceCoding> {
+    let mut best: Option<RiceCoding> = None;
+
+    for partition_order in 0..=MAX_PARTITION_ORDER {
+        let partitions = 1usize << partition_order;
+        if block_size & (partitions - 1) != 0 || block_size / partitions <= order {
+            break;
+        }
+        let partition_len = block_size / partitions;
+
+        let mut parameters = Vec::with_capacity(partitions);
+        let mut total_bits = 2 + 4; // Coding method + partition order
+        let mut start = 0;
+        let mut valid = true;
+        for p in 0..partitions {
+            // The first partition is shorter by the warm-up samples
+            let len = if p == 0 {
+                partition_len - order
+            } else {
+                partition_len
+            };
+            let values = &residual[start..start + len];
+            start += len;
+
+            let Some((parameter, bits)) = best_rice_parameter(values) else {
+                valid = false;
+                break;
+            };
+            parameters.push(parameter);
+            total_bits += 4 + bits;
+        }
+
+        if valid && best.as_ref().is_none_or(|b| total_bits < b.bits) {
+            best = Some(RiceCoding {
+                partition_order,
+                parameters,
+                bits: total_bits,
+            });
+        }
+    }
+
+    best
+}
+
+/// Cheapest Rice parameter for a partition and its cost in bits
+fn best_rice_parameter(values: &[i64]) -> Option<(u32, u64)> {
+    let n = values.len() as u64;
+    if n == 0 {
+        return Some((0, 0));
+    }
+    let sum: u64 = values.iter().map(|&r| zigzag(r)).sum();
+    let mean = sum / n;
+    let estimate = if mean == 0 {
+        0
+    } else {
+        63 - mean.leading_zeros()
+    };
+
+    let cost = |k: u32| -> u64 {
+        values.iter().map(|&r| zigzag(r) >> k).sum::<u64>() + n * (k as u64 + 1)
+    };
+    [estimate.saturating_sub(1), estimate, estimate + 1]
+        .into_iter()
+        .filter(|&k| k <= MAX_RICE_PARAMETER)
+        .map(|k| (k, cost(k)))
+        .min_by_key(|&(_, bits)| bits)
+}
+
+fn write_residual(
+    bits: &mut BitWriter,
+    residual: &[i64],
+    coding: &RiceCoding,
+    block_size: usize,
+    order: usize,
+) {
+    bits.write(0b00, 2); // Rice coding with 4-bit parameters
+    bits.write(coding.partition_order as u64, 4);
+
+    let partition_len = block_size >> coding.partition_order;
+    let mut start = 0;
+    for (p, &k) in coding.parameters.iter().enumerate() {
+        let len = if p == 0 {
+            partition_len - order
+        } else {
+            partition_len
+        };
+        bits.write(k as u64, 4);
+        for &r in &residual[start..start + len] {
+            let u = zigzag(r);
+            bits.write_unary(u >> k);
+            if k > 0 {
+                bits.write(u & ((1 << k) - 1), k);
+            }
+        }
+        start += len;
+    }
+}
+
+/// Frame numbers use the same variable-length coding as UTF-8
+fn write_utf8_number(bits: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        bits.write(value, 8);
+        return;
+    }
+    let continuation_bytes = match value {
+        0..=0x7FF => 1,
+        0x800..=0xFFFF => 2,
+        0x1_0000..=0x1F_FFFF => 3,
+        0x20_0000..=0x3FF_FFFF => 4,
+        0x400_0000..=0x7FFF_FFFF => 5,
+        _ => 6,
+    };
+    // Leading ones count the bytes, the rest of the first byte holds the top bits
+    let prefix = (0xFF00u64 >> (continuation_bytes + 1)) & 0xFF;
+    bits.write(prefix | (value >> (6 * continuation_bytes)), 8);
+    for i in (0..continuation_bytes).rev() {
+        bits.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// MSB-first bit writer
+struct BitWriter {
+    bytes: Vec<u8>,
+    current: u64,
+    filled: u32,
+}
+
+impl BitWriter {
+    fn with_capacity(bytes: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(bytes),
+            current: 0,
+            filled: 0,
+        }
+    }
+
+    /// Write the low `count` bits of `value` (count <= 64)
+    fn write(&mut self, value: u64, count: u32) {
+        if count > 32 {
+            self.write(value >> 32, count - 32);
+            self.write(value & 0xFFFF_FFFF, 32);
+            return;
+        }
+        if count == 0 {
+            return;
+        }
+        let value = value & ((1u64 << count) - 1);
+        self.current = (self.current << count) | value;
+        self.filled += count;
+        while self.filled >= 8 {
+            self.filled -= 8;
+            self.bytes.push((self.current >> self.filled) as u8);
+        }
+        self.current &= (1u64 << self.filled) - 1;
+    }
+
+    /// Write a two's complement value in `count` bits
+    fn write_signed(&mut self, value: i64, count: u32) {
+        self.write(value as u64, count);
+    }
+
+    /// Write `value` zeros followed by a one
+    fn write_unary(&mut self, mut value: u64) {
+        while value >= 32 {
+            self.write(0, 32);
+            value -= 32;
+        }
+        self.write(1, value as u32 + 1);
+    }
+
+    /// Write `count` zero bytes
+    fn write_zeros(&mut self, count: usize) {
+        for _ in 0..count {
+            self.write(0, 8);
+        }
+    }
+
+    /// Pad with zeros to the next byte boundary
+    fn align(&mut self) {
+        if self.filled > 0 {
+            self.write(0, 8 - self.filled);
+        }
+    }
+
+    /// Bytes completed so far (excludes a partial byte)
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::DecoderOptions;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    /// Interleaved test signal: a sine with some noise, using most of the sample range
+    fn signal(frames: usize, channels: u16, bits: u16) -> Vec<i32> {
+        let peak = ((1i64 << (bits - 1)) - 1) as f64;
+        let mut noise = 0x2545_f491u32;
+        (0..frames * channels as usize)
+            .map(|i| {
+                noise ^= noise << 13;
+                noise ^= noise >> 17;
+                noise ^= noise << 5;
+                let frame = (i / channels as usize) as f64;
+                let tone = (frame * 0.03 * (1 + i % channels as usize) as f64).sin() * 0.8;
+                let jitter = (noise as f64 / u32::MAX as f64 - 0.5) * 0.2;
+                ((tone + jitter) * peak).round() as i32
+            })
+            .collect()
+    }
+
+    struct Decoded {
+        min_block: u16,
+        max_block: u16,
+        total_frames: u64,
+        blocks: Vec<u64>,
+        samples: Vec<i32>,
+        cues: Vec<u64>,
+    }
+
+    /// Finish the encoder and decode the stream, checking nothing was left after the last frame
+    fn finish_and_decode(encoder: FlacEncoder<Cursor<Vec<u8>>>, bits: u16) -> Decoded {
+        let cursor = encoder.finish().unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+        let bytes = cursor.into_inner();
+
+        // STREAMINFO follows "fLaC" and the metadata block header
+        let min_block = u16::from_be_bytes([bytes[8], bytes[9]]);
+        let max_block = u16::from_be_bytes([bytes[10], bytes[11]]);
+
+        let stream = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension("flac");
+        let mut format = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .unwrap()
+            .format;
+        let params = format.default_track().unwrap().codec_params.clone();
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions { verify: true })
+            .unwrap();
+
+        let cues = format.cues().iter().map(|cue| cue.start_ts).collect();
+        let mut blocks = Vec::new();
+        let mut samples = Vec::new();
+        while let Ok(packet) = format.next_packet() {
+            blocks.push(packet.dur);
+            let decoded = decoder.decode(&packet).unwrap();
+            let mut buffer = SampleBuffer::<i32>::new(decoded.capacity() as u64, *decoded.spec());
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend(buffer.samples().iter().map(|&s| s >> (32 - bits)));
+        }
+        Decoded {
+            min_block,
+            max_block,
+            total_frames: params.n_frames.unwrap(),
+            blocks,
+            samples,
+            cues,
+        }
+    }
+
+    #[test]
+    fn round_trips_losslessly() {
+        for (channels, bits) in [(1, 16), (2, 16), (2, 24), (3, 8)] {
+            let samples = signal(BLOCK_SIZE * 3 + 100, channels, bits);
+            let mut encoder =
+                FlacEncoder::new(Cursor::new(Vec::new()), 16000, channels, bits).unwrap();
+            encoder.write_samples(&samples).unwrap();
+
+            let decoded = finish_and_decode(encoder, bits);
+            assert_eq!(decoded.min_block, MIN_DECLARED_BLOCK_SIZE as u16);
+            assert_eq!(decoded.max_block, BLOCK_SIZE as u16);
+            assert_eq!(decoded.total_frames, (BLOCK_SIZE * 3 + 100) as u64);
+            assert_eq!(decoded.blocks, [4096, 4096, 4096, 100]);
+            assert!(
+                decoded.samples == samples,
+                "{channels} channels at {bits} bits differ"
+            );
+        }
+    }
+
+    #[test]
+    fn rewrites_short_flushed_blocks() {
+        let channels = 2;
+        let samples = signal(BLOCK_SIZE * 2, channels, 16);
+        let frame = |n: usize| n * channels as usize;
+
+        let mut encoder = FlacEncoder::new(Cursor::new(Vec::new()), 16000, channels, 16).unwrap();
+        encoder.write_samples(&samples[..frame(5)]).unwrap();
+        encoder.flush().unwrap(); // 5 frame block
+        encoder.write_samples(&samples[frame(5)..frame(9)]).unwrap();
+        encoder.flush().unwrap(); // Rewritten as a 9 frame block
+        encoder
+            .write_samples(&samples[frame(9)..frame(100)])
+            .unwrap();
+        encoder.flush().unwrap(); // Rewritten as a 100 frame block
+        encoder.write_samples(&samples[frame(100)..]).unwrap();
+
+        let decoded = finish_and_decode(encoder, 16);
+        assert_eq!(decoded.total_frames, (BLOCK_SIZE * 2) as u64);
+        assert_eq!(decoded.blocks, [100, 4096, 4096 - 100]);
+        assert!(decoded.samples == samples);
+    }
+
+    #[test]
+    fn rewritten_blocks_cover_the_block_they_replace() {
+        // A full constant block has a shorter header than a short constant one
+        let samples = vec![1234; BLOCK_SIZE];
+
+        let mut encoder = FlacEncoder::new(Cursor::new(Vec::new()), 16000, 1, 16).unwrap();
+        encoder.write_samples(&samples[..15]).unwrap();
+        encoder.flush().unwrap();
+        encoder.write_samples(&samples[15..]).unwrap();
+
+        let decoded = finish_and_decode(encoder, 16);
+        assert_eq!(decoded.blocks, [BLOCK_SIZE as u64]);
+        assert!(decoded.samples == samples);
+    }
+
+    #[test]
+    fn writes_cue_points_as_tracks() {
+        let samples = signal(BLOCK_SIZE * 2, 1, 16);
+        let mut encoder =
+            FlacEncoder::with_cue_sheet(Cursor::new(Vec::new()), 16000, 1, 16).unwrap();
+        encoder.write_samples(&samples[..5000]).unwrap();
+        encoder.set_cue_points(&[0, 5000]).unwrap();
+        encoder.flush().unwrap();
+        encoder.write_samples(&samples[5000..]).unwrap();
+
+        assert!(encoder.set_cue_points(&[0; MAX_CUE_POINTS + 1]).is_err());
+        let mut without_space = FlacEncoder::new(Cursor::new(Vec::new()), 16000, 1, 16).unwrap();
+        assert!(without_space.set_cue_points(&[0]).is_err());
+
+        // Symphonia lists the lead-out track as a cue too
+        let decoded = finish_and_decode(encoder, 16);
+        assert_eq!(decoded.cues, [0, 5000, (BLOCK_SIZE * 2) as u64]);
+        assert!(decoded.samples == samples);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::recorder::encryption::RecordingFile;
+use crate::recorder::flac::FlacEncoder;
+use crate::recorder::markers::RecordingMarker;
+use crate::recorder::writer::{Ditherer, OutputSampleFormat, WriterOptions};
+use crate::recorder::writer_stats::WriterStats;
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::{debug, info};
+
+/// FLAC file writer that streams blocks to disk while recording.
+///
+/// STREAMINFO and the seek table are patched every second like the WAV headers,
+/// so a recording cut short by a crash is still a playable FLAC file. FLAC has no
+/// float samples: 32-bit float sessions are dithered to 24-bit. Markers are embedded
+/// as CUESHEET tracks, which have no labels, so the labels only come back from the session.
+pub struct FlacWriter {
+    encoder: FlacEncoder<BufWriter<RecordingFile>>,
+    bits_per_sample: u16,
+    dither: Ditherer,
+    scratch: Vec<i32>,
+    last_header_update: Instant,
+    stats: WriterStats,
+}
+
+impl FlacWriter {
+    /// Create a new FLAC file with the given options and write the stream header
+    pub fn with_options(
+        file_path: PathBuf,
+        sample_rate: u32,
+        channels: u16,
+        options: WriterOptions,
+    ) -> io::Result<Self> {
+        let file = RecordingFile::create(&file_path, options.encryption_key.as_ref())?;
+        let encrypted = file.is_encrypted();
+
+        let bits_per_sample = match options.sample_format {
+            OutputSampleFormat::Pcm16 => 16,
+            OutputSampleFormat::Pcm24 | OutputSampleFormat::Float32 => 24,
+        };
+        let encoder = FlacEncoder::with_cue_sheet(
+            BufWriter::new(file),
+            sample_rate,
+            channels,
+            bits_per_sample,
+        )?;
+
+        info!(
+            "Created FLAC file at {:?}: {}Hz, {} channels, {}-bit{}",
+            file_path,
+            sample_rate,
+            channels,
+            bits_per_sample,
+            if encrypted { ", encrypted" } else { "" }
+        );
+
+        Ok(Self {
+            encoder,
+            bits_per_sample,
+            dither: Ditherer::new(),
+            scratch: Vec::new(),
+            last_header_update: Instant::now(),
+            stats: WriterStats::new(file_path, sample_rate, channels, options.encryption_key),
+        })
+    }
+
+    /// Write f32 samples, dithered to the stream's bit depth
+    pub fn write_samples_f32(&mut self, samples: &[f32]) -> io::Result<()> {
+        self.scratch.clear();
+        for &sample in samples {
+            let value = self.dither.quantize(sample, self.bits_per_sample);
+            self.scratch.push(value);
+            self.stats.analyze(sample);
+        }
+        self.encoder.write_samples(&self.scratch)?;
+
+        self.on_samples_written(samples.len())
+    }
+
+    /// Account for written samples and update the stream header periodically (every second)
+    fn on_samples_written(&mut self, count: usize) -> io::Result<()> {
+        self.stats.add_samples(count);
+
+        if self.last_header_update.elapsed().as_secs() >= 1 {
+            self.encoder.update_header()?;
+            self.last_header_update = Instant::now();
+            debug!(
+                "Updated FLAC header: {} samples written ({:.2} seconds)",
+                self.stats.samples_written(),
+                self.stats.duration_seconds()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Write out buffered samples and the final stream header.
+    /// Recording can continue afterwards, the next finalize covers the new audio.
+    pub fn finalize(&mut self) -> io::Result<()> {
+        self.encoder.flush()?;
+        self.stats.write_peaks();
+
+        info!(
+            "Finalized FLAC file {:?}: {} samples, {:.2} seconds",
+            self.stats.file_path(),
+            self.stats.samples_written(),
+            self.stats.duration_seconds()
+        );
+
+        Ok(())
+    }
+
+    /// Add a marker at the current position, written to the CUESHEET with the next header update
+    pub fn add_marker(&mut self, label: String) -> io::Result<RecordingMarker> {
+        let mut cue_points: Vec<u64> = self
+            .stats
+            .markers()
+            .iter()
+            .map(|m| m.sample_position)
+            .collect();
+        cue_points.push(self.stats.frames_written());
+        self.encoder.set_cue_points(&cue_points)?;
+        Ok(self.stats.add_marker(label))
+    }
+
+    pub fn stats(&self) -> &WriterStats {
+        &self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut WriterStats {
+        &mut self.stats
+    }
+}
+
+impl Drop for FlacWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finalize() {
+            tracing::error!("Failed to finalize FLAC file on drop: {}", e);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::recorder::quality::to_dbfs;
+use crate::recorder::sink::AudioSink;
+use serde::Serialize;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// Length of the blocks each level reading covers
+const BLOCK_MS: u32 = 50;
+
+/// Input level over the latest block - emitted to frontend
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioLevel {
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    pub time_seconds: f32, // End of the block from the start of the recording
+}
+
+/// Called from the audio thread with every new level reading
+pub type LevelHandler = Arc<dyn Fn(AudioLevel) + Send + Sync>;
+
+/// Measures peak and RMS levels in 50 ms blocks, for VU meters
+pub struct LevelMeter {
+    latest: Arc<Mutex<Option<AudioLevel>>>,
+    on_level: Option<LevelHandler>,
+    sample_rate: u32,
+    channels: u16,
+    block_samples: usize,
+    block_position: usize,
+    block_peak: f32,
+    block_sum_squares: f64,
+    samples_seen: u64,
+}
+
+impl LevelMeter {
+    pub fn new(on_level: Option<LevelHandler>) -> Self {
+        Self {
+            latest: Arc::new(Mutex::new(None)),
+            on_level,
+            sample_rate: 0,
+            channels: 0,
+            block_samples: 0,
+            block_position: 0,
+            block_peak: 0.0,
+            block_sum_squares: 0.0,
+            samples_seen: 0,
+        }
+    }
+
+    /// Shared slot holding the most recent reading
+    pub fn latest(&self) -> Arc<Mutex<Option<AudioLevel>>> {
+        self.latest.clone()
+    }
+
+    fn end_block(&mut self) {
+        let level = AudioLevel {
+            peak_dbfs: to_dbfs(self.block_peak),
+            rms_dbfs: to_dbfs((self.block_sum_squares / self.block_position as f64).sqrt() as f32),
+            time_seconds: self.samples_seen as f32
+                / (self.sample_rate as f32 * self.channels as f32),
+        };
+        self.block_position = 0;
+        self.block_peak = 0.0;
+        self.block_sum_squares = 0.0;
+
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some(level);
+        }
+        if let Some(handler) = &self.on_level {
+            handler(level);
+        }
+    }
+}
+
+impl AudioSink for LevelMeter {
+    fn name(&self) -> &str {
+        "level meter"
+    }
+
+    fn open(&mut self, sample_rate: u32, channels: u16) -> io::Result<()> {
+        let channels = channels.max(1);
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.block_samples = (sample_rate * BLOCK_MS / 1000).max(1) as usize * channels as usize;
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            let magnitude = sample.abs();
+            self.block_peak = self.block_peak.max(magnitude);
+            self.block_sum_squares += (sample as f64) * (sample as f64);
+            self.block_position += 1;
+            self.samples_seen += 1;
+            if self.block_position >= self.block_samples {
+                self.end_block();
+            }
+        }
+        Ok(())
+    }
+}
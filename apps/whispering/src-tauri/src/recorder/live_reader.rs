@@ -0,0 +1,251 @@
+use crate::recorder::encryption::RecordingKeyStore;
+use crate::recorder::writer::OutputSampleFormat;
+use serde::Serialize;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Most audio a single read returns
+pub const MAX_READ_SECONDS: u64 = 30;
+
+/// Encrypted segments are rewritten in place when the writer flushes, so a read can
+/// catch one half-written. Those reads fail authentication and are retried.
+const READ_ATTEMPTS: usize = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(20);
+
+/// Audio read from the recording in progress - returned to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveAudio {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub start_frame: u64,
+    pub available_frames: u64, // Frames on disk so far, the next read can start at the end of this one
+    pub samples: Vec<f32>,     // Interleaved
+}
+
+/// Handle for reading a WAV recording while it is being written.
+///
+/// The writer publishes how many frames have reached disk each time it flushes, and
+/// reads never go past that point, so they only ever see complete samples. Each read
+/// opens its own file handle and decrypts it if needed, leaving the writer untouched.
+#[derive(Debug, Clone)]
+pub struct LiveRecording {
+    file_path: PathBuf,
+    data_start: u64,
+    sample_rate: u32,
+    channels: u16,
+    sample_format: OutputSampleFormat,
+    durable_frames: Arc<AtomicU64>,
+}
+
+impl LiveRecording {
+    pub(crate) fn new(
+        file_path: PathBuf,
+        data_start: u64,
+        sample_rate: u32,
+        channels: u16,
+        sample_format: OutputSampleFormat,
+    ) -> Self {
+        Self {
+            file_path,
+            data_start,
+            sample_rate,
+            channels,
+            sample_format,
+            durable_frames: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Record that the first `frames` frames are flushed to disk
+    pub(crate) fn publish(&self, frames: u64) {
+        self.durable_frames.store(frames, Ordering::Release);
+    }
+
+    /// Frames that can be read so far
+    pub fn available_frames(&self) -> u64 {
+        self.durable_frames.load(Ordering::Acquire)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Read up to `max_frames` frames starting at `start_frame`, as interleaved f32 samples
+    pub fn read(
+        &self,
+        keys: &RecordingKeyStore,
+        start_frame: u64,
+        max_frames: u64,
+    ) -> io::Result<LiveAudio> {
+        let available_frames = self.available_frames();
+        let frames = available_frames.saturating_sub(start_frame).min(max_frames);
+
+        let mut attempt = 1;
+        let bytes = loop {
+            match self.read_frames(keys, start_frame, frames) {
+                Ok(bytes) => break bytes,
+                Err(e) if e.kind() == io::ErrorKind::InvalidData && attempt < READ_ATTEMPTS => {
+                    attempt += 1;
+                    std::thread::sleep(RETRY_DELAY);
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let samples = match self.sample_format {
+            OutputSampleFormat::Float32 => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            OutputSampleFormat::Pcm16 => bytes
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect(),
+            OutputSampleFormat::Pcm24 => bytes
+                .chunks_exact(3)
+                .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0)
+                .collect(),
+        };
+
+        Ok(LiveAudio {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            start_frame,
+            available_frames,
+            samples,
+        })
+    }
+
+    /// Read the raw sample bytes of a range of frames
+    fn read_frames(
+        &self,
+        keys: &RecordingKeyStore,
+        start_frame: u64,
+        frames: u64,
+    ) -> io::Result<Vec<u8>> {
+        let frame_bytes = self.channels as u64 * self.sample_format.bits_per_sample() as u64 / 8;
+        let mut bytes = vec![0; (frames * frame_bytes) as usize];
+        if bytes.is_empty() {
+            return Ok(bytes);
+        }
+
+        let mut file = keys.open_recording(&self.file_path)?;
+        file.seek(SeekFrom::Start(self.data_start + start_frame * frame_bytes))?;
+        file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::encryption::RecordingKey;
+    use crate::recorder::wav_writer::WavWriter;
+    use crate::recorder::writer::WriterOptions;
+    use std::path::Path;
+
+    fn writer(path: &Path, channels: u16, options: WriterOptions) -> WavWriter {
+        WavWriter::with_options(path.to_path_buf(), 16000, channels, options).unwrap()
+    }
+
+    fn format(sample_format: OutputSampleFormat) -> WriterOptions {
+        WriterOptions {
+            sample_format,
+            ..WriterOptions::default()
+        }
+    }
+
+    #[test]
+    fn reads_only_frames_published_by_the_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("live.wav");
+        let keys = RecordingKeyStore::new();
+        let mut writer = writer(&path, 2, WriterOptions::default());
+        let live = writer.live_recording();
+        let samples: Vec<f32> = (0..20).map(|i| i as f32 / 100.0).collect();
+
+        writer.write_samples_f32(&samples).unwrap();
+        assert_eq!(live.available_frames(), 0);
+        assert!(live.read(&keys, 0, 100).unwrap().samples.is_empty());
+
+        writer.finalize().unwrap();
+        let all = live.read(&keys, 0, 100).unwrap();
+        assert_eq!((all.sample_rate, all.channels), (16000, 2));
+        assert_eq!(all.available_frames, 10);
+        assert_eq!(all.samples, samples);
+
+        let middle = live.read(&keys, 3, 4).unwrap();
+        assert_eq!(middle.start_frame, 3);
+        assert_eq!(middle.samples, samples[6..14]);
+        assert!(live.read(&keys, 10, 100).unwrap().samples.is_empty());
+        assert!(live.read(&keys, 50, 100).unwrap().samples.is_empty());
+    }
+
+    #[test]
+    fn decodes_every_sample_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys = RecordingKeyStore::new();
+        // Values every format holds exactly, negative ones check sign extension
+        let lsb24 = 1.0 / 8_388_608.0;
+        let cases = [
+            (OutputSampleFormat::Float32, vec![0.1, -0.7, 1.0, -1.0]),
+            (
+                OutputSampleFormat::Pcm16,
+                vec![0.5, -0.5, -1.0 / 32768.0, 32767.0 / 32768.0, -1.0],
+            ),
+            (
+                OutputSampleFormat::Pcm24,
+                vec![0.25, -lsb24, -0.25 - lsb24, 1.0 - lsb24, -1.0, lsb24],
+            ),
+        ];
+        for (sample_format, samples) in cases {
+            let path = dir.path().join(format!("{:?}.wav", sample_format));
+            let mut writer = writer(&path, 1, format(sample_format));
+            writer.write_samples_f32(&samples).unwrap();
+            writer.finalize().unwrap();
+
+            let audio = writer.live_recording().read(&keys, 0, 100).unwrap();
+            assert_eq!(audio.samples, samples, "{:?}", sample_format);
+        }
+    }
+
+    #[test]
+    fn reads_encrypted_recordings_with_the_recording_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("live.wav.enc");
+        let key = RecordingKey::generate();
+        let options = WriterOptions {
+            encryption_key: Some(key.clone()),
+            ..format(OutputSampleFormat::Pcm16)
+        };
+        let mut writer = writer(&path, 1, options);
+        let samples: Vec<f32> = (0..50_000)
+            .map(|i| (i % 200 - 100) as f32 / 128.0)
+            .collect();
+        writer.write_samples_f32(&samples).unwrap();
+        writer.finalize().unwrap();
+        let live = writer.live_recording();
+
+        let audio = live
+            .read(&RecordingKeyStore::with_key(key), 40_000, 1000)
+            .unwrap();
+        assert_eq!(audio.samples, samples[40_000..41_000]);
+
+        // A key that can't authenticate the file fails once the retries run out
+        let error = live
+            .read(
+                &RecordingKeyStore::with_key(RecordingKey::generate()),
+                0,
+                10,
+            )
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,113 @@
+use serde::Serialize;
+
+/// Bookmark dropped while recording - returned to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingMarker {
+    pub id: u32,
+    pub label: String,
+    pub sample_position: u64, // In frames from the start of the recording
+    pub time_seconds: f32,
+}
+
+/// Encode markers as a `cue ` chunk followed by a `LIST`/`adtl` chunk of `labl` entries.
+///
+/// These are the standard RIFF chunks other audio tools (Audacity, Reaper, Sound Forge)
+/// use for markers, so the bookmarks survive outside Whispering.
+pub fn encode_marker_chunks(markers: &[RecordingMarker]) -> Vec<u8> {
+    if markers.is_empty() {
+        return Vec::new();
+    }
+
+    let mut out = Vec::new();
+
+    // cue chunk: one 24-byte cue point per marker
+    out.extend_from_slice(b"cue ");
+    out.extend_from_slice(&(4 + 24 * markers.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(markers.len() as u32).to_le_bytes());
+    for marker in markers {
+        // Cue positions are 32-bit, which covers ~24 hours at 48kHz
+        let position = marker.sample_position.min(u32::MAX as u64) as u32;
+        out.extend_from_slice(&marker.id.to_le_bytes());
+        out.extend_from_slice(&position.to_le_bytes()); // Play order position
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&0u32.to_le_bytes()); // Chunk start
+        out.extend_from_slice(&0u32.to_le_bytes()); // Block start
+        out.extend_from_slice(&position.to_le_bytes()); // Sample offset
+    }
+
+    // LIST/adtl chunk with a NUL-terminated label per cue point
+    let mut adtl = Vec::new();
+    adtl.extend_from_slice(b"adtl");
+    for marker in markers {
+        let mut text = marker.label.as_bytes().to_vec();
+        text.push(0);
+        adtl.extend_from_slice(b"labl");
+        adtl.extend_from_slice(&(4 + text.len() as u32).to_le_bytes());
+        adtl.extend_from_slice(&marker.id.to_le_bytes());
+        adtl.extend_from_slice(&text);
+        if text.len() % 2 == 1 {
+            adtl.push(0); // Chunks are word aligned
+        }
+    }
+    out.extend_from_slice(b"LIST");
+    out.extend_from_slice(&(adtl.len() as u32).to_le_bytes());
+    out.extend_from_slice(&adtl);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(id: u32, label: &str, sample_position: u64) -> RecordingMarker {
+        RecordingMarker {
+            id,
+            label: label.to_string(),
+            sample_position,
+            time_seconds: 0.0,
+        }
+    }
+
+    #[test]
+    fn no_markers_write_no_chunks() {
+        assert!(encode_marker_chunks(&[]).is_empty());
+    }
+
+    #[test]
+    fn encodes_cue_points_and_labels() {
+        let chunks = encode_marker_chunks(&[marker(1, "Hi", 48000), marker(2, "Odd", 96000)]);
+
+        assert_eq!(&chunks[..4], b"cue ");
+        assert_eq!(chunks[4..8], (4u32 + 2 * 24).to_le_bytes());
+        assert_eq!(chunks[8..12], 2u32.to_le_bytes());
+        assert_eq!(chunks[12 + 24 + 4..12 + 24 + 8], 96000u32.to_le_bytes());
+
+        let list = &chunks[12 + 2 * 24..];
+        assert_eq!(&list[..4], b"LIST");
+        assert_eq!(list[4..8], (list.len() as u32 - 8).to_le_bytes());
+        assert_eq!(
+            &list[8..],
+            [
+                &b"adtl"[..],
+                b"labl",
+                &7u32.to_le_bytes(),
+                &1u32.to_le_bytes(),
+                b"Hi\0\0", // Padded to an even length
+                b"labl",
+                &8u32.to_le_bytes(),
+                &2u32.to_le_bytes(),
+                b"Odd\0",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn cue_positions_past_32_bits_are_clamped() {
+        let chunks = encode_marker_chunks(&[marker(1, "Late", u32::MAX as u64 + 1000)]);
+        assert_eq!(chunks[12 + 4..12 + 8], u32::MAX.to_le_bytes());
+        assert_eq!(chunks[12 + 20..12 + 24], u32::MAX.to_le_bytes());
+    }
+}
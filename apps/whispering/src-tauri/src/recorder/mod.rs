@@ -1,12 +1,54 @@
 pub mod commands;
+pub mod cpal_source;
+pub mod device_test;
+pub mod encryption;
+pub mod flac;
+pub mod flac_writer;
+pub mod level_meter;
+pub mod live_reader;
+pub mod markers;
+pub mod network_sink;
+pub mod opus;
+pub mod opus_writer;
+pub mod peaks;
+pub mod quality;
 pub mod recorder;
+pub mod scope;
+pub mod sink;
+pub mod source;
+pub mod storage;
+pub mod synthetic_source;
+pub mod vad;
+pub mod wav_metadata;
 pub mod wav_writer;
+pub mod writer;
+pub mod writer_stats;
 
 // Export everything from commands for easy access
 pub use commands::{
-    cancel_recording, close_recording_session, enumerate_recording_devices,
-    get_current_recording_id, init_recording_session, start_recording, stop_recording, AppData,
+    add_recording_marker, cancel_recording, clean_up_recordings, close_recording_session,
+    enumerate_recording_devices, get_current_recording_id, get_recording_retention,
+    get_recordings_storage_stats, init_recording_session, migrate_recordings_folder,
+    select_recordings_folder, set_recording_retention, start_recording, stop_recording,
+    test_recording_device, AppData, RecorderCommandError,
 };
 
 // Export key types from recorder
-pub use recorder::AudioRecording;
+pub use device_test::{DeviceProblem, DeviceTestResult};
+pub use encryption::{RecordingKey, RecordingKeyStore};
+pub use level_meter::{AudioLevel, LevelMeter};
+pub use live_reader::{LiveAudio, LiveRecording};
+pub use markers::RecordingMarker;
+pub use network_sink::NetworkSink;
+pub use peaks::WaveformPeaks;
+pub use quality::{SignalQuality, SignalWarning};
+pub use recorder::{AudioRecording, BufferSizeRequest, RecordingSessionInfo};
+pub use scope::{RecordingPathError, RecordingsScope};
+pub use sink::{AudioSink, MemoryBuffer, MemorySink, SinkId};
+pub use source::{open_source, AudioSource, SourceFormat};
+pub use storage::{
+    CleanupReport, RecordingRetention, RetentionPolicy, RetentionSettings, StorageStats,
+};
+pub use synthetic_source::{ManualDriver, Signal, SyntheticSource};
+pub use vad::{VoiceActivity, VoiceActivityDetector};
+pub use writer::{OutputSampleFormat, RecordingFormat, RecordingInfo};
@@ -0,0 +1,150 @@
+use crate::recorder::sink::AudioSink;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Buffers queued for sending before new audio is dropped
+const QUEUE_BUFFERS: usize = 64;
+
+/// How long connecting, or a single send, may take before the stream gives up
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Streams recorded audio over TCP as 16-bit PCM WAV.
+///
+/// The stream starts with a WAV header of unknown length, so tools like ffplay or a
+/// transcription server can consume it directly. Sending happens on its own thread;
+/// if the connection can't keep up, buffers are dropped rather than stalling capture.
+pub struct NetworkSink {
+    stream: Option<TcpStream>,
+    peer: String,
+    sender: Option<SyncSender<Vec<u8>>>,
+    thread: Option<JoinHandle<()>>,
+    dropped_buffers: u64,
+}
+
+impl NetworkSink {
+    /// Connect to a listener, e.g. `127.0.0.1:9000`
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let addr = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No address found for '{}'", address),
+            )
+        })?;
+        let stream = TcpStream::connect_timeout(&addr, NETWORK_TIMEOUT)?;
+        stream.set_write_timeout(Some(NETWORK_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        info!("Connected audio stream to {}", addr);
+
+        Ok(Self {
+            stream: Some(stream),
+            peer: addr.to_string(),
+            sender: None,
+            thread: None,
+            dropped_buffers: 0,
+        })
+    }
+}
+
+impl AudioSink for NetworkSink {
+    fn name(&self) -> &str {
+        "network stream"
+    }
+
+    fn open(&mut self, sample_rate: u32, channels: u16) -> io::Result<()> {
+        let mut stream = self
+            .stream
+            .take()
+            .ok_or_else(|| io::Error::other("Network stream was already opened"))?;
+        stream.write_all(&streaming_wav_header(sample_rate, channels))?;
+
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(QUEUE_BUFFERS);
+        let peer = self.peer.clone();
+        self.thread = Some(thread::spawn(move || {
+            for bytes in receiver {
+                if let Err(e) = stream.write_all(&bytes) {
+                    warn!("Audio stream to {} failed: {}", peer, e);
+                    return;
+                }
+            }
+            let _ = stream.flush();
+            debug!("Audio stream to {} closed", peer);
+        }));
+        self.sender = Some(sender);
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Network stream is not open"))?;
+
+        let mut bytes = Vec::with_capacity(samples.len() * 2);
+        for &sample in samples {
+            // The inverse of the capture scaling, so 16-bit device audio goes out unchanged
+            let value = (sample.clamp(-1.0, 1.0) * 32768.0) as i16;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        match sender.try_send(bytes) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped_buffers += 1;
+                Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "Network stream is falling behind, dropping audio",
+                ))
+            }
+            Err(TrySendError::Disconnected(_)) => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Network stream connection closed",
+            )),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        // Closing the channel lets the thread send what's queued and exit
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if self.dropped_buffers > 0 {
+            warn!(
+                "Audio stream to {} dropped {} buffers",
+                self.peer, self.dropped_buffers
+            );
+            self.dropped_buffers = 0;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NetworkSink {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// 16-bit PCM WAV header with the RIFF and data sizes left at their maximum
+fn streaming_wav_header(sample_rate: u32, channels: u16) -> Vec<u8> {
+    let block_align = channels * 2;
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&channels.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header
+}
@@ -0,0 +1,921 @@
+use audiopus::coder::{Decoder, Encoder};
+use audiopus::packet::Packet;
+use audiopus::{Application, Bitrate, Channels, MutSignals, SampleRate, Signal};
+use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Granule positions in Ogg Opus always count 48kHz samples
+const GRANULE_RATE: u64 = 48000;
+
+/// Length of each encoded packet
+const FRAME_MILLISECONDS: u32 = 20;
+
+/// Largest packet libopus produces for a single frame
+const MAX_PACKET_LEN: usize = 4000;
+
+/// Longest audio a single packet can hold (120 ms), in 48kHz samples per channel
+const MAX_PACKET_SAMPLES: usize = 5760;
+
+/// Speech bitrates: transparent for voice at roughly 1/20th of 16-bit PCM
+const MONO_BITRATE: i32 = 24_000;
+const STEREO_BITRATE: i32 = 32_000;
+
+/// Most lacing values a single Ogg page can hold
+const MAX_PAGE_SEGMENTS: usize = 255;
+
+/// Frames read from a WAV at a time when converting
+const CONVERT_CHUNK_FRAMES: usize = 16 * 1024;
+
+const PAGE_FLAG_CONTINUED: u8 = 0x01;
+const PAGE_FLAG_BOS: u8 = 0x02;
+const PAGE_FLAG_EOS: u8 = 0x04;
+
+/// Streaming Ogg Opus encoder (RFC 7845) tuned for speech.
+///
+/// Rates libopus doesn't take natively (e.g. 44.1kHz) are resampled to 48kHz, and
+/// anything beyond stereo is downmixed to mono. Audio is encoded in 20 ms packets
+/// and written out page by page, so everything up to the last completed page is
+/// readable at any time. Finishing a stream pads the final packet and trims the
+/// padding through the end granule position; writing again afterwards starts a new
+/// chained stream in the same file.
+pub struct OpusEncoder<W: Write> {
+    writer: W,
+    encoder: Encoder,
+    source_rate: u32,
+    encoder_rate: u32,
+    channels: u16,
+    encoded_channels: usize,
+    resampler: Option<LinearResampler>,
+    frame_len: usize, // Interleaved samples per packet
+    pending: Vec<f32>,
+    packet: Vec<u8>,
+    lookahead: u64,      // Encoder delay, in frames at the encoder rate
+    pre_skip: u64,       // The same delay in 48kHz samples
+    real_frames: u64,    // Frames of actual audio fed to the encoder
+    encoded_frames: u64, // Frames encoded, including padding
+    page: OggPage,
+    serial: u32,
+    stream_open: bool,
+}
+
+impl<W: Write> OpusEncoder<W> {
+    /// Create an encoder and write the headers of the first stream
+    pub fn new(writer: W, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        if channels == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Opus needs at least one channel",
+            ));
+        }
+
+        let encoder_rate = match sample_rate {
+            8000 | 12000 | 16000 | 24000 | 48000 => sample_rate,
+            _ => GRANULE_RATE as u32,
+        };
+        let encoded_channels = if channels == 2 { 2 } else { 1 };
+        let resampler = (encoder_rate != sample_rate)
+            .then(|| LinearResampler::new(sample_rate, encoder_rate, encoded_channels));
+
+        let mut encoder = Self {
+            writer,
+            encoder: create_encoder(encoder_rate, encoded_channels)?,
+            source_rate: sample_rate,
+            encoder_rate,
+            channels,
+            encoded_channels,
+            resampler,
+            frame_len: (encoder_rate * FRAME_MILLISECONDS / 1000) as usize * encoded_channels,
+            pending: Vec::new(),
+            packet: vec![0; MAX_PACKET_LEN],
+            lookahead: 0,
+            pre_skip: 0,
+            real_frames: 0,
+            encoded_frames: 0,
+            page: OggPage::default(),
+            serial: stream_serial(),
+            stream_open: false,
+        };
+        encoder.start_stream()?;
+        Ok(encoder)
+    }
+
+    /// Encode interleaved samples. Complete packets are buffered into the current page.
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        if !self.stream_open {
+            self.start_stream()?;
+        }
+
+        let channels = self.channels as usize;
+        let mut mixed = Vec::with_capacity(samples.len() / channels * self.encoded_channels);
+        for frame in samples.chunks_exact(channels) {
+            if self.encoded_channels == channels {
+                mixed.extend_from_slice(frame);
+            } else {
+                mixed.push(frame.iter().sum::<f32>() / channels as f32);
+            }
+        }
+
+        let start = self.pending.len();
+        match &mut self.resampler {
+            Some(resampler) => resampler.process(&mixed, &mut self.pending),
+            None => self.pending.extend_from_slice(&mixed),
+        }
+        self.real_frames += ((self.pending.len() - start) / self.encoded_channels) as u64;
+
+        self.encode_pending()
+    }
+
+    /// End the current page and flush it, so all audio encoded so far is on disk
+    pub fn flush_page(&mut self) -> io::Result<()> {
+        if self.stream_open && !self.page.is_empty() {
+            let granule = self.granule(self.encoded_frames);
+            self.page.write(&mut self.writer, self.serial, granule, 0)?;
+        }
+        self.writer.flush()
+    }
+
+    /// Encode the remaining audio and end the stream. No-op if no stream is open.
+    pub fn finish_stream(&mut self) -> io::Result<()> {
+        if !self.stream_open {
+            return self.writer.flush();
+        }
+
+        // Feed silence until the encoder's lookahead and the last packet are flushed out
+        let needed = self.pending.len() + self.lookahead as usize * self.encoded_channels;
+        let padded = needed.div_ceil(self.frame_len) * self.frame_len;
+        self.pending.resize(padded.max(self.pending.len()), 0.0);
+        self.encode_pending()?;
+
+        // The final granule position trims the padding
+        let end = self.pre_skip + self.real_frames * GRANULE_RATE / self.encoder_rate as u64;
+        self.page
+            .write(&mut self.writer, self.serial, end, PAGE_FLAG_EOS)?;
+        self.writer.flush()?;
+        self.stream_open = false;
+        Ok(())
+    }
+
+    /// Finish the stream and return the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_stream()?;
+        Ok(self.writer)
+    }
+
+    /// Seconds of audio written, at the input sample rate
+    pub fn duration_seconds(&self) -> f64 {
+        self.real_frames as f64 / self.encoder_rate as f64
+    }
+
+    /// Write the identification and comment headers of a new logical stream
+    fn start_stream(&mut self) -> io::Result<()> {
+        if self.encoded_frames > 0 {
+            // Chained streams each need a fresh encoder and serial number
+            self.encoder = create_encoder(self.encoder_rate, self.encoded_channels)?;
+            self.serial = self.serial.wrapping_add(1);
+        }
+        self.lookahead = self.encoder.lookahead().map_err(io::Error::other)? as u64;
+        self.pre_skip = self.granule(self.lookahead);
+        self.real_frames = 0;
+        self.encoded_frames = 0;
+        self.pending.clear();
+
+        let mut head = Vec::with_capacity(19);
+        head.extend_from_slice(b"OpusHead");
+        head.push(1); // Version
+        head.push(self.encoded_channels as u8);
+        head.extend_from_slice(&(self.pre_skip as u16).to_le_bytes());
+        head.extend_from_slice(&self.source_rate.to_le_bytes()); // Original rate, informational
+        head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+        head.push(0); // Channel mapping family: mono or stereo
+        self.page.push(&head);
+        self.page
+            .write(&mut self.writer, self.serial, 0, PAGE_FLAG_BOS)?;
+
+        let vendor = format!("Whispering ({})", audiopus::version());
+        let mut tags = Vec::with_capacity(16 + vendor.len());
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor.as_bytes());
+        tags.extend_from_slice(&0u32.to_le_bytes()); // No user comments
+        self.page.push(&tags);
+        self.page.write(&mut self.writer, self.serial, 0, 0)?;
+
+        self.stream_open = true;
+        Ok(())
+    }
+
+    /// Encode every complete packet in the pending samples
+    fn encode_pending(&mut self) -> io::Result<()> {
+        let packets = self.pending.len() / self.frame_len;
+        for i in 0..packets {
+            let frame = &self.pending[i * self.frame_len..(i + 1) * self.frame_len];
+            let len = self
+                .encoder
+                .encode_float(frame, &mut self.packet)
+                .map_err(io::Error::other)?;
+
+            if !self.page.fits(len) {
+                let granule = self.granule(self.encoded_frames);
+                self.page.write(&mut self.writer, self.serial, granule, 0)?;
+            }
+            self.page.push(&self.packet[..len]);
+            self.encoded_frames += (self.frame_len / self.encoded_channels) as u64;
+        }
+        self.pending.drain(..packets * self.frame_len);
+        Ok(())
+    }
+
+    /// Granule position after the given number of encoded frames
+    fn granule(&self, frames: u64) -> u64 {
+        frames * GRANULE_RATE / self.encoder_rate as u64
+    }
+}
+
+/// Convert a WAV stream to Ogg Opus, returning the writer and the duration in seconds
+pub fn encode_wav_as_opus<R: Read, W: Write>(source: R, writer: W) -> io::Result<(W, f64)> {
+    let mut reader = hound::WavReader::new(source).map_err(io::Error::other)?;
+    let spec = reader.spec();
+    let mut encoder = OpusEncoder::new(writer, spec.sample_rate, spec.channels)?;
+
+    let chunk_len = CONVERT_CHUNK_FRAMES * spec.channels as usize;
+    let mut chunk = Vec::with_capacity(chunk_len);
+    match spec.sample_format {
+        hound::SampleFormat::Float => {
+            for sample in reader.samples::<f32>() {
+                chunk.push(sample.map_err(io::Error::other)?);
+                if chunk.len() == chunk_len {
+                    encoder.write_samples(&chunk)?;
+                    chunk.clear();
+                }
+            }
+        }
+        hound::SampleFormat::Int => {
+            let full_scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+            for sample in reader.samples::<i32>() {
+                chunk.push(sample.map_err(io::Error::other)? as f32 / full_scale);
+                if chunk.len() == chunk_len {
+                    encoder.write_samples(&chunk)?;
+                    chunk.clear();
+                }
+            }
+        }
+    }
+    encoder.write_samples(&chunk)?;
+
+    let duration = encoder.duration_seconds();
+    Ok((encoder.finish()?, duration))
+}
+
+/// Audio decoded from an Ogg Opus file
+#[derive(Debug, Clone)]
+pub struct DecodedOpus {
+    pub sample_rate: u32, // Always 48kHz
+    pub channels: u16,
+    pub samples: Vec<f32>, // Interleaved
+}
+
+/// Decode an Ogg Opus file (RFC 7845), including chained streams.
+///
+/// Each stream's pre-skip and final granule position are applied, so the decoded audio
+/// lines up with what was encoded. Decoding stops at the first incomplete or damaged
+/// page and keeps what came before it, so a recording cut short by a crash still
+/// decodes. Mono and stereo streams are supported; chained streams are decoded with the
+/// channel count of the first one.
+pub fn decode_ogg_opus(data: &[u8]) -> io::Result<DecodedOpus> {
+    let mut output = DecodedOpus {
+        sample_rate: GRANULE_RATE as u32,
+        channels: 0,
+        samples: Vec::new(),
+    };
+    let mut stream: Option<OpusStream> = None;
+    let mut packet = Vec::new();
+
+    let mut position = 0;
+    while let Some(page) = OggPageRef::parse(&data[position..]) {
+        position += page.len;
+
+        // A new stream starts once the previous one ended; other streams multiplexed
+        // alongside it (e.g. a video track) are skipped
+        if page.flags & PAGE_FLAG_BOS != 0 && stream.as_ref().is_none_or(|s| s.ended) {
+            if let Some(previous) = stream.as_mut() {
+                previous.finish(&mut output);
+            }
+            stream = Some(OpusStream::new(page.serial, output.samples.len()));
+            packet.clear();
+        }
+        let Some(current) = stream
+            .as_mut()
+            .filter(|s| s.serial == page.serial && !s.ended)
+        else {
+            continue;
+        };
+
+        // A packet can only continue from the previous page
+        if page.flags & PAGE_FLAG_CONTINUED == 0 {
+            packet.clear();
+        }
+        let mut offset = 0;
+        for &lacing in page.lacing {
+            let len = lacing as usize;
+            packet.extend_from_slice(&page.body[offset..offset + len]);
+            offset += len;
+            if len < 255 {
+                current.packet(&packet, &mut output)?;
+                packet.clear();
+            }
+        }
+
+        if page.granule != u64::MAX {
+            current.last_granule = Some(page.granule);
+        }
+        if page.flags & PAGE_FLAG_EOS != 0 {
+            current.finish(&mut output);
+        }
+    }
+
+    if let Some(current) = stream.as_mut() {
+        current.finish(&mut output);
+    }
+    if output.channels == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No Opus stream found",
+        ));
+    }
+    Ok(output)
+}
+
+/// Decoding state of one logical Opus stream
+struct OpusStream {
+    serial: u32,
+    decoder: Option<Decoder>,
+    packets: u64,
+    pre_skip: u64,
+    decoded: u64, // Samples per channel decoded, including pre-skip
+    start: usize, // Where this stream's audio starts in the output
+    last_granule: Option<u64>,
+    ended: bool,
+}
+
+impl OpusStream {
+    fn new(serial: u32, start: usize) -> Self {
+        Self {
+            serial,
+            decoder: None,
+            packets: 0,
+            pre_skip: 0,
+            decoded: 0,
+            start,
+            last_granule: None,
+            ended: false,
+        }
+    }
+
+    fn packet(&mut self, packet: &[u8], output: &mut DecodedOpus) -> io::Result<()> {
+        self.packets += 1;
+        match self.packets {
+            1 => self.read_header(packet, output),
+            2 => Ok(()), // OpusTags
+            _ => self.decode(packet, output),
+        }
+    }
+
+    fn read_header(&mut self, packet: &[u8], output: &mut DecodedOpus) -> io::Result<()> {
+        if packet.len() < 19 || !packet.starts_with(b"OpusHead") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an Ogg Opus stream",
+            ));
+        }
+        let channels = packet[9];
+        let mapping_family = packet[18];
+        if mapping_family != 0 || !(1..=2).contains(&channels) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Unsupported Opus channel layout: {} channels", channels),
+            ));
+        }
+        self.pre_skip = u16::from_le_bytes([packet[10], packet[11]]) as u64;
+
+        if output.channels == 0 {
+            output.channels = channels as u16;
+        }
+        // libopus mixes to the requested channel count, which covers chains that differ
+        let decoder_channels = if output.channels == 2 {
+            Channels::Stereo
+        } else {
+            Channels::Mono
+        };
+        self.decoder =
+            Some(Decoder::new(SampleRate::Hz48000, decoder_channels).map_err(io::Error::other)?);
+        Ok(())
+    }
+
+    fn decode(&mut self, packet: &[u8], output: &mut DecodedOpus) -> io::Result<()> {
+        let Some(decoder) = self.decoder.as_mut() else {
+            return Ok(());
+        };
+        let Ok(packet) = Packet::try_from(packet) else {
+            return Ok(()); // Empty packets carry no audio
+        };
+
+        let channels = output.channels as usize;
+        let mut buffer = vec![0.0f32; MAX_PACKET_SAMPLES * channels];
+        let signals = MutSignals::try_from(&mut buffer[..]).map_err(io::Error::other)?;
+        let frames = decoder
+            .decode_float(Some(packet), signals, false)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let skip = (self.pre_skip.saturating_sub(self.decoded) as usize).min(frames);
+        self.decoded += frames as u64;
+        output
+            .samples
+            .extend_from_slice(&buffer[skip * channels..frames * channels]);
+        Ok(())
+    }
+
+    /// End the stream, trimming padding past its final granule position
+    fn finish(&mut self, output: &mut DecodedOpus) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        if let Some(granule) = self.last_granule {
+            let frames = granule.saturating_sub(self.pre_skip) as usize;
+            let end = self.start + frames * output.channels as usize;
+            if output.samples.len() > end {
+                output.samples.truncate(end);
+            }
+        }
+    }
+}
+
+/// An Ogg page borrowed from the input
+struct OggPageRef<'a> {
+    len: usize,
+    flags: u8,
+    granule: u64,
+    serial: u32,
+    lacing: &'a [u8],
+    body: &'a [u8],
+}
+
+impl<'a> OggPageRef<'a> {
+    /// Parse the page at the start of `data`, None if it is incomplete or damaged
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < 27 || !data.starts_with(b"OggS") || data[4] != 0 {
+            return None;
+        }
+        let segments = data[26] as usize;
+        let lacing = data.get(27..27 + segments)?;
+        let body_len: usize = lacing.iter().map(|&l| l as usize).sum();
+        let len = 27 + segments + body_len;
+        let page = data.get(..len)?;
+
+        let mut check = page.to_vec();
+        check[22..26].fill(0);
+        let crc = u32::from_le_bytes([page[22], page[23], page[24], page[25]]);
+        if ogg_crc(&check) != crc {
+            return None;
+        }
+
+        Some(Self {
+            len,
+            flags: page[5],
+            granule: u64::from_le_bytes(page[6..14].try_into().ok()?),
+            serial: u32::from_le_bytes(page[14..18].try_into().ok()?),
+            lacing,
+            body: &page[27 + segments..],
+        })
+    }
+}
+
+/// Create a libopus encoder configured for speech
+fn create_encoder(sample_rate: u32, channels: usize) -> io::Result<Encoder> {
+    let rate = SampleRate::try_from(sample_rate as i32).map_err(io::Error::other)?;
+    let (channels, bitrate) = if channels == 2 {
+        (Channels::Stereo, STEREO_BITRATE)
+    } else {
+        (Channels::Mono, MONO_BITRATE)
+    };
+    let mut encoder = Encoder::new(rate, channels, Application::Voip).map_err(io::Error::other)?;
+    encoder
+        .set_bitrate(Bitrate::BitsPerSecond(bitrate))
+        .map_err(io::Error::other)?;
+    encoder
+        .set_signal(Signal::Voice)
+        .map_err(io::Error::other)?;
+    Ok(encoder)
+}
+
+/// Serial number for a new stream. Only needs to differ between chained streams.
+fn stream_serial() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() ^ d.as_secs() as u32)
+        .unwrap_or(0x5748_5350)
+}
+
+/// Packets collected for the next Ogg page
+#[derive(Default)]
+struct OggPage {
+    sequence: u32,
+    lacing: Vec<u8>,
+    body: Vec<u8>,
+}
+
+impl OggPage {
+    fn is_empty(&self) -> bool {
+        self.lacing.is_empty()
+    }
+
+    /// Whether a packet of this length still fits on the page
+    fn fits(&self, len: usize) -> bool {
+        self.lacing.len() + len / 255 < MAX_PAGE_SEGMENTS
+    }
+
+    fn push(&mut self, packet: &[u8]) {
+        self.lacing
+            .extend(std::iter::repeat_n(255, packet.len() / 255));
+        self.lacing.push((packet.len() % 255) as u8);
+        self.body.extend_from_slice(packet);
+    }
+
+    /// Write the page with the granule position of its last packet and start a new one
+    fn write<W: Write>(
+        &mut self,
+        writer: &mut W,
+        serial: u32,
+        granule: u64,
+        flags: u8,
+    ) -> io::Result<()> {
+        let mut page = Vec::with_capacity(27 + self.lacing.len() + self.body.len());
+        page.extend_from_slice(b"OggS");
+        page.push(0); // Version
+        page.push(flags);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]); // CRC, filled in below
+        page.push(self.lacing.len() as u8);
+        page.extend_from_slice(&self.lacing);
+        page.extend_from_slice(&self.body);
+
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        writer.write_all(&page)?;
+
+        self.sequence = if flags & PAGE_FLAG_EOS != 0 {
+            0
+        } else {
+            self.sequence + 1
+        };
+        self.lacing.clear();
+        self.body.clear();
+        Ok(())
+    }
+}
+
+/// CRC-32 used by Ogg (polynomial 0x04C11DB7, no reflection)
+fn ogg_crc(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = (i as u32) << 24;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 0x8000_0000 != 0 {
+                    (crc << 1) ^ 0x04C1_1DB7
+                } else {
+                    crc << 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    data.iter().fold(0, |crc, &byte| {
+        (crc << 8) ^ TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+/// Streaming linear interpolation resampler for interleaved audio
+struct LinearResampler {
+    step: f64,
+    channels: usize,
+    position: f64,      // Next output position, in input frames relative to `previous`
+    previous: Vec<f32>, // Last input frame of the previous call
+}
+
+impl LinearResampler {
+    fn new(from_rate: u32, to_rate: u32, channels: usize) -> Self {
+        Self {
+            step: from_rate as f64 / to_rate as f64,
+            channels,
+            position: 0.0,
+            previous: Vec::new(),
+        }
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let channels = self.channels;
+        let history = self.previous.len() / channels;
+        let frames = history + input.len() / channels;
+        let sample = |frame: usize, channel: usize| {
+            if frame < history {
+                self.previous[frame * channels + channel]
+            } else {
+                input[(frame - history) * channels + channel]
+            }
+        };
+
+        while self.position + 1.0 < frames as f64 {
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+            for channel in 0..channels {
+                let a = sample(index, channel);
+                let b = sample(index + 1, channel);
+                output.push(a + (b - a) * fraction);
+            }
+            self.position += self.step;
+        }
+
+        if frames > 0 {
+            // Keep the last frame to interpolate across the next call
+            let last: Vec<f32> = (0..channels).map(|c| sample(frames - 1, c)).collect();
+            self.position -= (frames - 1) as f64;
+            self.previous = last;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Interleaved rising sweep, an octave apart on each channel. Unlike a steady tone it
+    /// only lines up with itself at one offset.
+    fn tone(sample_rate: u32, channels: u16, frames: usize) -> Vec<f32> {
+        (0..frames * channels as usize)
+            .map(|i| {
+                let t = (i / channels as usize) as f64 / sample_rate as f64;
+                let octave = (1 + i % channels as usize) as f64;
+                let phase = (200.0 * t + 300.0 * t * t) * octave;
+                ((phase * std::f64::consts::TAU).sin() * 0.5) as f32
+            })
+            .collect()
+    }
+
+    fn wav(sample_rate: u32, channels: u16, samples: &[f32]) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample((sample * 32767.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    /// Every page in the file, checking they are contiguous
+    fn pages(data: &[u8]) -> Vec<OggPageRef<'_>> {
+        let mut pages = Vec::new();
+        let mut position = 0;
+        while let Some(page) = OggPageRef::parse(&data[position..]) {
+            position += page.len;
+            pages.push(page);
+        }
+        assert_eq!(position, data.len());
+        pages
+    }
+
+    /// Shift of the first channel of `decoded` against `source` that matches best, in frames
+    fn best_lag(source: &[f32], decoded: &[f32], channels: usize) -> i64 {
+        let source: Vec<f32> = source.iter().step_by(channels).copied().collect();
+        let decoded: Vec<f32> = decoded.iter().step_by(channels).copied().collect();
+        let window = 4800..14400;
+        (-1000i64..=1000)
+            .map(|lag| {
+                let error: f32 = window
+                    .clone()
+                    .map(|i| source[i] - decoded[(i as i64 + lag) as usize])
+                    .map(|d| d * d)
+                    .sum();
+                (lag, error)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn ogg_crc_matches_the_reference_check_value() {
+        assert_eq!(ogg_crc(b""), 0);
+        assert_eq!(ogg_crc(b"123456789"), 0x89A1_897F);
+    }
+
+    #[test]
+    fn pages_round_trip_through_the_parser() {
+        let packets = [vec![1u8; 0], vec![2; 254], vec![3; 255], vec![4; 600]];
+        let mut page = OggPage::default();
+        for packet in &packets {
+            page.push(packet);
+        }
+        let mut data = Vec::new();
+        page.write(&mut data, 0xDEAD_BEEF, 12345, PAGE_FLAG_BOS)
+            .unwrap();
+        page.push(&[5; 10]);
+        page.write(&mut data, 0xDEAD_BEEF, 23456, PAGE_FLAG_EOS)
+            .unwrap();
+
+        let parsed = pages(&data);
+        assert_eq!(parsed.len(), 2);
+        let first = &parsed[0];
+        assert_eq!(first.flags, PAGE_FLAG_BOS);
+        assert_eq!(first.granule, 12345);
+        assert_eq!(first.serial, 0xDEAD_BEEF);
+        assert_eq!(first.lacing, [0, 254, 255, 0, 255, 255, 90]);
+        assert_eq!(first.body, packets.concat());
+        assert_eq!(u32::from_le_bytes(data[18..22].try_into().unwrap()), 0);
+        let second = &parsed[1];
+        assert_eq!(second.flags, PAGE_FLAG_EOS);
+        assert_eq!(second.body, [5; 10]);
+        assert_eq!(
+            u32::from_le_bytes(data[first.len + 18..first.len + 22].try_into().unwrap()),
+            1
+        );
+    }
+
+    #[test]
+    fn parser_rejects_damaged_and_incomplete_pages() {
+        let mut page = OggPage::default();
+        page.push(&[7; 300]);
+        let mut data = Vec::new();
+        page.write(&mut data, 1, 0, 0).unwrap();
+        assert!(OggPageRef::parse(&data).is_some());
+
+        for len in [0, 26, 28, data.len() - 1] {
+            assert!(OggPageRef::parse(&data[..len]).is_none(), "{}", len);
+        }
+        for byte in [0, 4, 6, 30, data.len() - 1] {
+            let mut damaged = data.clone();
+            damaged[byte] ^= 0x10;
+            assert!(OggPageRef::parse(&damaged).is_none(), "{}", byte);
+        }
+    }
+
+    #[test]
+    fn page_is_full_at_255_lacing_values() {
+        let mut page = OggPage::default();
+        for _ in 0..254 {
+            assert!(page.fits(10));
+            page.push(&[0; 10]);
+        }
+        assert!(page.fits(254));
+        assert!(!page.fits(255));
+        page.push(&[0; 254]);
+        assert!(!page.fits(0));
+    }
+
+    #[test]
+    fn resampler_interpolates_linearly_across_calls() {
+        // A ramp is reproduced exactly by linear interpolation
+        let frames = 4410;
+        let input: Vec<f32> = (0..frames * 2)
+            .map(|i| (i / 2) as f32 + (i % 2) as f32 * 10_000.0)
+            .collect();
+
+        let mut whole = Vec::new();
+        LinearResampler::new(44100, 48000, 2).process(&input, &mut whole);
+        let mut pieces = Vec::new();
+        let mut resampler = LinearResampler::new(44100, 48000, 2);
+        for chunk in input.chunks(2 * 37) {
+            resampler.process(chunk, &mut pieces);
+        }
+        resampler.process(&[], &mut pieces);
+
+        assert_eq!(whole.len(), pieces.len());
+        assert!((whole.len() / 2).abs_diff(frames * 48000 / 44100) <= 1);
+        let step = 44100.0 / 48000.0;
+        for (frame, values) in pieces.chunks_exact(2).enumerate() {
+            let expected = frame as f64 * step;
+            assert!((values[0] as f64 - expected).abs() < 1e-2, "{}", frame);
+            assert!(
+                (values[1] as f64 - expected - 10_000.0).abs() < 1e-2,
+                "{}",
+                frame
+            );
+        }
+    }
+
+    #[test]
+    fn wav_round_trips_through_opus() {
+        for (rate, channels) in [(48000, 1), (48000, 2), (44100, 1), (44100, 2), (16000, 1)] {
+            let frames = rate as usize * 3 / 2;
+            let source = tone(rate, channels, frames);
+            let (opus, duration) =
+                encode_wav_as_opus(Cursor::new(wav(rate, channels, &source)), Vec::new()).unwrap();
+            assert!((duration - 1.5).abs() < 1e-3, "{} Hz: {}", rate, duration);
+
+            let parsed = pages(&opus);
+            let head = parsed[0].body;
+            let pre_skip = u16::from_le_bytes([head[10], head[11]]) as usize;
+            assert!(pre_skip > 0);
+            assert_eq!(parsed.last().unwrap().flags, PAGE_FLAG_EOS);
+
+            let decoded = decode_ogg_opus(&opus).unwrap();
+            assert_eq!(decoded.sample_rate, 48000);
+            assert_eq!(decoded.channels, channels);
+            let decoded_frames = decoded.samples.len() / channels as usize;
+            let expected_frames = frames * 48000 / rate as usize;
+            // Resampling may round by a frame; trimming is otherwise exact
+            assert!(
+                decoded_frames.abs_diff(expected_frames) <= 1,
+                "{} Hz x{}: {} frames, expected {}",
+                rate,
+                channels,
+                decoded_frames,
+                expected_frames
+            );
+
+            // Pre-skip removed: the audio lines up with the source up to the codec's own
+            // phase shift of a few samples, not late by the encoder delay
+            if rate == 48000 {
+                let lag = best_lag(&source, &decoded.samples, channels as usize);
+                assert!(
+                    lag.abs() < pre_skip as i64 / 16,
+                    "{} Hz x{}: shifted by {}",
+                    rate,
+                    channels,
+                    lag
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn writing_after_finish_chains_a_new_stream() {
+        let mut encoder = OpusEncoder::new(Vec::new(), 48000, 1).unwrap();
+        encoder.write_samples(&tone(48000, 1, 48000)).unwrap();
+        encoder.finish_stream().unwrap();
+        encoder.finish_stream().unwrap(); // No-op once finished
+        encoder.write_samples(&tone(48000, 1, 24000)).unwrap();
+        let opus = encoder.finish().unwrap();
+
+        let parsed = pages(&opus);
+        let starts: Vec<_> = parsed
+            .iter()
+            .filter(|page| page.flags & PAGE_FLAG_BOS != 0)
+            .collect();
+        let ends: Vec<_> = parsed
+            .iter()
+            .filter(|page| page.flags & PAGE_FLAG_EOS != 0)
+            .collect();
+        assert_eq!(starts.len(), 2);
+        assert_eq!(ends.len(), 2);
+        assert_ne!(starts[0].serial, starts[1].serial);
+        assert!(starts[1].body.starts_with(b"OpusHead"));
+
+        let decoded = decode_ogg_opus(&opus).unwrap();
+        assert_eq!(decoded.samples.len(), 72000);
+    }
+
+    #[test]
+    fn keeps_the_audio_before_a_truncated_page() {
+        let mut encoder = OpusEncoder::new(Vec::new(), 48000, 1).unwrap();
+        for chunk in tone(48000, 1, 48000 * 2).chunks(4800) {
+            encoder.write_samples(chunk).unwrap();
+            encoder.flush_page().unwrap();
+        }
+        let opus = encoder.finish().unwrap();
+        let full = decode_ogg_opus(&opus).unwrap();
+
+        let cut = opus.len() * 2 / 3;
+        let partial = decode_ogg_opus(&opus[..cut]).unwrap();
+        assert!(partial.samples.len() > 48000);
+        assert!(partial.samples.len() < full.samples.len());
+        assert_eq!(partial.samples, full.samples[..partial.samples.len()]);
+
+        // Damage in the middle stops decoding there too
+        let mut damaged = opus.clone();
+        damaged[cut] ^= 0xFF;
+        let recovered = decode_ogg_opus(&damaged).unwrap();
+        assert!(recovered.samples.len() < full.samples.len());
+        assert_eq!(recovered.samples, full.samples[..recovered.samples.len()]);
+
+        assert!(decode_ogg_opus(&opus[..20]).is_err());
+    }
+}
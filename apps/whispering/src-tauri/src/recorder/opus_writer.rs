@@ -0,0 +1,114 @@
+use crate::recorder::encryption::RecordingFile;
+use crate::recorder::opus::OpusEncoder;
+use crate::recorder::writer::WriterOptions;
+use crate::recorder::writer_stats::WriterStats;
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::{debug, info};
+
+/// Extension of Opus recordings
+pub const OPUS_EXTENSION: &str = "opus";
+
+/// Extension of Opus recordings that are encrypted at rest
+pub const ENCRYPTED_OPUS_EXTENSION: &str = "opus.enc";
+
+/// Ogg Opus file writer for compact speech recordings.
+///
+/// About 20x smaller than 16-bit WAV, which keeps long recordings under the upload
+/// limits of cloud transcription APIs. Opus is lossy and always works on float audio,
+/// so the session's sample format doesn't apply. Completed pages are flushed every
+/// second, so a recording cut short by a crash is still playable. Markers are returned
+/// from the session but not embedded in the file.
+pub struct OpusWriter {
+    encoder: OpusEncoder<BufWriter<RecordingFile>>,
+    last_page_flush: Instant,
+    stats: WriterStats,
+}
+
+impl OpusWriter {
+    /// Create a new Ogg Opus file with the given options and write the stream headers
+    pub fn with_options(
+        file_path: PathBuf,
+        sample_rate: u32,
+        channels: u16,
+        options: WriterOptions,
+    ) -> io::Result<Self> {
+        let file = RecordingFile::create(&file_path, options.encryption_key.as_ref())?;
+        let encrypted = file.is_encrypted();
+        let encoder = OpusEncoder::new(BufWriter::new(file), sample_rate, channels)?;
+
+        info!(
+            "Created Opus file at {:?}: {}Hz, {} channels{}",
+            file_path,
+            sample_rate,
+            channels,
+            if encrypted { ", encrypted" } else { "" }
+        );
+
+        Ok(Self {
+            encoder,
+            last_page_flush: Instant::now(),
+            stats: WriterStats::new(file_path, sample_rate, channels, options.encryption_key),
+        })
+    }
+
+    /// Write f32 samples
+    pub fn write_samples_f32(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            self.stats.analyze(sample);
+        }
+        self.encoder.write_samples(samples)?;
+
+        self.on_samples_written(samples.len())
+    }
+
+    /// Account for written samples and flush completed pages periodically (every second)
+    fn on_samples_written(&mut self, count: usize) -> io::Result<()> {
+        self.stats.add_samples(count);
+
+        if self.last_page_flush.elapsed().as_secs() >= 1 {
+            self.encoder.flush_page()?;
+            self.last_page_flush = Instant::now();
+            debug!(
+                "Flushed Opus pages: {} samples written ({:.2} seconds)",
+                self.stats.samples_written(),
+                self.stats.duration_seconds()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Encode buffered audio and end the Opus stream.
+    /// Recording can continue afterwards, new audio goes into a chained stream.
+    pub fn finalize(&mut self) -> io::Result<()> {
+        self.encoder.finish_stream()?;
+        self.stats.write_peaks();
+
+        info!(
+            "Finalized Opus file {:?}: {} samples, {:.2} seconds",
+            self.stats.file_path(),
+            self.stats.samples_written(),
+            self.stats.duration_seconds()
+        );
+
+        Ok(())
+    }
+
+    pub fn stats(&self) -> &WriterStats {
+        &self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut WriterStats {
+        &mut self.stats
+    }
+}
+
+impl Drop for OpusWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finalize() {
+            tracing::error!("Failed to finalize Opus file on drop: {}", e);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::recorder::encryption::{RecordingFile, RecordingKey};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Number of peak buckets generated per second of audio
+const PEAKS_PER_SECOND: u32 = 20;
+
+/// Min/max waveform summary - returned to frontend and persisted next to the recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaveformPeaks {
+    pub sample_rate: u32,
+    pub frames_per_peak: u32,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+/// Accumulates min/max peaks per fixed-size bucket of frames while recording
+pub struct PeakAccumulator {
+    sample_rate: u32,
+    channels: u16,
+    frames_per_peak: u32,
+    frame_in_bucket: u32,
+    channel_in_frame: u16,
+    bucket_min: f32,
+    bucket_max: f32,
+    min: Vec<f32>,
+    max: Vec<f32>,
+}
+
+impl PeakAccumulator {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            sample_rate,
+            channels: channels.max(1),
+            frames_per_peak: (sample_rate / PEAKS_PER_SECOND).max(1),
+            frame_in_bucket: 0,
+            channel_in_frame: 0,
+            // Empty buckets start out inverted, so the first sample sets both ends
+            bucket_min: f32::INFINITY,
+            bucket_max: f32::NEG_INFINITY,
+            min: Vec::new(),
+            max: Vec::new(),
+        }
+    }
+
+    /// Add one interleaved sample; channels are folded into a single envelope
+    #[inline]
+    pub fn push(&mut self, sample: f32) {
+        if sample < self.bucket_min {
+            self.bucket_min = sample;
+        }
+        if sample > self.bucket_max {
+            self.bucket_max = sample;
+        }
+
+        self.channel_in_frame += 1;
+        if self.channel_in_frame < self.channels {
+            return;
+        }
+        self.channel_in_frame = 0;
+
+        self.frame_in_bucket += 1;
+        if self.frame_in_bucket >= self.frames_per_peak {
+            self.min.push(self.bucket_min);
+            self.max.push(self.bucket_max);
+            self.frame_in_bucket = 0;
+            self.bucket_min = f32::INFINITY;
+            self.bucket_max = f32::NEG_INFINITY;
+        }
+    }
+
+    /// Snapshot of all peaks so far, including the partially filled last bucket
+    pub fn peaks(&self) -> WaveformPeaks {
+        let mut min = self.min.clone();
+        let mut max = self.max.clone();
+        if self.frame_in_bucket > 0 {
+            min.push(self.bucket_min);
+            max.push(self.bucket_max);
+        }
+
+        WaveformPeaks {
+            sample_rate: self.sample_rate,
+            frames_per_peak: self.frames_per_peak,
+            min,
+            max,
+        }
+    }
+}
+
+/// Path of the peaks file stored next to a recording (`<id>.peaks.json` for any audio format,
+/// `<id>.peaks.json.enc` next to encrypted recordings)
+pub fn peaks_file_path(audio_path: &Path) -> PathBuf {
+    let name = audio_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let id = name.split('.').next().unwrap_or_default();
+    let suffix = if name.ends_with(".enc") { ".enc" } else { "" };
+    audio_path.with_file_name(format!("{}.peaks.json{}", id, suffix))
+}
+
+/// Write peaks next to the recording so waveforms can be drawn without decoding audio.
+/// The peaks outline the speech, so they are encrypted with the recording's key if it has one.
+pub fn write_peaks_file(
+    audio_path: &Path,
+    peaks: &WaveformPeaks,
+    key: Option<&RecordingKey>,
+) -> io::Result<()> {
+    let json = serde_json::to_vec(peaks).map_err(io::Error::other)?;
+    let mut file = RecordingFile::create(&peaks_file_path(audio_path), key)?;
+    file.write_all(&json)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::encryption::RecordingKeyStore;
+
+    #[test]
+    fn folds_channels_into_one_envelope_per_bucket() {
+        // 100 Hz makes buckets of 5 frames
+        let mut peaks = PeakAccumulator::new(100, 2);
+        let frames: [(f32, f32); 12] = [
+            (0.1, -0.2),
+            (0.3, 0.0),
+            (0.0, 0.0),
+            (-0.5, 0.2),
+            (0.0, 0.0),
+            (0.9, 0.8),
+            (0.7, 0.6),
+            (0.5, 0.4),
+            (0.3, 0.2),
+            (0.1, 0.05),
+            (-1.0, 0.0),
+            (0.0, 0.25),
+        ];
+        for (left, right) in frames {
+            peaks.push(left);
+            peaks.push(right);
+        }
+
+        let summary = peaks.peaks();
+        assert_eq!((summary.sample_rate, summary.frames_per_peak), (100, 5));
+        // The last bucket is only partly filled
+        assert_eq!(summary.min, [-0.5, 0.05, -1.0]);
+        assert_eq!(summary.max, [0.3, 0.9, 0.25]);
+
+        // Taking a snapshot doesn't close the partial bucket
+        peaks.push(0.5);
+        peaks.push(0.0);
+        assert_eq!(peaks.peaks().max, [0.3, 0.9, 0.5]);
+    }
+
+    #[test]
+    fn no_audio_has_no_peaks() {
+        let summary = PeakAccumulator::new(48000, 1).peaks();
+        assert_eq!(summary.frames_per_peak, 2400);
+        assert!(summary.min.is_empty() && summary.max.is_empty());
+    }
+
+    #[test]
+    fn peaks_file_sits_next_to_the_recording() {
+        let cases = [
+            ("/r/abc.wav", "/r/abc.peaks.json"),
+            ("/r/abc.wav.enc", "/r/abc.peaks.json.enc"),
+            ("/r/abc.flac", "/r/abc.peaks.json"),
+            ("/r/abc.flac.enc", "/r/abc.peaks.json.enc"),
+            ("/r/abc.opus", "/r/abc.peaks.json"),
+        ];
+        for (audio, peaks) in cases {
+            assert_eq!(peaks_file_path(Path::new(audio)), Path::new(peaks));
+        }
+    }
+
+    #[test]
+    fn peaks_files_are_encrypted_with_the_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut accumulator = PeakAccumulator::new(20, 1);
+        for sample in [0.5, -0.25, 1.0] {
+            accumulator.push(sample);
+        }
+        let peaks = accumulator.peaks();
+        let key = RecordingKey::generate();
+        let keys = RecordingKeyStore::with_key(key.clone());
+
+        for (name, key) in [("plain.wav", None), ("secret.wav.enc", Some(&key))] {
+            let audio = dir.path().join(name);
+            write_peaks_file(&audio, &peaks, key).unwrap();
+
+            let raw = std::fs::read(peaks_file_path(&audio)).unwrap();
+            assert_eq!(
+                serde_json::from_slice::<WaveformPeaks>(&raw).is_ok(),
+                key.is_none(),
+                "{}",
+                name
+            );
+            let bytes = keys.read_recording(&peaks_file_path(&audio)).unwrap();
+            let read: WaveformPeaks = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!((read.min, read.max), (peaks.min.clone(), peaks.max.clone()));
+        }
+    }
+}
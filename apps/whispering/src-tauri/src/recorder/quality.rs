@@ -0,0 +1,271 @@
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Samples at or above this magnitude count as clipped
+const CLIP_THRESHOLD: f32 = 0.999;
+
+/// Samples below one 16-bit step count as digital silence
+const SILENCE_THRESHOLD: f32 = 1.0 / 32768.0;
+
+/// Length of the blocks used to build the level histogram
+const BLOCK_MS: u32 = 20;
+
+/// Level histogram covers -120 dBFS..0 dBFS in half-dB bins
+const HISTOGRAM_MIN_DB: f32 = -120.0;
+const HISTOGRAM_BINS_PER_DB: f32 = 2.0;
+const HISTOGRAM_BINS: usize = 240;
+
+/// Minimum number of blocks (one second) before an SNR estimate is reported
+const MIN_BLOCKS_FOR_SNR: u64 = (1000 / BLOCK_MS) as u64;
+
+/// Problems detected in the input signal while recording
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SignalWarning {
+    /// The first second of input was digital silence (muted device or denied permission)
+    #[serde(rename_all = "camelCase")]
+    DeadInput { seconds_analyzed: f32 },
+}
+
+/// Called from the audio thread when a signal warning is raised
+pub type SignalWarningHandler = Arc<dyn Fn(SignalWarning) + Send + Sync>;
+
+/// Input signal statistics - returned to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignalQuality {
+    pub total_samples: u64,
+    pub clipped_samples: u64,
+    pub clipping_ratio: f32,
+    pub silence_ratio: f32,
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    pub noise_floor_dbfs: Option<f32>, // None until enough audio was analyzed
+    pub snr_db: Option<f32>,
+    pub dead_input: bool, // The whole recording was digital silence
+}
+
+/// Tracks clipping, digital silence and level distribution of recorded samples
+pub struct SignalAnalyzer {
+    first_second_samples: u64,
+    block_samples: u32,
+    total_samples: u64,
+    clipped_samples: u64,
+    silent_samples: u64,
+    peak: f32,
+    sum_squares: f64,
+    block_sum_squares: f64,
+    block_position: u32,
+    block_count: u64,
+    level_histogram: [u64; HISTOGRAM_BINS],
+    dead_input_checked: bool,
+    pending_warning: Option<SignalWarning>,
+}
+
+impl SignalAnalyzer {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as u32;
+        Self {
+            first_second_samples: sample_rate as u64 * channels as u64,
+            block_samples: (sample_rate * BLOCK_MS / 1000).max(1) * channels,
+            total_samples: 0,
+            clipped_samples: 0,
+            silent_samples: 0,
+            peak: 0.0,
+            sum_squares: 0.0,
+            block_sum_squares: 0.0,
+            block_position: 0,
+            block_count: 0,
+            level_histogram: [0; HISTOGRAM_BINS],
+            dead_input_checked: false,
+            pending_warning: None,
+        }
+    }
+
+    /// Add one interleaved sample
+    #[inline]
+    pub fn push(&mut self, sample: f32) {
+        let magnitude = sample.abs();
+
+        self.total_samples += 1;
+        if magnitude >= CLIP_THRESHOLD {
+            self.clipped_samples += 1;
+        }
+        if magnitude < SILENCE_THRESHOLD {
+            self.silent_samples += 1;
+        }
+        if magnitude > self.peak {
+            self.peak = magnitude;
+        }
+
+        let square = sample as f64 * sample as f64;
+        self.sum_squares += square;
+        self.block_sum_squares += square;
+        self.block_position += 1;
+        if self.block_position >= self.block_samples {
+            let rms = (self.block_sum_squares / self.block_samples as f64).sqrt() as f32;
+            self.level_histogram[histogram_bin(to_dbfs(rms))] += 1;
+            self.block_count += 1;
+            self.block_sum_squares = 0.0;
+            self.block_position = 0;
+        }
+
+        // Flat input during the first second almost always means a muted or blocked mic
+        if !self.dead_input_checked && self.total_samples >= self.first_second_samples {
+            self.dead_input_checked = true;
+            if self.silent_samples == self.total_samples {
+                self.pending_warning = Some(SignalWarning::DeadInput {
+                    seconds_analyzed: 1.0,
+                });
+            }
+        }
+    }
+
+    /// Take the warning raised since the last call, if any
+    pub fn take_warning(&mut self) -> Option<SignalWarning> {
+        self.pending_warning.take()
+    }
+
+    /// Summarize everything analyzed so far
+    pub fn quality(&self) -> SignalQuality {
+        let total = self.total_samples.max(1) as f64;
+        let rms = (self.sum_squares / total).sqrt() as f32;
+
+        // Estimate noise floor and speech level from the quiet and loud ends of the
+        // block level distribution
+        let (noise_floor_dbfs, snr_db) = if self.block_count >= MIN_BLOCKS_FOR_SNR {
+            let noise = self.level_percentile(0.10);
+            let signal = self.level_percentile(0.95);
+            (Some(noise), Some((signal - noise).max(0.0)))
+        } else {
+            (None, None)
+        };
+
+        SignalQuality {
+            total_samples: self.total_samples,
+            clipped_samples: self.clipped_samples,
+            clipping_ratio: (self.clipped_samples as f64 / total) as f32,
+            silence_ratio: (self.silent_samples as f64 / total) as f32,
+            peak_dbfs: to_dbfs(self.peak),
+            rms_dbfs: to_dbfs(rms),
+            noise_floor_dbfs,
+            snr_db,
+            dead_input: self.total_samples > 0 && self.silent_samples == self.total_samples,
+        }
+    }
+
+    /// Block level (dBFS) below which the given fraction of blocks fall
+    fn level_percentile(&self, fraction: f64) -> f32 {
+        let target = (self.block_count as f64 * fraction).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bin, &count) in self.level_histogram.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return HISTOGRAM_MIN_DB + bin as f32 / HISTOGRAM_BINS_PER_DB;
+            }
+        }
+        0.0
+    }
+}
+
+/// Convert a linear amplitude to dBFS, floored at the histogram minimum
+pub fn to_dbfs(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return HISTOGRAM_MIN_DB;
+    }
+    (20.0 * amplitude.log10()).max(HISTOGRAM_MIN_DB)
+}
+
+fn histogram_bin(dbfs: f32) -> usize {
+    let bin = ((dbfs - HISTOGRAM_MIN_DB) * HISTOGRAM_BINS_PER_DB) as usize;
+    bin.min(HISTOGRAM_BINS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn analyze(analyzer: &mut SignalAnalyzer, level: f32, samples: usize) {
+        for i in 0..samples {
+            analyzer.push(if i % 2 == 0 { level } else { -level });
+        }
+    }
+
+    #[test]
+    fn counts_clipped_and_silent_samples() {
+        let mut analyzer = SignalAnalyzer::new(SAMPLE_RATE, 1);
+        analyze(&mut analyzer, 1.0, 10);
+        analyze(&mut analyzer, 0.0, 40);
+        analyze(&mut analyzer, 0.5, 50);
+
+        let quality = analyzer.quality();
+        assert_eq!(quality.total_samples, 100);
+        assert_eq!(quality.clipped_samples, 10);
+        assert_eq!(quality.clipping_ratio, 0.1);
+        assert_eq!(quality.silence_ratio, 0.4);
+        assert_eq!(quality.peak_dbfs, 0.0);
+        // Mean square of 10 * 1.0 and 50 * 0.25 over 100 samples
+        assert!((quality.rms_dbfs - to_dbfs(0.225f32.sqrt())).abs() < 1e-4);
+        assert!(!quality.dead_input);
+        // Less than a second of blocks is too little for an SNR estimate
+        assert_eq!(quality.noise_floor_dbfs, None);
+        assert_eq!(quality.snr_db, None);
+    }
+
+    #[test]
+    fn warns_once_when_the_first_second_is_silent() {
+        let mut analyzer = SignalAnalyzer::new(SAMPLE_RATE, 2);
+        analyze(&mut analyzer, 0.0, 2 * SAMPLE_RATE as usize - 1);
+        assert!(analyzer.take_warning().is_none());
+        analyze(&mut analyzer, 0.0, 1);
+        assert!(matches!(
+            analyzer.take_warning(),
+            Some(SignalWarning::DeadInput { .. })
+        ));
+        assert!(analyzer.take_warning().is_none());
+
+        analyze(&mut analyzer, 0.0, 4 * SAMPLE_RATE as usize);
+        assert!(analyzer.take_warning().is_none());
+        assert!(analyzer.quality().dead_input);
+        analyze(&mut analyzer, 0.1, 1);
+        assert!(!analyzer.quality().dead_input);
+    }
+
+    #[test]
+    fn quiet_input_in_the_first_second_is_not_dead() {
+        let mut analyzer = SignalAnalyzer::new(SAMPLE_RATE, 1);
+        analyze(&mut analyzer, 0.0, 1000);
+        analyze(&mut analyzer, 2.0 / 32768.0, 1);
+        analyze(&mut analyzer, 0.0, SAMPLE_RATE as usize);
+        assert!(analyzer.take_warning().is_none());
+    }
+
+    #[test]
+    fn estimates_noise_floor_and_snr_from_block_levels() {
+        let mut analyzer = SignalAnalyzer::new(SAMPLE_RATE, 1);
+        let block = (SAMPLE_RATE * BLOCK_MS / 1000) as usize;
+        // 80 blocks of background at -60 dBFS and 20 of speech at -6 dBFS
+        for i in 0..100 {
+            let level = if i % 5 == 0 { 0.5 } else { 0.001 };
+            analyze(&mut analyzer, level, block);
+        }
+
+        let quality = analyzer.quality();
+        let noise = quality.noise_floor_dbfs.unwrap();
+        let snr = quality.snr_db.unwrap();
+        assert!((noise - -60.0).abs() <= 0.5, "noise floor {}", noise);
+        assert!((snr - 54.0).abs() <= 1.0, "snr {}", snr);
+    }
+
+    #[test]
+    fn converts_amplitudes_to_dbfs() {
+        assert_eq!(to_dbfs(1.0), 0.0);
+        assert!((to_dbfs(0.5) - -6.0206).abs() < 1e-3);
+        assert_eq!(to_dbfs(0.0), HISTOGRAM_MIN_DB);
+        assert_eq!(to_dbfs(1e-9), HISTOGRAM_MIN_DB);
+        assert_eq!(histogram_bin(HISTOGRAM_MIN_DB), 0);
+        assert_eq!(histogram_bin(6.0), HISTOGRAM_BINS - 1);
+    }
+}
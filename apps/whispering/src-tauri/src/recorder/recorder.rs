@@ -1,12 +1,22 @@
-use crate::recorder::wav_writer::WavWriter;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, SampleFormat, Stream};
+use crate::recorder::live_reader::LiveRecording;
+use crate::recorder::markers::RecordingMarker;
+use crate::recorder::network_sink::NetworkSink;
+use crate::recorder::peaks::{peaks_file_path, WaveformPeaks};
+use crate::recorder::quality::{SignalQuality, SignalWarningHandler};
+use crate::recorder::scope::validate_recording_id;
+use crate::recorder::sink::{AudioSink, FileSink, MemoryBuffer, MemorySink, SinkId, SinkSet};
+use crate::recorder::source::{open_source, AudioSource};
+use crate::recorder::storage::{
+    parse_recording_file_name, register_recording, unregister_recordings,
+};
+use crate::recorder::writer::{RecordingWriter, WriterOptions};
+use cpal::traits::{DeviceTrait, HostTrait};
 use serde::Serialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread::{self, JoinHandle};
-use tracing::{debug, error, info};
+use tracing::{debug, info, warn};
 
 /// Simple result type using String for errors
 pub type Result<T> = std::result::Result<T, String>;
@@ -19,85 +29,62 @@ pub struct AudioRecording {
     pub sample_rate: u32,
     pub channels: u16,
     pub duration_seconds: f32,
-    pub file_path: Option<String>, // Path to the WAV file
+    pub file_path: Option<String>, // Path to the recording file
+    pub encrypted: bool, // Read it back through `read_recording`, never store it decrypted
+    pub waveform_peaks: Option<WaveformPeaks>, // Also persisted as <id>.peaks.json(.enc)
+    pub signal_quality: Option<SignalQuality>,
+    pub markers: Vec<RecordingMarker>, // Also stored as cue points in WAV recordings
 }
 
-/// Minimal wrapper to handle the Stream in its own thread
-/// This is necessary because CPAL streams aren't Send+Sync on macOS
-struct StreamHolder {
-    thread: Option<JoinHandle<()>>,
-    is_recording: Arc<AtomicBool>,
-    should_stop: Arc<AtomicBool>,
-}
-
-impl StreamHolder {
-    fn new<F>(create_stream: F, is_recording: Arc<AtomicBool>) -> Result<Self>
-    where
-        F: FnOnce() -> Result<Stream> + Send + 'static,
-    {
-        let should_stop = Arc::new(AtomicBool::new(false));
-        let should_stop_clone = should_stop.clone();
-
-        // Create and run the stream in its own thread
-        let thread = thread::spawn(move || {
-            // Create the stream in this thread
-            let stream = match create_stream() {
-                Ok(s) => s,
-                Err(e) => {
-                    error!("Failed to create stream in thread: {}", e);
-                    return;
-                }
-            };
-
-            // Keep the stream alive until told to stop
-            while !should_stop_clone.load(Ordering::Acquire) {
-                thread::sleep(std::time::Duration::from_millis(100));
-            }
-            // Stream drops here, which stops it
-            drop(stream);
-        });
-
-        Ok(Self {
-            thread: Some(thread),
-            is_recording,
-            should_stop,
-        })
-    }
-
-    fn stop(&mut self) {
-        self.is_recording.store(false, Ordering::Release);
-        self.should_stop.store(true, Ordering::Release);
-        if let Some(thread) = self.thread.take() {
-            let _ = thread.join();
-        }
-    }
+/// Requested capture buffer size for a recording session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferSizeRequest {
+    /// Let the host decide (can be very large on some ALSA setups)
+    Default,
+    /// Exact number of frames per callback, must be supported by the device
+    Frames(u32),
+    /// Target latency in milliseconds, converted to frames and clamped to the device range
+    LatencyMs(u32),
 }
 
-impl Drop for StreamHolder {
-    fn drop(&mut self) {
-        self.stop();
-    }
+/// Negotiated session configuration - returned to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSessionInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub buffer_size_frames: Option<u32>, // None when the host default is used
+    pub buffer_latency_ms: Option<f32>,
+    pub supported_buffer_range: Option<(u32, u32)>, // None when the device doesn't report it
 }
 
 /// Simplified recorder state
 pub struct RecorderState {
-    stream_holder: Option<StreamHolder>,
-    writer: Option<Arc<Mutex<WavWriter>>>,
+    source: Option<Box<dyn AudioSource>>,
+    writer: Option<Arc<Mutex<RecordingWriter>>>,
+    sinks: SinkSet,
+    file_sink: Option<SinkId>,
+    memory_buffers: HashMap<SinkId, MemoryBuffer>, // Stay readable until the session closes
     is_recording: Arc<AtomicBool>,
     sample_rate: u32,
     channels: u16,
     file_path: Option<PathBuf>,
+    encrypted: bool,
 }
 
 impl RecorderState {
     pub fn new() -> Self {
         Self {
-            stream_holder: None,
+            source: None,
             writer: None,
+            sinks: SinkSet::new(),
+            file_sink: None,
+            memory_buffers: HashMap::new(),
             is_recording: Arc::new(AtomicBool::new(false)),
             sample_rate: 0,
             channels: 0,
             file_path: None,
+            encrypted: false,
         }
     }
 
@@ -113,84 +100,124 @@ impl RecorderState {
         Ok(devices)
     }
 
-    /// Initialize recording session - creates stream and WAV writer
+    /// Initialize recording session - opens the device and creates the file writer
+    #[allow(clippy::too_many_arguments)]
     pub fn init_session(
         &mut self,
         device_name: String,
         output_folder: PathBuf,
         recording_id: String,
         preferred_sample_rate: Option<u32>,
-    ) -> Result<()> {
+        buffer_size: BufferSizeRequest,
+        on_signal_warning: Option<SignalWarningHandler>,
+        writer_options: WriterOptions,
+    ) -> Result<RecordingSessionInfo> {
+        // Find the device (or synthetic source) and negotiate its format
+        let source = open_source(&device_name, preferred_sample_rate, buffer_size)?;
+
+        self.init_session_with_source(
+            source,
+            output_folder,
+            recording_id,
+            on_signal_warning,
+            writer_options,
+        )
+    }
+
+    /// Initialize recording session from an already opened audio source
+    pub fn init_session_with_source(
+        &mut self,
+        mut source: Box<dyn AudioSource>,
+        output_folder: PathBuf,
+        recording_id: String,
+        on_signal_warning: Option<SignalWarningHandler>,
+        mut writer_options: WriterOptions,
+    ) -> Result<RecordingSessionInfo> {
+        // Reject ids that could escape the output folder (e.g. "../../x")
+        validate_recording_id(&recording_id).map_err(|e| e.to_string())?;
+
         // Clean up any existing session
         self.close_session()?;
 
         // Create file path
-        let file_path = output_folder.join(format!("{}.wav", recording_id));
-
-        // Find the device
-        let host = cpal::default_host();
-        let device = find_device(&host, &device_name)?;
-
-        // Get optimal config for voice with optional preferred sample rate
-        let config = get_optimal_config(&device, preferred_sample_rate)?;
-        let sample_format = config.sample_format();
-        let sample_rate = config.sample_rate().0;
-        let channels = config.channels();
-
-        // Create WAV writer
-        let writer = WavWriter::new(file_path.clone(), sample_rate, channels)
-            .map_err(|e| format!("Failed to create WAV file: {}", e))?;
+        let encrypted = writer_options.encryption_key.is_some();
+        let extension = writer_options.format.extension(encrypted);
+        let file_path = output_folder.join(format!("{}.{}", recording_id, extension));
+
+        let format = source.format().clone();
+        let sample_rate = format.sample_rate;
+        let channels = format.channels;
+
+        // Describe the recording by its id and source unless told otherwise
+        let info = &mut writer_options.info;
+        info.title.get_or_insert_with(|| recording_id.clone());
+        info.device.get_or_insert_with(|| source.name().to_string());
+
+        // Create the file writer
+        let writer =
+            RecordingWriter::create(file_path.clone(), sample_rate, channels, writer_options)
+                .map_err(|e| format!("Failed to create recording file: {}", e))?;
         let writer = Arc::new(Mutex::new(writer));
 
-        // Create stream config
-        let stream_config = cpal::StreamConfig {
-            channels,
-            sample_rate: cpal::SampleRate(sample_rate),
-            buffer_size: cpal::BufferSize::Default,
-        };
+        // Retention only manages recordings listed in the folder's manifest
+        if let Err(e) = register_recording(&output_folder, &recording_id) {
+            warn!(
+                "Failed to add recording {} to the manifest: {}",
+                recording_id, e
+            );
+        }
 
         // Create fresh recording flag
         self.is_recording = Arc::new(AtomicBool::new(false));
         let is_recording = self.is_recording.clone();
 
-        // Create the stream holder with a closure that builds the stream
-        let writer_clone = writer.clone();
-        let is_recording_clone = is_recording.clone();
-
-        let stream_holder = StreamHolder::new(
-            move || match sample_format {
-                SampleFormat::F32 => {
-                    build_stream_f32(&device, &stream_config, is_recording_clone, writer_clone)
-                }
-                SampleFormat::I16 => {
-                    build_stream_i16(&device, &stream_config, is_recording_clone, writer_clone)
-                }
-                SampleFormat::U16 => {
-                    build_stream_u16(&device, &stream_config, is_recording_clone, writer_clone)
-                }
-                _ => Err("Unsupported sample format".to_string()),
-            },
-            is_recording,
-        )?;
+        // The file writer is the session's first sink, others can be added later
+        let sinks = SinkSet::new();
+        let file_sink = sinks
+            .add(Box::new(FileSink::new(writer.clone(), on_signal_warning)))
+            .map_err(|e| format!("Failed to add file sink: {}", e))?;
+
+        // Samples are only handed to the sinks while the recording flag is set
+        let sinks_clone = sinks.clone();
+        source.start(Box::new(move |data: &[f32]| {
+            if is_recording.load(Ordering::Acquire) {
+                sinks_clone.write(data);
+            }
+        }))?;
+
+        info!(
+            "Recording session initialized from '{}': {} Hz, {} channels, buffer: {:?} frames, file: {:?}",
+            source.name(),
+            sample_rate,
+            channels,
+            format.buffer_frames,
+            file_path
+        );
 
         // Store everything
-        self.stream_holder = Some(stream_holder);
+        self.source = Some(source);
         self.writer = Some(writer);
+        self.sinks = sinks;
+        self.file_sink = Some(file_sink);
         self.sample_rate = sample_rate;
         self.channels = channels;
         self.file_path = Some(file_path);
+        self.encrypted = encrypted;
 
-        info!(
-            "Recording session initialized: {} Hz, {} channels, file: {:?}",
-            sample_rate, channels, self.file_path
-        );
-
-        Ok(())
+        Ok(RecordingSessionInfo {
+            sample_rate,
+            channels,
+            buffer_size_frames: format.buffer_frames,
+            buffer_latency_ms: format
+                .buffer_frames
+                .map(|frames| frames as f32 * 1000.0 / sample_rate as f32),
+            supported_buffer_range: format.supported_buffer_range,
+        })
     }
 
     /// Start recording - just set the flag
     pub fn start_recording(&mut self) -> Result<()> {
-        if self.stream_holder.is_none() {
+        if self.source.is_none() {
             return Err("No recording session initialized".to_string());
         }
 
@@ -205,13 +232,19 @@ impl RecorderState {
         // Stop recording flag first
         self.is_recording.store(false, Ordering::Release);
 
-        // Finalize the WAV file and get metadata
+        // Finalize the recording file and get metadata
+        let mut waveform_peaks = None;
+        let mut signal_quality = None;
+        let mut markers = Vec::new();
         let (sample_rate, channels, duration) = if let Some(writer) = &self.writer {
             let mut w = writer
                 .lock()
                 .map_err(|e| format!("Failed to lock writer: {}", e))?;
             w.finalize()
-                .map_err(|e| format!("Failed to finalize WAV: {}", e))?;
+                .map_err(|e| format!("Failed to finalize recording: {}", e))?;
+            waveform_peaks = Some(w.get_waveform_peaks());
+            signal_quality = Some(w.get_signal_quality());
+            markers = w.get_markers().to_vec();
             w.get_metadata()
         } else {
             (self.sample_rate, self.channels, 0.0)
@@ -230,22 +263,132 @@ impl RecorderState {
             channels,
             duration_seconds: duration,
             file_path,
+            encrypted: self.encrypted,
+            waveform_peaks,
+            signal_quality,
+            markers,
         })
     }
 
+    /// Add a sink that receives the session's audio alongside the recording file
+    pub fn add_sink(&mut self, mut sink: Box<dyn AudioSink>) -> Result<SinkId> {
+        if self.source.is_none() {
+            return Err("No recording session initialized".to_string());
+        }
+
+        sink.open(self.sample_rate, self.channels)
+            .map_err(|e| format!("Failed to open audio sink '{}': {}", sink.name(), e))?;
+        let name = sink.name().to_string();
+        let id = self
+            .sinks
+            .add(sink)
+            .map_err(|e| format!("Failed to add audio sink: {}", e))?;
+
+        info!("Added audio sink {} '{}'", id, name);
+        Ok(id)
+    }
+
+    /// Remove a sink added with `add_sink` and finish it
+    pub fn remove_sink(&mut self, id: SinkId) -> Result<()> {
+        if self.file_sink == Some(id) {
+            return Err("The recording file can't be removed from its session".to_string());
+        }
+
+        let mut sink = self
+            .sinks
+            .remove(id)
+            .map_err(|e| format!("Failed to remove audio sink: {}", e))?
+            .ok_or_else(|| format!("No audio sink with id {}", id))?;
+        sink.finish()
+            .map_err(|e| format!("Failed to finish audio sink '{}': {}", sink.name(), e))?;
+
+        info!("Removed audio sink {} '{}'", id, sink.name());
+        Ok(())
+    }
+
+    /// Stream the session's audio over the network. Refused for encrypted sessions, whose
+    /// audio must not leave the device unencrypted.
+    pub fn add_network_sink(&mut self, sink: NetworkSink) -> Result<SinkId> {
+        if self.encrypted {
+            return Err("Encrypted recordings can't be streamed over the network".to_string());
+        }
+        self.add_sink(Box::new(sink))
+    }
+
+    /// Keep the session's audio in memory, everything or the last `max_seconds`
+    pub fn add_memory_sink(&mut self, max_seconds: Option<f32>) -> Result<SinkId> {
+        let sink = MemorySink::new(max_seconds);
+        let buffer = sink.buffer();
+        let id = self.add_sink(Box::new(sink))?;
+        self.memory_buffers.insert(id, buffer);
+        Ok(id)
+    }
+
+    /// Interleaved samples collected by a memory sink, also after it was removed
+    pub fn memory_sink_samples(&self, id: SinkId) -> Result<Vec<f32>> {
+        self.memory_buffers
+            .get(&id)
+            .map(MemoryBuffer::samples)
+            .ok_or_else(|| format!("No memory sink with id {}", id))
+    }
+
+    /// Whether the open session writes an encrypted recording
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Ids and names of the session's sinks, the recording file included
+    pub fn list_sinks(&self) -> Vec<(SinkId, String)> {
+        self.sinks.list()
+    }
+
+    /// Drop a labelled marker at the current position of the active recording
+    pub fn add_marker(&mut self, label: String) -> Result<RecordingMarker> {
+        if !self.is_recording.load(Ordering::Acquire) {
+            return Err("Not currently recording".to_string());
+        }
+
+        let writer = self
+            .writer
+            .as_ref()
+            .ok_or_else(|| "No recording session initialized".to_string())?;
+        let mut w = writer
+            .lock()
+            .map_err(|e| format!("Failed to lock writer: {}", e))?;
+        let marker = w
+            .add_marker(label)
+            .map_err(|e| format!("Failed to add marker: {}", e))?;
+
+        info!(
+            "Added marker {} '{}' at {:.2}s",
+            marker.id, marker.label, marker.time_seconds
+        );
+        Ok(marker)
+    }
+
     /// Cancel recording - stop and delete the file
     pub fn cancel_recording(&mut self) -> Result<()> {
         // Stop recording
         self.is_recording.store(false, Ordering::Release);
 
-        // Delete the file if it exists
-        if let Some(file_path) = &self.file_path {
-            std::fs::remove_file(file_path).ok(); // Ignore errors
+        // Clear the session first so the writer has released the file
+        let file_path = self.file_path.clone();
+        self.close_session()?;
+
+        // Delete the file and its peaks if they exist
+        if let Some(file_path) = file_path {
+            std::fs::remove_file(&file_path).ok(); // Ignore errors
+            std::fs::remove_file(peaks_file_path(&file_path)).ok();
             debug!("Deleted recording file: {:?}", file_path);
-        }
 
-        // Clear the session
-        self.close_session()?;
+            let id = file_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_recording_file_name);
+            if let (Some(folder), Some((id, _))) = (file_path.parent(), id) {
+                unregister_recordings(folder, &[id.to_string()]).ok();
+            }
+        }
 
         Ok(())
     }
@@ -255,12 +398,15 @@ impl RecorderState {
         // Stop recording if active
         self.is_recording.store(false, Ordering::Release);
 
-        // Stop and drop the stream holder
-        if let Some(mut holder) = self.stream_holder.take() {
-            holder.stop();
+        // Stop and drop the audio source
+        if let Some(mut source) = self.source.take() {
+            source.stop();
         }
 
-        // Finalize and drop the writer
+        // Finish every sink, then finalize and drop the writer
+        self.sinks.finish_all();
+        self.file_sink = None;
+        self.memory_buffers.clear();
         if let Some(writer) = self.writer.take() {
             if let Ok(mut w) = writer.lock() {
                 let _ = w.finalize(); // Ignore errors during cleanup
@@ -269,6 +415,7 @@ impl RecorderState {
 
         // Clear state
         self.file_path = None;
+        self.encrypted = false;
         self.sample_rate = 0;
         self.channels = 0;
 
@@ -279,208 +426,216 @@ impl RecorderState {
     /// Get current recording ID if actively recording
     pub fn get_current_recording_id(&self) -> Option<String> {
         if self.is_recording.load(Ordering::Acquire) {
+            // Ids never contain '.', so this also strips multi-part extensions like .wav.enc
             self.file_path
                 .as_ref()
-                .and_then(|path| path.file_stem())
-                .and_then(|stem| stem.to_str())
+                .and_then(|path| path.file_name())
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.split('.').next())
                 .map(|s| s.to_string())
         } else {
             None
         }
     }
-}
 
-/// Find a recording device by name
-fn find_device(host: &cpal::Host, device_name: &str) -> Result<Device> {
-    // Handle "default" device
-    if device_name.to_lowercase() == "default" {
-        return host
-            .default_input_device()
-            .ok_or_else(|| "No default input device available".to_string());
+    /// Recording ID of the open session, whether or not it is recording
+    pub fn get_session_recording_id(&self) -> Option<String> {
+        self.file_path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+            .map(|s| s.to_string())
     }
 
-    // Find specific device
-    let devices: Vec<_> = host.input_devices().map_err(|e| e.to_string())?.collect();
-
-    for device in devices {
-        if let Ok(name) = device.name() {
-            if name == device_name {
-                return Ok(device);
-            }
-        }
+    /// Handle for reading the open session's recording while it is being written.
+    /// None when the session records FLAC or Opus, which can't be read until finished.
+    pub fn get_live_recording(&self) -> Result<Option<LiveRecording>> {
+        let writer = self
+            .writer
+            .as_ref()
+            .ok_or_else(|| "No recording session initialized".to_string())?;
+        let w = writer
+            .lock()
+            .map_err(|e| format!("Failed to lock writer: {}", e))?;
+        Ok(w.live_recording())
     }
-
-    Err(format!("Device '{}' not found", device_name))
 }
 
-/// Get optimal configuration for voice recording
-fn get_optimal_config(
-    device: &Device,
-    preferred_sample_rate: Option<u32>,
-) -> Result<cpal::SupportedStreamConfig> {
-    // Use preferred sample rate or default to 16kHz for voice
-    let target_sample_rate = preferred_sample_rate.unwrap_or(16000);
-
-    let configs: Vec<_> = device
-        .supported_input_configs()
-        .map_err(|e| e.to_string())?
-        .collect();
-
-    if configs.is_empty() {
-        return Err("No supported input configurations".to_string());
+impl Drop for RecorderState {
+    fn drop(&mut self) {
+        let _ = self.close_session();
     }
+}
 
-    // Try to find mono config with target sample rate
-    for config in &configs {
-        if config.channels() == 1 {
-            let min_rate = config.min_sample_rate().0;
-            let max_rate = config.max_sample_rate().0;
-            if min_rate <= target_sample_rate && max_rate >= target_sample_rate {
-                return Ok(config.with_sample_rate(cpal::SampleRate(target_sample_rate)));
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::encryption::RecordingKey;
+    use crate::recorder::synthetic_source::{ManualDriver, Signal, SyntheticSource};
+    use std::path::Path;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    /// Open a session on a manually paced sine source writing into `folder`
+    fn manual_session(recorder: &mut RecorderState, folder: &Path, id: &str) -> ManualDriver {
+        let (source, driver) =
+            SyntheticSource::manual(Signal::Sine { frequency: 440.0 }, SAMPLE_RATE, 1).unwrap();
+        recorder
+            .init_session_with_source(
+                Box::new(source),
+                folder.to_path_buf(),
+                id.to_string(),
+                None,
+                WriterOptions::default(),
+            )
+            .unwrap();
+        driver
     }
 
-    // Try stereo with target sample rate if mono not available
-    for config in &configs {
-        let min_rate = config.min_sample_rate().0;
-        let max_rate = config.max_sample_rate().0;
-        if min_rate <= target_sample_rate && max_rate >= target_sample_rate {
-            return Ok(config.with_sample_rate(cpal::SampleRate(target_sample_rate)));
-        }
-    }
+    #[test]
+    fn records_only_between_start_and_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = RecorderState::new();
+        let driver = manual_session(&mut recorder, dir.path(), "lifecycle");
+        assert_eq!(
+            recorder.get_session_recording_id().as_deref(),
+            Some("lifecycle")
+        );
+        assert_eq!(recorder.get_current_recording_id(), None);
 
-    // If target rate not supported, try to find closest rate
-    let mut best_config = None;
-    let mut best_diff = u32::MAX;
-
-    for config in &configs {
-        // Prefer mono
-        if config.channels() == 1 {
-            let min_rate = config.min_sample_rate().0;
-            let max_rate = config.max_sample_rate().0;
-
-            // Find closest supported rate
-            let closest_rate = if target_sample_rate < min_rate {
-                min_rate
-            } else if target_sample_rate > max_rate {
-                max_rate
-            } else {
-                target_sample_rate
-            };
-
-            let diff = (closest_rate as i32 - target_sample_rate as i32).abs() as u32;
-            if diff < best_diff {
-                best_diff = diff;
-                best_config = Some(config.with_sample_rate(cpal::SampleRate(closest_rate)));
-            }
-        }
+        // The source runs from init, but nothing is kept before start
+        assert_eq!(driver.push_frames(1600), 1600);
+
+        recorder.start_recording().unwrap();
+        assert_eq!(
+            recorder.get_current_recording_id().as_deref(),
+            Some("lifecycle")
+        );
+        driver.push_frames(4000);
+        driver.push_frames(4000);
+
+        let recording = recorder.stop_recording().unwrap();
+        assert_eq!(recorder.get_current_recording_id(), None);
+        driver.push_frames(1600);
+
+        assert_eq!(recording.sample_rate, SAMPLE_RATE);
+        assert_eq!(recording.channels, 1);
+        assert!((recording.duration_seconds - 0.5).abs() < 1e-6);
+
+        let path = PathBuf::from(recording.file_path.unwrap());
+        assert_eq!(path, dir.path().join("lifecycle.wav"));
+        recorder.close_session().unwrap();
+        assert_eq!(recorder.get_session_recording_id(), None);
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, SAMPLE_RATE);
+        assert_eq!(reader.duration(), 8000);
     }
 
-    // Return best config or fall back to default
-    best_config
-        .or_else(|| device.default_input_config().ok())
-        .ok_or_else(|| "Failed to find suitable audio configuration".to_string())
-}
+    #[test]
+    fn closed_session_stops_delivering() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = RecorderState::new();
+        let driver = manual_session(&mut recorder, dir.path(), "closed");
 
-/// Build stream for f32 samples
-fn build_stream_f32(
-    device: &Device,
-    config: &cpal::StreamConfig,
-    is_recording: Arc<AtomicBool>,
-    writer: Arc<Mutex<WavWriter>>,
-) -> Result<Stream> {
-    let err_fn = |err| error!("Audio stream error: {}", err);
-
-    let stream = device
-        .build_input_stream(
-            config,
-            move |data: &[f32], _: &_| {
-                if is_recording.load(Ordering::Acquire) {
-                    if let Ok(mut w) = writer.lock() {
-                        let _ = w.write_samples_f32(data);
-                    }
-                }
-            },
-            err_fn,
-            None,
-        )
-        .map_err(|e| format!("Failed to build stream: {}", e))?;
+        recorder.start_recording().unwrap();
+        assert_eq!(driver.push_frames(160), 160);
+        recorder.close_session().unwrap();
 
-    // Start the stream immediately
-    stream
-        .play()
-        .map_err(|e| format!("Failed to start stream: {}", e))?;
+        assert_eq!(driver.push_frames(160), 0);
+        assert!(recorder.start_recording().is_err());
+    }
 
-    Ok(stream)
-}
+    #[test]
+    fn cancel_deletes_the_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = RecorderState::new();
+        let driver = manual_session(&mut recorder, dir.path(), "cancelled");
 
-/// Build stream for i16 samples
-fn build_stream_i16(
-    device: &Device,
-    config: &cpal::StreamConfig,
-    is_recording: Arc<AtomicBool>,
-    writer: Arc<Mutex<WavWriter>>,
-) -> Result<Stream> {
-    let err_fn = |err| error!("Audio stream error: {}", err);
-
-    let stream = device
-        .build_input_stream(
-            config,
-            move |data: &[i16], _: &_| {
-                if is_recording.load(Ordering::Acquire) {
-                    if let Ok(mut w) = writer.lock() {
-                        let _ = w.write_samples_i16(data);
-                    }
-                }
-            },
-            err_fn,
-            None,
-        )
-        .map_err(|e| format!("Failed to build stream: {}", e))?;
+        recorder.start_recording().unwrap();
+        driver.push_frames(1600);
+        assert!(dir.path().join("cancelled.wav").exists());
 
-    // Start the stream immediately
-    stream
-        .play()
-        .map_err(|e| format!("Failed to start stream: {}", e))?;
+        recorder.cancel_recording().unwrap();
+        assert!(!dir.path().join("cancelled.wav").exists());
+        assert_eq!(recorder.get_session_recording_id(), None);
+        assert_eq!(driver.push_frames(160), 0);
+    }
 
-    Ok(stream)
-}
+    #[test]
+    fn new_session_replaces_the_open_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = RecorderState::new();
+        let first = manual_session(&mut recorder, dir.path(), "first");
+        let second = manual_session(&mut recorder, dir.path(), "second");
+
+        assert_eq!(first.push_frames(160), 0);
+        assert_eq!(second.push_frames(160), 160);
+        assert_eq!(
+            recorder.get_session_recording_id().as_deref(),
+            Some("second")
+        );
+    }
 
-/// Build stream for u16 samples
-fn build_stream_u16(
-    device: &Device,
-    config: &cpal::StreamConfig,
-    is_recording: Arc<AtomicBool>,
-    writer: Arc<Mutex<WavWriter>>,
-) -> Result<Stream> {
-    let err_fn = |err| error!("Audio stream error: {}", err);
-
-    let stream = device
-        .build_input_stream(
-            config,
-            move |data: &[u16], _: &_| {
-                if is_recording.load(Ordering::Acquire) {
-                    if let Ok(mut w) = writer.lock() {
-                        let _ = w.write_samples_u16(data);
-                    }
-                }
-            },
-            err_fn,
-            None,
-        )
-        .map_err(|e| format!("Failed to build stream: {}", e))?;
+    #[test]
+    fn rejects_unsafe_recording_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = RecorderState::new();
+        let (source, _driver) = SyntheticSource::manual(Signal::Silence, SAMPLE_RATE, 1).unwrap();
 
-    // Start the stream immediately
-    stream
-        .play()
-        .map_err(|e| format!("Failed to start stream: {}", e))?;
+        let result = recorder.init_session_with_source(
+            Box::new(source),
+            dir.path().to_path_buf(),
+            "../escape".to_string(),
+            None,
+            WriterOptions::default(),
+        );
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
 
-    Ok(stream)
-}
+    #[test]
+    fn memory_sink_keeps_session_audio() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = RecorderState::new();
+        let driver = manual_session(&mut recorder, dir.path(), "memory");
+
+        let id = recorder.add_memory_sink(None).unwrap();
+        assert_eq!(recorder.list_sinks().len(), 2);
+        recorder.start_recording().unwrap();
+        driver.push_frames(1600);
+        recorder.remove_sink(id).unwrap();
+        driver.push_frames(1600);
+
+        assert_eq!(recorder.list_sinks().len(), 1);
+        assert_eq!(recorder.memory_sink_samples(id).unwrap().len(), 1600);
+        recorder.close_session().unwrap();
+        assert!(recorder.memory_sink_samples(id).is_err());
+    }
 
-impl Drop for RecorderState {
-    fn drop(&mut self) {
-        let _ = self.close_session();
+    #[test]
+    fn encrypted_session_refuses_network_sink() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut recorder = RecorderState::new();
+        let (source, _driver) = SyntheticSource::manual(Signal::Silence, SAMPLE_RATE, 1).unwrap();
+        recorder
+            .init_session_with_source(
+                Box::new(source),
+                dir.path().to_path_buf(),
+                "secret".to_string(),
+                None,
+                WriterOptions {
+                    encryption_key: Some(RecordingKey::generate()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let sink = NetworkSink::connect(&address).unwrap();
+        assert!(recorder.add_network_sink(sink).is_err());
+        assert_eq!(recorder.list_sinks().len(), 1);
     }
 }
@@ -0,0 +1,432 @@
+use crate::recorder::storage::{parse_recording_file_name, MANIFEST_FILE};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+use tauri::{AppHandle, Manager, Runtime};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Longest recording id accepted (nanoid ids are 21 characters)
+const MAX_RECORDING_ID_LEN: usize = 64;
+
+/// Folder in the app data dir that recordings go to unless the user picks another
+pub const DEFAULT_RECORDINGS_DIR: &str = "recordings";
+
+/// File in the app config dir that remembers folders the user picked
+const USER_FOLDERS_FILE: &str = "recordings-scope.json";
+
+#[derive(Debug, Error, Serialize)]
+#[serde(tag = "name", rename_all = "PascalCase")]
+pub enum RecordingPathError {
+    #[error("Invalid recording id '{id}': only letters, digits, '-' and '_' are allowed")]
+    InvalidRecordingId { id: String },
+
+    #[error("Invalid output folder '{path}': {message}")]
+    InvalidOutputFolder { path: String, message: String },
+
+    #[error("Output folder '{path}' is outside the allowed recordings locations")]
+    OutputFolderNotAllowed { path: String },
+
+    #[error("'{path}' is not a recording")]
+    NotARecording { path: String },
+}
+
+/// Check that a recording id is safe to use as a file name
+pub fn validate_recording_id(id: &str) -> Result<(), RecordingPathError> {
+    let valid = !id.is_empty()
+        && id.len() <= MAX_RECORDING_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(RecordingPathError::InvalidRecordingId { id: id.to_string() })
+    }
+}
+
+/// Folders the recorder is allowed to write recordings into
+pub struct RecordingsScope {
+    allowed_roots: RwLock<Vec<PathBuf>>,
+    user_folders_file: RwLock<Option<PathBuf>>,
+    home_dir: RwLock<Option<PathBuf>>,
+}
+
+impl RecordingsScope {
+    pub fn new() -> Self {
+        Self {
+            allowed_roots: RwLock::new(Vec::new()),
+            user_folders_file: RwLock::new(None),
+            home_dir: RwLock::new(None),
+        }
+    }
+
+    /// Allow the default recordings folder, the standard user media folders and
+    /// any folder the user picked in a previous run
+    pub fn init<R: Runtime>(&self, app: &AppHandle<R>) {
+        let paths = app.path();
+        let defaults = [
+            paths
+                .app_data_dir()
+                .map(|dir| dir.join(DEFAULT_RECORDINGS_DIR)),
+            paths.audio_dir(),
+            paths.document_dir(),
+            paths.desktop_dir(),
+            paths.download_dir(),
+        ];
+        for root in defaults.into_iter().flatten() {
+            self.allow(root);
+        }
+        if let (Ok(home), Ok(mut home_dir)) = (paths.home_dir(), self.home_dir.write()) {
+            *home_dir = Some(home);
+        }
+
+        match paths.app_config_dir() {
+            Ok(config_dir) => self.load_user_folders(config_dir.join(USER_FOLDERS_FILE)),
+            Err(e) => warn!(
+                "Failed to resolve app config dir for recordings scope: {}",
+                e
+            ),
+        }
+    }
+
+    /// Add a root folder to the scope
+    pub fn allow(&self, root: PathBuf) {
+        if let Ok(mut roots) = self.allowed_roots.write() {
+            if !roots.contains(&root) {
+                debug!("Allowing recordings folder {:?}", root);
+                roots.push(root);
+            }
+        }
+    }
+
+    /// Add a folder the user explicitly picked and remember it across restarts
+    pub fn allow_user_folder(&self, folder: PathBuf) -> io::Result<()> {
+        self.allow(folder.clone());
+
+        let Some(file) = self.user_folders_file.read().ok().and_then(|f| f.clone()) else {
+            return Ok(());
+        };
+        let mut folders = read_user_folders(&file);
+        if !folders.contains(&folder) {
+            folders.push(folder);
+        }
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(&folders).map_err(io::Error::other)?;
+        fs::write(&file, json)
+    }
+
+    /// Allow the output folder saved in the frontend settings before folders had to be
+    /// picked through `allow_user_folder`. Only works until the user folders file is
+    /// first written, so it can't be used to widen the scope later on, and only for a
+    /// folder that already holds recordings and isn't a filesystem root or the home
+    /// dir. Returns whether the folder was allowed.
+    pub fn migrate_saved_folder(&self, folder: Option<PathBuf>) -> io::Result<bool> {
+        let Some(file) = self.user_folders_file.read().ok().and_then(|f| f.clone()) else {
+            return Ok(false);
+        };
+        if file.exists() {
+            return Ok(false);
+        }
+
+        // Written even without a folder, which closes the migration for good
+        let folders: Vec<PathBuf> = folder
+            .into_iter()
+            .filter(|folder| match self.check_saved_folder(folder) {
+                Ok(()) => true,
+                Err(reason) => {
+                    warn!("Not allowing recordings folder {:?}: {}", folder, reason);
+                    false
+                }
+            })
+            .collect();
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(&folders).map_err(io::Error::other)?;
+        fs::write(&file, json)?;
+
+        let migrated = !folders.is_empty();
+        for folder in folders {
+            info!("Allowing recordings folder saved in settings: {:?}", folder);
+            self.allow(folder);
+        }
+        Ok(migrated)
+    }
+
+    /// Normalize a folder and check it lies within one of the allowed roots.
+    /// Returns the normalized path on success.
+    pub fn resolve(&self, folder: &Path) -> Result<PathBuf, RecordingPathError> {
+        let normalized =
+            normalize_path(folder).map_err(|message| RecordingPathError::InvalidOutputFolder {
+                path: folder.display().to_string(),
+                message,
+            })?;
+
+        let roots = self
+            .allowed_roots
+            .read()
+            .map(|roots| roots.clone())
+            .unwrap_or_default();
+        let allowed = roots.iter().any(|root| {
+            normalize_path(root)
+                .map(|root| normalized.starts_with(root))
+                .unwrap_or(false)
+        });
+
+        if allowed {
+            Ok(normalized)
+        } else {
+            Err(RecordingPathError::OutputFolderNotAllowed {
+                path: folder.display().to_string(),
+            })
+        }
+    }
+
+    /// Check a folder saved by an earlier version is safe to allow without the user
+    /// picking it again
+    fn check_saved_folder(&self, folder: &Path) -> Result<(), String> {
+        let normalized = normalize_path(folder)?;
+        if normalized.parent().is_none() {
+            return Err("it is a filesystem root".to_string());
+        }
+        let home = self.home_dir.read().ok().and_then(|home| home.clone());
+        if let Some(home) = home.and_then(|home| normalize_path(&home).ok()) {
+            if home.starts_with(&normalized) {
+                return Err("it contains the home folder".to_string());
+            }
+        }
+        if !contains_recordings(&normalized) {
+            return Err("it holds no recordings".to_string());
+        }
+        Ok(())
+    }
+
+    fn load_user_folders(&self, file: PathBuf) {
+        let folders = read_user_folders(&file);
+        info!("Loaded {} user recordings folders", folders.len());
+        for folder in folders {
+            self.allow(folder);
+        }
+        if let Ok(mut f) = self.user_folders_file.write() {
+            *f = Some(file);
+        }
+    }
+}
+
+fn read_user_folders(file: &Path) -> Vec<PathBuf> {
+    fs::read(file)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+/// Whether a folder holds a recordings manifest or files named like recordings
+fn contains_recordings(folder: &Path) -> bool {
+    if folder.join(MANIFEST_FILE).is_file() {
+        return true;
+    }
+    let Ok(entries) = fs::read_dir(folder) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        entry.file_type().is_ok_and(|kind| kind.is_file())
+            && entry
+                .file_name()
+                .to_str()
+                .and_then(parse_recording_file_name)
+                .is_some_and(|(id, _)| validate_recording_id(id).is_ok())
+    })
+}
+
+/// Make a path absolute and free of `..`, resolving symlinks in the part that exists.
+/// The folder itself may not exist yet.
+fn normalize_path(path: &Path) -> Result<PathBuf, String> {
+    if !path.is_absolute() {
+        return Err("path must be absolute".to_string());
+    }
+    if path.components().any(|c| c == Component::ParentDir) {
+        return Err("path must not contain '..'".to_string());
+    }
+
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .ok_or_else(|| "path has no existing ancestor".to_string())?;
+    let canonical = existing
+        .canonicalize()
+        .map_err(|e| format!("failed to resolve path: {}", e))?;
+    let rest = path
+        .strip_prefix(existing)
+        .map_err(|e| format!("failed to resolve path: {}", e))?;
+
+    Ok(canonical.join(rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scope that remembers user folders in `config` and treats `home` as the home dir
+    fn scope(config: &Path, home: &Path) -> RecordingsScope {
+        let scope = RecordingsScope::new();
+        scope.load_user_folders(config.join(USER_FOLDERS_FILE));
+        *scope.home_dir.write().unwrap() = Some(home.to_path_buf());
+        scope
+    }
+
+    #[test]
+    fn recording_ids_must_be_safe_file_names() {
+        for id in ["abc", "V1StGXR8_Z5jdHi6B-myT", "a", &"x".repeat(64)] {
+            assert!(validate_recording_id(id).is_ok(), "{:?}", id);
+        }
+        for id in [
+            "",
+            &"x".repeat(65),
+            "..",
+            "a/b",
+            "a\\b",
+            "a.wav",
+            "id with spaces",
+            "ünïcode",
+            "nul\0",
+        ] {
+            assert!(validate_recording_id(id).is_err(), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn normalize_path_rejects_relative_and_parent_components() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(normalize_path(Path::new("recordings")).is_err());
+        assert!(normalize_path(&dir.path().join("a/../b")).is_err());
+    }
+
+    #[test]
+    fn normalize_path_keeps_missing_folders_under_the_canonical_ancestor() {
+        let dir = tempfile::tempdir().unwrap();
+        let canonical = dir.path().canonicalize().unwrap();
+
+        assert_eq!(normalize_path(dir.path()).unwrap(), canonical);
+        assert_eq!(
+            normalize_path(&dir.path().join("missing/nested")).unwrap(),
+            canonical.join("missing/nested")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn normalize_path_resolves_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::create_dir(&target).unwrap();
+        std::os::unix::fs::symlink(&target, dir.path().join("link")).unwrap();
+
+        assert_eq!(
+            normalize_path(&dir.path().join("link/new")).unwrap(),
+            target.canonicalize().unwrap().join("new")
+        );
+    }
+
+    #[test]
+    fn resolve_only_accepts_folders_inside_allowed_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("recordings");
+        fs::create_dir(&root).unwrap();
+        let scope = RecordingsScope::new();
+        scope.allow(root.clone());
+
+        let canonical = root.canonicalize().unwrap();
+        assert_eq!(scope.resolve(&root).unwrap(), canonical);
+        assert_eq!(
+            scope.resolve(&root.join("sub/dir")).unwrap(),
+            canonical.join("sub/dir")
+        );
+        assert!(matches!(
+            scope.resolve(dir.path()),
+            Err(RecordingPathError::OutputFolderNotAllowed { .. })
+        ));
+        assert!(matches!(
+            scope.resolve(&dir.path().join("recordings-other")),
+            Err(RecordingPathError::OutputFolderNotAllowed { .. })
+        ));
+        assert!(matches!(
+            scope.resolve(&root.join("../elsewhere")),
+            Err(RecordingPathError::InvalidOutputFolder { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_rejects_symlinks_out_of_allowed_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("recordings");
+        let outside = dir.path().join("outside");
+        fs::create_dir(&root).unwrap();
+        fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+        let scope = RecordingsScope::new();
+        scope.allow(root.clone());
+
+        assert!(matches!(
+            scope.resolve(&root.join("escape/sub")),
+            Err(RecordingPathError::OutputFolderNotAllowed { .. })
+        ));
+    }
+
+    #[test]
+    fn migrates_a_saved_folder_with_recordings_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let saved = dir.path().join("home/Recordings");
+        fs::create_dir_all(&saved).unwrap();
+        fs::write(saved.join("V1StGXR8_Z5jdHi6B-myT.wav"), b"").unwrap();
+        let scope = scope(&dir.path().join("config"), &dir.path().join("home"));
+
+        assert!(scope.migrate_saved_folder(Some(saved.clone())).unwrap());
+        assert!(scope.resolve(&saved).is_ok());
+
+        // The first call closes the migration
+        let other = dir.path().join("home/Other");
+        fs::create_dir_all(&other).unwrap();
+        fs::write(other.join("abc.wav"), b"").unwrap();
+        assert!(!scope.migrate_saved_folder(Some(other.clone())).unwrap());
+        assert!(scope.resolve(&other).is_err());
+    }
+
+    #[test]
+    fn does_not_migrate_roots_home_or_folders_without_recordings() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        let empty = home.join("Empty");
+        let music = home.join("Music");
+        fs::create_dir_all(&empty).unwrap();
+        fs::create_dir_all(&music).unwrap();
+        fs::write(home.join("abc.wav"), b"").unwrap();
+        fs::write(music.join("song.mp3"), b"").unwrap();
+
+        let root = dir.path().ancestors().last().unwrap().to_path_buf();
+        let relative = PathBuf::from("Recordings");
+        let folders = [
+            root,
+            dir.path().to_path_buf(),
+            home.clone(),
+            empty,
+            music,
+            relative,
+        ];
+        for (i, folder) in folders.into_iter().enumerate() {
+            let scope = scope(&dir.path().join(format!("config-{}", i)), &home);
+            assert!(
+                !scope.migrate_saved_folder(Some(folder.clone())).unwrap(),
+                "{:?}",
+                folder
+            );
+            assert!(scope.resolve(&home.join("abc")).is_err());
+        }
+    }
+}
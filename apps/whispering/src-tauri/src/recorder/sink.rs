@@ -0,0 +1,380 @@
+use crate::recorder::quality::SignalWarningHandler;
+use crate::recorder::writer::RecordingWriter;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+/// Identifies a sink within a recording session
+pub type SinkId = u32;
+
+/// A consumer of the audio captured by a recording session.
+///
+/// The session hands every buffer it records to all of its sinks in turn, from the
+/// audio thread, so `write` should return quickly and hand slow work (network, disk
+/// syncs) to its own thread.
+pub trait AudioSink: Send {
+    /// Short name used in logs
+    fn name(&self) -> &str;
+
+    /// Called once when the sink joins a session, before any samples arrive
+    fn open(&mut self, _sample_rate: u32, _channels: u16) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Consume interleaved f32 samples
+    fn write(&mut self, samples: &[f32]) -> io::Result<()>;
+
+    /// Called when the sink is removed or the session closes
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct SinkEntry {
+    id: SinkId,
+    sink: Box<dyn AudioSink>,
+    failing: bool,
+}
+
+#[derive(Default)]
+struct SinkList {
+    next_id: SinkId,
+    entries: Vec<SinkEntry>,
+}
+
+/// The sinks of a recording session, shared with the audio callback.
+///
+/// A sink that fails keeps receiving audio, so a brief hiccup (a full disk that frees
+/// up, a slow network) doesn't end it. Failures are logged once until it recovers.
+#[derive(Clone, Default)]
+pub struct SinkSet {
+    list: Arc<Mutex<SinkList>>,
+}
+
+impl SinkSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sink, it receives audio from the next buffer on
+    pub fn add(&self, sink: Box<dyn AudioSink>) -> io::Result<SinkId> {
+        let mut list = self.lock()?;
+        list.next_id += 1;
+        let id = list.next_id;
+        list.entries.push(SinkEntry {
+            id,
+            sink,
+            failing: false,
+        });
+        Ok(id)
+    }
+
+    /// Remove a sink, returning it unfinished
+    pub fn remove(&self, id: SinkId) -> io::Result<Option<Box<dyn AudioSink>>> {
+        let mut list = self.lock()?;
+        let index = list.entries.iter().position(|entry| entry.id == id);
+        Ok(index.map(|index| list.entries.remove(index).sink))
+    }
+
+    /// Ids and names of the current sinks
+    pub fn list(&self) -> Vec<(SinkId, String)> {
+        match self.lock() {
+            Ok(list) => list
+                .entries
+                .iter()
+                .map(|entry| (entry.id, entry.sink.name().to_string()))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Hand samples to every sink
+    pub fn write(&self, samples: &[f32]) {
+        let Ok(mut list) = self.list.lock() else {
+            return;
+        };
+        for entry in list.entries.iter_mut() {
+            match entry.sink.write(samples) {
+                Ok(()) if entry.failing => {
+                    entry.failing = false;
+                    debug!("Audio sink '{}' recovered", entry.sink.name());
+                }
+                Ok(()) => {}
+                Err(e) if !entry.failing => {
+                    entry.failing = true;
+                    warn!("Audio sink '{}' failed to write: {}", entry.sink.name(), e);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Remove and finish every sink
+    pub fn finish_all(&self) {
+        let entries = match self.lock() {
+            Ok(mut list) => std::mem::take(&mut list.entries),
+            Err(_) => return,
+        };
+        for mut entry in entries {
+            if let Err(e) = entry.sink.finish() {
+                warn!("Failed to finish audio sink '{}': {}", entry.sink.name(), e);
+            }
+        }
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, SinkList>> {
+        self.list
+            .lock()
+            .map_err(|_| io::Error::other("Audio sink list lock poisoned"))
+    }
+}
+
+/// Writes the session's recording file and reports signal warnings.
+/// The recorder finalizes the writer itself, so finishing this sink does nothing.
+pub struct FileSink {
+    writer: Arc<Mutex<RecordingWriter>>,
+    on_signal_warning: Option<SignalWarningHandler>,
+}
+
+impl FileSink {
+    pub fn new(
+        writer: Arc<Mutex<RecordingWriter>>,
+        on_signal_warning: Option<SignalWarningHandler>,
+    ) -> Self {
+        Self {
+            writer,
+            on_signal_warning,
+        }
+    }
+}
+
+impl AudioSink for FileSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let warning = {
+            let mut w = self
+                .writer
+                .lock()
+                .map_err(|_| io::Error::other("Recording writer lock poisoned"))?;
+            w.write_samples_f32(samples)?;
+            w.take_signal_warning()
+        };
+        if let (Some(warning), Some(handler)) = (warning, &self.on_signal_warning) {
+            handler(warning);
+        }
+        Ok(())
+    }
+}
+
+/// Shared view of the audio collected by a [`MemorySink`]
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBuffer {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+}
+
+impl MemoryBuffer {
+    /// Copy of the buffered interleaved samples
+    pub fn samples(&self) -> Vec<f32> {
+        self.samples
+            .lock()
+            .map(|samples| samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Take the buffered samples, leaving the buffer empty
+    pub fn take(&self) -> Vec<f32> {
+        self.samples
+            .lock()
+            .map(|mut samples| samples.drain(..).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Keeps recorded audio in memory, optionally only the most recent seconds
+pub struct MemorySink {
+    buffer: MemoryBuffer,
+    max_seconds: Option<f32>,
+    max_samples: usize,
+}
+
+impl MemorySink {
+    /// Buffer everything, or only the last `max_seconds` when given
+    pub fn new(max_seconds: Option<f32>) -> Self {
+        Self {
+            buffer: MemoryBuffer::default(),
+            max_seconds,
+            max_samples: usize::MAX,
+        }
+    }
+
+    /// Handle for reading the buffered audio, valid after the sink is removed
+    pub fn buffer(&self) -> MemoryBuffer {
+        self.buffer.clone()
+    }
+}
+
+impl AudioSink for MemorySink {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn open(&mut self, sample_rate: u32, channels: u16) -> io::Result<()> {
+        if let Some(seconds) = self.max_seconds {
+            self.max_samples = (seconds.max(0.0) * sample_rate as f32) as usize * channels as usize;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let mut buffer = self
+            .buffer
+            .samples
+            .lock()
+            .map_err(|_| io::Error::other("Memory buffer lock poisoned"))?;
+        buffer.extend(samples);
+        let excess = buffer.len().saturating_sub(self.max_samples);
+        buffer.drain(..excess);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records what it receives, failing while `fail_writes` is above zero
+    struct TestSink {
+        received: Arc<Mutex<Vec<f32>>>,
+        fail_writes: usize,
+        fail_finish: bool,
+        finished: Arc<Mutex<bool>>,
+    }
+
+    impl TestSink {
+        fn new(fail_writes: usize) -> Self {
+            Self {
+                received: Arc::default(),
+                fail_writes,
+                fail_finish: false,
+                finished: Arc::default(),
+            }
+        }
+    }
+
+    impl AudioSink for TestSink {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+            if self.fail_writes > 0 {
+                self.fail_writes -= 1;
+                return Err(io::Error::other("sink unavailable"));
+            }
+            self.received.lock().unwrap().extend_from_slice(samples);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            *self.finished.lock().unwrap() = true;
+            if self.fail_finish {
+                return Err(io::Error::other("finish failed"));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn every_sink_receives_every_buffer_while_it_is_added() {
+        let sinks = SinkSet::new();
+        let first = TestSink::new(0);
+        let first_received = first.received.clone();
+        let first_id = sinks.add(Box::new(first)).unwrap();
+        sinks.write(&[0.1, 0.2]);
+
+        let memory = MemorySink::new(None);
+        let buffer = memory.buffer();
+        let memory_id = sinks.add(Box::new(memory)).unwrap();
+        assert_eq!(memory_id, first_id + 1);
+        assert_eq!(
+            sinks.list(),
+            [
+                (first_id, "test".to_string()),
+                (memory_id, "memory".to_string())
+            ]
+        );
+        sinks.write(&[0.3]);
+
+        assert!(sinks.remove(first_id).unwrap().is_some());
+        assert!(sinks.remove(first_id).unwrap().is_none());
+        sinks.write(&[0.4]);
+
+        assert_eq!(*first_received.lock().unwrap(), [0.1, 0.2, 0.3]);
+        assert_eq!(buffer.samples(), [0.3, 0.4]);
+        // Ids aren't reused after a removal
+        let again = Box::new(TestSink::new(0));
+        assert_eq!(sinks.add(again).unwrap(), memory_id + 1);
+    }
+
+    #[test]
+    fn failing_sink_keeps_receiving_audio_until_it_recovers() {
+        let sinks = SinkSet::new();
+        let flaky = TestSink::new(2);
+        let steady = TestSink::new(0);
+        let (flaky_received, steady_received) = (flaky.received.clone(), steady.received.clone());
+        sinks.add(Box::new(flaky)).unwrap();
+        sinks.add(Box::new(steady)).unwrap();
+        let failing = || {
+            let list = sinks.list.lock().unwrap();
+            list.entries.iter().map(|e| e.failing).collect::<Vec<_>>()
+        };
+
+        sinks.write(&[1.0]);
+        assert_eq!(failing(), [true, false]);
+        sinks.write(&[2.0]);
+        assert_eq!(failing(), [true, false]);
+        sinks.write(&[3.0]);
+        assert_eq!(failing(), [false, false]);
+
+        assert_eq!(*flaky_received.lock().unwrap(), [3.0]);
+        assert_eq!(*steady_received.lock().unwrap(), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn finish_all_finishes_every_sink_even_when_one_fails() {
+        let sinks = SinkSet::new();
+        let mut broken = TestSink::new(0);
+        broken.fail_finish = true;
+        let fine = TestSink::new(0);
+        let (broken_finished, fine_finished) = (broken.finished.clone(), fine.finished.clone());
+        sinks.add(Box::new(broken)).unwrap();
+        sinks.add(Box::new(fine)).unwrap();
+
+        sinks.finish_all();
+        assert!(*broken_finished.lock().unwrap());
+        assert!(*fine_finished.lock().unwrap());
+        assert!(sinks.list().is_empty());
+    }
+
+    #[test]
+    fn memory_sink_keeps_only_the_most_recent_seconds() {
+        let mut sink = MemorySink::new(Some(0.5));
+        let buffer = sink.buffer();
+        sink.open(10, 2).unwrap();
+
+        let samples: Vec<f32> = (0..25).map(|i| i as f32).collect();
+        sink.write(&samples[..7]).unwrap();
+        assert_eq!(buffer.samples(), samples[..7]);
+        sink.write(&samples[7..]).unwrap();
+        assert_eq!(buffer.samples(), samples[15..]);
+
+        assert_eq!(buffer.take(), samples[15..]);
+        assert!(buffer.samples().is_empty());
+        sink.write(&[1.0, 2.0]).unwrap();
+        assert_eq!(buffer.samples(), [1.0, 2.0]);
+    }
+}
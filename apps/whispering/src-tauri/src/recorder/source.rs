@@ -0,0 +1,133 @@
+use crate::recorder::cpal_source::CpalSource;
+use crate::recorder::recorder::{BufferSizeRequest, Result};
+use crate::recorder::synthetic_source::{Signal, SyntheticSource};
+use std::path::PathBuf;
+use tracing::debug;
+
+/// Device identifier prefix for built-in generated sources, e.g. `synthetic:sine:440`
+pub const SYNTHETIC_DEVICE_PREFIX: &str = "synthetic:";
+
+/// Device identifier prefix for WAV file playback, e.g. `file:/path/to/audio.wav`
+pub const FILE_DEVICE_PREFIX: &str = "file:";
+
+/// Sample rate used by generated sources when the caller doesn't specify one
+const DEFAULT_SYNTHETIC_SAMPLE_RATE: u32 = 16000;
+
+/// Receives interleaved f32 samples from an audio source
+pub type SampleCallback = Box<dyn FnMut(&[f32]) + Send + 'static>;
+
+/// Negotiated format of the audio a source delivers
+#[derive(Debug, Clone)]
+pub struct SourceFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: String, // Native format before conversion to f32
+    pub buffer_frames: Option<u32>, // None when the host default is used
+    pub supported_buffer_range: Option<(u32, u32)>, // None when the device doesn't report it
+}
+
+/// Something the recorder can capture audio from
+pub trait AudioSource: Send {
+    /// Human readable name of the source
+    fn name(&self) -> &str;
+
+    /// Format of the samples passed to the callback
+    fn format(&self) -> &SourceFormat;
+
+    /// Start delivering samples; runs until `stop` is called or the source is dropped
+    fn start(&mut self, on_samples: SampleCallback) -> Result<()>;
+
+    /// Stop delivering samples and release the underlying device
+    fn stop(&mut self);
+}
+
+/// Open the source named by a device identifier.
+///
+/// Plain names (and "default") are input devices. `synthetic:sine[:hz]`,
+/// `synthetic:noise` and `synthetic:silence` are generated signals, and
+/// `file:<path>` plays back a WAV file as if it were a microphone. The path isn't
+/// checked here, commands must keep it inside the allowed recordings locations.
+pub fn open_source(
+    device_identifier: &str,
+    preferred_sample_rate: Option<u32>,
+    buffer_size: BufferSizeRequest,
+) -> Result<Box<dyn AudioSource>> {
+    if let Some(path) = device_identifier.strip_prefix(FILE_DEVICE_PREFIX) {
+        debug!("Opening WAV file source: {}", path);
+        let source = SyntheticSource::from_wav_file(PathBuf::from(path), buffer_size)?;
+        return Ok(Box::new(source));
+    }
+
+    if let Some(spec) = device_identifier.strip_prefix(SYNTHETIC_DEVICE_PREFIX) {
+        debug!("Opening synthetic source: {}", spec);
+        let signal = parse_signal(spec)?;
+        let sample_rate = preferred_sample_rate.unwrap_or(DEFAULT_SYNTHETIC_SAMPLE_RATE);
+        let source = SyntheticSource::new(signal, sample_rate, 1, buffer_size)?;
+        return Ok(Box::new(source));
+    }
+
+    let source = CpalSource::open(device_identifier, preferred_sample_rate, buffer_size)?;
+    Ok(Box::new(source))
+}
+
+/// Parse the part of a synthetic device identifier after the prefix
+fn parse_signal(spec: &str) -> Result<Signal> {
+    let mut parts = spec.split(':');
+    match (parts.next(), parts.next()) {
+        (Some("sine"), None) => Ok(Signal::Sine { frequency: 440.0 }),
+        (Some("sine"), Some(frequency)) => frequency
+            .parse::<f32>()
+            .ok()
+            .filter(|f| *f > 0.0)
+            .map(|frequency| Signal::Sine { frequency })
+            .ok_or_else(|| format!("Invalid sine frequency '{}'", frequency)),
+        (Some("noise"), None) => Ok(Signal::Noise),
+        (Some("silence"), None) => Ok(Signal::Silence),
+        _ => Err(format!("Unknown synthetic source '{}'", spec)),
+    }
+}
+
+/// Resolve a buffer size request into a fixed frame count (None = host default)
+pub(crate) fn resolve_buffer_size(
+    request: BufferSizeRequest,
+    supported_range: Option<(u32, u32)>,
+    sample_rate: u32,
+) -> Result<Option<u32>> {
+    match request {
+        BufferSizeRequest::Default => Ok(None),
+        BufferSizeRequest::Frames(frames) => {
+            if frames == 0 {
+                return Err("Buffer size must be greater than zero".to_string());
+            }
+            if let Some((min, max)) = supported_range {
+                if frames < min || frames > max {
+                    return Err(format!(
+                        "Buffer size of {} frames is not supported by this device (supported: {}-{})",
+                        frames, min, max
+                    ));
+                }
+            } else {
+                debug!(
+                    "Device does not report a buffer size range, trying {} frames",
+                    frames
+                );
+            }
+            Ok(Some(frames))
+        }
+        BufferSizeRequest::LatencyMs(latency_ms) => {
+            if latency_ms == 0 {
+                return Err("Latency target must be greater than zero".to_string());
+            }
+            let frames = ((sample_rate as u64 * latency_ms as u64) / 1000).max(1) as u32;
+            let frames = match supported_range {
+                Some((min, max)) => frames.clamp(min, max),
+                None => frames,
+            };
+            debug!(
+                "Latency target {} ms at {} Hz resolved to {} frames",
+                latency_ms, sample_rate, frames
+            );
+            Ok(Some(frames))
+        }
+    }
+}
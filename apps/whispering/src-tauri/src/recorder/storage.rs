@@ -0,0 +1,815 @@
+use crate::recorder::commands::AppData;
+use crate::recorder::encryption::{
+    RecordingFile, RecordingKey, RecordingKeyStore, ENCRYPTED_EXTENSION,
+};
+use crate::recorder::flac::FlacEncoder;
+use crate::recorder::opus_writer::{ENCRYPTED_OPUS_EXTENSION, OPUS_EXTENSION};
+use crate::recorder::peaks::peaks_file_path;
+use crate::recorder::scope::validate_recording_id;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{info, warn};
+
+/// Extension of losslessly compressed recordings
+pub const COMPRESSED_EXTENSION: &str = "flac";
+
+/// Extension of compressed recordings that are encrypted at rest
+pub const ENCRYPTED_COMPRESSED_EXTENSION: &str = "flac.enc";
+
+/// File in a recordings folder listing the ids of the recordings the recorder wrote there.
+/// Retention only ever touches those, never other audio files that share the folder.
+pub const MANIFEST_FILE: &str = ".whispering-recordings.json";
+
+/// Serializes manifest updates from recording sessions and cleanup runs
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// File in the app config dir holding the background retention settings
+const RETENTION_FILE: &str = "recording-retention.json";
+
+/// How often the background task applies the retention settings
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Wait before the first background run so it doesn't compete with app startup
+const RETENTION_STARTUP_DELAY: Duration = Duration::from_secs(60);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Interleaved samples handed to the FLAC encoder at a time
+const COMPRESS_CHUNK_SAMPLES: usize = 64 * 1024;
+
+/// Recordings in a folder - returned to frontend
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageStats {
+    pub recording_count: usize,
+    pub total_bytes: u64,
+    pub compressed_count: usize,
+    pub oldest_recording_ms: Option<u64>, // Unix time the oldest recording was last written
+}
+
+/// What a cleanup run removed and compressed - returned to frontend
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupReport {
+    pub deleted: Vec<String>, // Recording ids
+    pub freed_bytes: u64,
+    pub compressed: Vec<String>, // Recording ids
+    pub compression_saved_bytes: u64,
+}
+
+/// Rules for cleaning up a recordings folder. Every rule is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Delete recordings last written more than this many days ago
+    pub max_age_days: Option<u32>,
+    /// Delete the oldest recordings until the folder holds at most this many bytes
+    pub max_total_bytes: Option<u64>,
+    /// Losslessly compress WAV recordings older than this many days to FLAC
+    pub compress_after_days: Option<u32>,
+}
+
+/// Retention policy the background task applies to a recordings folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionSettings {
+    pub output_folder: PathBuf,
+    #[serde(flatten)]
+    pub policy: RetentionPolicy,
+}
+
+/// A recording file found in a recordings folder
+struct StoredRecording {
+    id: String,
+    path: PathBuf,
+    bytes: u64,
+    modified: SystemTime,
+    compressed: bool,
+}
+
+impl StoredRecording {
+    fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.modified).unwrap_or_default()
+    }
+}
+
+/// Outcome of trying to compress one recording
+enum Compression {
+    Done { path: PathBuf, bytes: u64 },
+    Skipped(&'static str),
+}
+
+/// Count and size the recordings the recorder wrote to a folder
+pub fn storage_stats(folder: &Path) -> io::Result<StorageStats> {
+    let recordings = scan_owned_recordings(folder)?;
+    Ok(StorageStats {
+        recording_count: recordings.len(),
+        total_bytes: recordings.iter().map(|r| r.bytes).sum(),
+        compressed_count: recordings.iter().filter(|r| r.compressed).count(),
+        oldest_recording_ms: recordings.first().map(|r| {
+            r.modified
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64
+        }),
+    })
+}
+
+/// Apply a retention policy to a recordings folder: delete recordings past the age limit,
+/// compress old WAVs, then delete the oldest recordings until the folder fits the size
+/// budget. Only recordings listed in the folder's manifest are touched, so other audio in
+/// the folder and recordings made before the manifest existed are left alone, as is the
+/// recording with `active_id` (the open session).
+pub fn apply_retention(
+    folder: &Path,
+    policy: &RetentionPolicy,
+    keys: &RecordingKeyStore,
+    active_id: Option<&str>,
+) -> io::Result<CleanupReport> {
+    let mut report = CleanupReport::default();
+    let now = SystemTime::now();
+    let mut recordings: Vec<StoredRecording> = scan_owned_recordings(folder)?
+        .into_iter()
+        .filter(|r| Some(r.id.as_str()) != active_id)
+        .collect();
+
+    if let Some(days) = policy.max_age_days {
+        let max_age = days_to_duration(days);
+        let reason = format!("older than {} days", days);
+        let mut kept = Vec::with_capacity(recordings.len());
+        for recording in recordings {
+            if recording.age(now) <= max_age || !remove_recording(&recording, &reason, &mut report)
+            {
+                kept.push(recording);
+            }
+        }
+        recordings = kept;
+    }
+
+    if let Some(days) = policy.compress_after_days {
+        let min_age = days_to_duration(days);
+        for recording in recordings.iter_mut() {
+            if recording.compressed || recording.age(now) < min_age {
+                continue;
+            }
+            match compress_recording(recording, keys) {
+                Ok(Compression::Done { path, bytes }) => {
+                    info!(
+                        "Compressed recording {} to FLAC: {} -> {} bytes",
+                        recording.id, recording.bytes, bytes
+                    );
+                    report.compressed.push(recording.id.clone());
+                    report.compression_saved_bytes += recording.bytes - bytes;
+                    recording.path = path;
+                    recording.bytes = bytes;
+                    recording.compressed = true;
+                }
+                Ok(Compression::Skipped(reason)) => {
+                    info!("Not compressing recording {}: {}", recording.id, reason);
+                }
+                Err(e) => warn!("Failed to compress recording {:?}: {}", recording.path, e),
+            }
+        }
+    }
+
+    if let Some(max_bytes) = policy.max_total_bytes {
+        // Recordings are sorted oldest first
+        let mut total: u64 = recordings.iter().map(|r| r.bytes).sum();
+        for recording in &recordings {
+            if total <= max_bytes {
+                break;
+            }
+            if remove_recording(recording, "over the storage budget", &mut report) {
+                total -= recording.bytes;
+            }
+        }
+    }
+
+    unregister_recordings(folder, &report.deleted)?;
+
+    info!(
+        "Recording cleanup in {:?}: deleted {} ({} bytes), compressed {} (saved {} bytes)",
+        folder,
+        report.deleted.len(),
+        report.freed_bytes,
+        report.compressed.len(),
+        report.compression_saved_bytes
+    );
+    Ok(report)
+}
+
+/// Find the recording files in a folder, oldest first. A missing folder has no recordings.
+fn scan_recordings(folder: &Path) -> io::Result<Vec<StoredRecording>> {
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut recordings = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name();
+        let Some((id, compressed)) = file_name.to_str().and_then(parse_recording_file_name) else {
+            continue;
+        };
+        recordings.push(StoredRecording {
+            id: id.to_string(),
+            path: entry.path(),
+            bytes: metadata.len(),
+            modified: metadata.modified()?,
+            compressed,
+        });
+    }
+
+    recordings.sort_by_key(|r| r.modified);
+    Ok(recordings)
+}
+
+/// Find the recordings listed in the folder's manifest, oldest first
+fn scan_owned_recordings(folder: &Path) -> io::Result<Vec<StoredRecording>> {
+    let owned: HashSet<String> = read_manifest(folder)?.into_iter().collect();
+    let mut recordings = scan_recordings(folder)?;
+    recordings.retain(|recording| owned.contains(&recording.id));
+    Ok(recordings)
+}
+
+/// Note in the folder's manifest that the recorder wrote the recording `id` there
+pub fn register_recording(folder: &Path, id: &str) -> io::Result<()> {
+    update_manifest(folder, |ids| {
+        if !ids.iter().any(|owned| owned == id) {
+            ids.push(id.to_string());
+        }
+    })
+}
+
+/// Drop recordings that were deleted from the folder's manifest
+pub fn unregister_recordings(folder: &Path, removed: &[String]) -> io::Result<()> {
+    if removed.is_empty() {
+        return Ok(());
+    }
+    update_manifest(folder, |ids| ids.retain(|id| !removed.contains(id)))
+}
+
+/// Ids listed in a folder's manifest. A folder without one has no recordings to manage,
+/// and an unreadable one manages none rather than guessing.
+fn read_manifest(folder: &Path) -> io::Result<Vec<String>> {
+    match fs::read(folder.join(MANIFEST_FILE)) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!(
+                "Ignoring unreadable recordings manifest in {:?}: {}",
+                folder, e
+            );
+            Vec::new()
+        })),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn update_manifest(folder: &Path, update: impl FnOnce(&mut Vec<String>)) -> io::Result<()> {
+    let _guard = MANIFEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut ids = read_manifest(folder)?;
+    update(&mut ids);
+
+    // Swap in the new list whole, so a crash can't leave half a manifest behind
+    let json = serde_json::to_vec(&ids).map_err(io::Error::other)?;
+    let temp = folder.join(format!("{}.tmp", MANIFEST_FILE));
+    fs::write(&temp, json)?;
+    fs::rename(&temp, folder.join(MANIFEST_FILE))
+}
+
+/// Split a recording's file name (`<id>.<extension>`) into its id and whether it is
+/// compressed. Returns None for files the recorder doesn't write.
+pub fn parse_recording_file_name(file_name: &str) -> Option<(&str, bool)> {
+    let (id, extension) = file_name.split_once('.')?;
+    let compressed = match extension {
+        "wav" | ENCRYPTED_EXTENSION => false,
+        COMPRESSED_EXTENSION
+        | ENCRYPTED_COMPRESSED_EXTENSION
+        | OPUS_EXTENSION
+        | ENCRYPTED_OPUS_EXTENSION => true,
+        _ => return None,
+    };
+    validate_recording_id(id).ok()?;
+    Some((id, compressed))
+}
+
+/// Find the file of a recording by id, the most recently written one if there are several
+pub fn find_recording(folder: &Path, id: &str) -> io::Result<Option<PathBuf>> {
+    Ok(scan_recordings(folder)?
+        .into_iter()
+        .rfind(|recording| recording.id == id)
+        .map(|recording| recording.path))
+}
+
+/// Delete a recording and its peaks file. Returns whether the recording is gone.
+fn remove_recording(recording: &StoredRecording, reason: &str, report: &mut CleanupReport) -> bool {
+    if let Err(e) = fs::remove_file(&recording.path) {
+        warn!("Failed to remove recording {:?}: {}", recording.path, e);
+        return false;
+    }
+    fs::remove_file(peaks_file_path(&recording.path)).ok(); // Not every recording has one
+
+    info!(
+        "Removed recording {} ({} bytes): {}",
+        recording.id, recording.bytes, reason
+    );
+    report.deleted.push(recording.id.clone());
+    report.freed_bytes += recording.bytes;
+    true
+}
+
+/// Losslessly compress a WAV recording to FLAC, keeping its timestamp and encryption.
+///
+/// Float recordings can only go to FLAC if every sample is an exact 16 or 24-bit
+/// integer scaled by 2^(bits - 1). That holds for recordings from integer devices,
+/// which are converted to float at that scale and written unprocessed. Audio from
+/// float devices, or audio that went through gain or resampling, almost never fits
+/// and stays WAV, as do recordings with markers (FLAC has no place for the cue chunk).
+fn compress_recording(
+    recording: &StoredRecording,
+    keys: &RecordingKeyStore,
+) -> io::Result<Compression> {
+    let mut source = keys.open_recording(&recording.path)?;
+    let encrypted = source.is_encrypted();
+    if has_chunk(&mut source, b"cue ")? {
+        return Ok(Compression::Skipped("it has markers"));
+    }
+    source.seek(SeekFrom::Start(0))?;
+
+    let mut reader = hound::WavReader::new(BufReader::new(source)).map_err(io::Error::other)?;
+    let spec = reader.spec();
+    let bits_per_sample = match spec.sample_format {
+        hound::SampleFormat::Int if spec.bits_per_sample <= 24 => spec.bits_per_sample,
+        hound::SampleFormat::Int => return Ok(Compression::Skipped("its samples are 32-bit")),
+        hound::SampleFormat::Float => match exact_integer_bits(&mut reader)? {
+            Some(bits) => {
+                reader.seek(0)?;
+                bits
+            }
+            None => return Ok(Compression::Skipped("its samples are not exact 16 or 24-bit integers")),
+        },
+    };
+
+    let (extension, key) = if encrypted {
+        (ENCRYPTED_COMPRESSED_EXTENSION, Some(keys.key()?))
+    } else {
+        (COMPRESSED_EXTENSION, None)
+    };
+    let target = recording
+        .path
+        .with_file_name(format!("{}.{}", recording.id, extension));
+    let temp = recording
+        .path
+        .with_file_name(format!("{}.{}.tmp", recording.id, extension));
+
+    // Encode next to the original and only swap once the FLAC is complete
+    let bytes = match encode_flac(
+        &mut reader,
+        bits_per_sample,
+        &temp,
+        key.as_ref(),
+        recording.modified,
+    ) {
+        Ok(bytes) if bytes < recording.bytes => bytes,
+        Ok(_) => {
+            fs::remove_file(&temp).ok();
+            return Ok(Compression::Skipped("FLAC would not be smaller"));
+        }
+        Err(e) => {
+            fs::remove_file(&temp).ok();
+            return Err(e);
+        }
+    };
+    fs::rename(&temp, &target)?;
+    fs::remove_file(&recording.path)?;
+
+    Ok(Compression::Done {
+        path: target,
+        bytes,
+    })
+}
+
+/// Encode every sample of a WAV into a new FLAC file and return its size
+fn encode_flac<R: Read>(
+    reader: &mut hound::WavReader<R>,
+    bits_per_sample: u16,
+    path: &Path,
+    key: Option<&RecordingKey>,
+    modified: SystemTime,
+) -> io::Result<u64> {
+    let spec = reader.spec();
+    let expected_frames = reader.duration() as u64;
+    let file = BufWriter::new(RecordingFile::create(path, key)?);
+    let mut encoder = FlacEncoder::new(file, spec.sample_rate, spec.channels, bits_per_sample)?;
+
+    let mut chunk = Vec::with_capacity(COMPRESS_CHUNK_SAMPLES);
+    match spec.sample_format {
+        hound::SampleFormat::Float => {
+            for sample in reader.samples::<f32>() {
+                let sample = sample.map_err(io::Error::other)?;
+                let value = float_to_int(sample, bits_per_sample)
+                    .ok_or_else(|| io::Error::other("Sample is not exact at the target depth"))?;
+                chunk.push(value);
+                if chunk.len() == COMPRESS_CHUNK_SAMPLES {
+                    encoder.write_samples(&chunk)?;
+                    chunk.clear();
+                }
+            }
+        }
+        hound::SampleFormat::Int => {
+            for sample in reader.samples::<i32>() {
+                chunk.push(sample.map_err(io::Error::other)?);
+                if chunk.len() == COMPRESS_CHUNK_SAMPLES {
+                    encoder.write_samples(&chunk)?;
+                    chunk.clear();
+                }
+            }
+        }
+    }
+    encoder.write_samples(&chunk)?;
+
+    if encoder.frames_written() != expected_frames {
+        return Err(io::Error::other(format!(
+            "Encoded {} frames but the WAV has {}",
+            encoder.frames_written(),
+            expected_frames
+        )));
+    }
+
+    let mut file = encoder.finish()?.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    drop(file);
+
+    // Keep the original timestamp so age rules still see when the recording was made
+    let file = File::options().write(true).open(path)?;
+    file.set_modified(modified)?;
+    Ok(file.metadata()?.len())
+}
+
+/// Smallest integer depth (16 or 24 bits) that holds every float sample exactly, if any
+fn exact_integer_bits<R: Read>(reader: &mut hound::WavReader<R>) -> io::Result<Option<u16>> {
+    let mut bits = 16;
+    for sample in reader.samples::<f32>() {
+        let sample = sample.map_err(io::Error::other)?;
+        if bits == 16 && float_to_int(sample, 16).is_none() {
+            bits = 24;
+        }
+        if bits == 24 && float_to_int(sample, 24).is_none() {
+            return Ok(None);
+        }
+    }
+    Ok(Some(bits))
+}
+
+/// Convert a float sample to an integer of the given depth if that loses nothing
+fn float_to_int(sample: f32, bits: u16) -> Option<i32> {
+    let full_scale = (1i64 << (bits - 1)) as f64;
+    let scaled = sample as f64 * full_scale;
+    let exact = scaled.fract() == 0.0 && scaled >= -full_scale && scaled < full_scale;
+    exact.then_some(scaled as i32)
+}
+
+/// Check whether a RIFF/WAVE stream contains a chunk with the given id
+fn has_chunk<R: Read + Seek>(reader: &mut R, id: &[u8; 4]) -> io::Result<bool> {
+    let mut header = [0u8; 12];
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a WAV file"));
+    }
+
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut pos = 12u64;
+    while pos + 8 <= end {
+        let mut chunk = [0u8; 8];
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut chunk)?;
+        if &chunk[0..4] == id {
+            return Ok(true);
+        }
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+        pos += 8 + size + size % 2; // Chunks are word aligned
+    }
+    Ok(false)
+}
+
+fn days_to_duration(days: u32) -> Duration {
+    Duration::from_secs(days as u64 * SECONDS_PER_DAY)
+}
+
+/// Retention settings for the background task, persisted in the app config dir
+pub struct RecordingRetention {
+    settings_file: RwLock<Option<PathBuf>>,
+    settings: RwLock<Option<RetentionSettings>>,
+}
+
+impl RecordingRetention {
+    pub fn new() -> Self {
+        Self {
+            settings_file: RwLock::new(None),
+            settings: RwLock::new(None),
+        }
+    }
+
+    /// Load the settings saved in a previous run
+    pub fn init<R: Runtime>(&self, app: &AppHandle<R>) {
+        let file = match app.path().app_config_dir() {
+            Ok(dir) => dir.join(RETENTION_FILE),
+            Err(e) => {
+                warn!(
+                    "Failed to resolve app config dir for recording retention: {}",
+                    e
+                );
+                return;
+            }
+        };
+
+        let saved: Option<RetentionSettings> = fs::read(&file)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        if let Some(settings) = &saved {
+            info!("Loaded recording retention settings: {:?}", settings);
+        }
+        if let Ok(mut s) = self.settings.write() {
+            *s = saved;
+        }
+        if let Ok(mut f) = self.settings_file.write() {
+            *f = Some(file);
+        }
+    }
+
+    /// Current settings, or None if background cleanup is off
+    pub fn get(&self) -> Option<RetentionSettings> {
+        self.settings.read().ok().and_then(|s| s.clone())
+    }
+
+    /// Replace the settings and remember them across restarts. None turns background cleanup off.
+    pub fn set(&self, settings: Option<RetentionSettings>) -> io::Result<()> {
+        if let Some(file) = self.settings_file.read().ok().and_then(|f| f.clone()) {
+            match &settings {
+                Some(settings) => {
+                    if let Some(parent) = file.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let json = serde_json::to_vec_pretty(settings).map_err(io::Error::other)?;
+                    fs::write(&file, json)?;
+                }
+                None => match fs::remove_file(&file) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                },
+            }
+        }
+
+        info!("Recording retention settings: {:?}", settings);
+        if let Ok(mut s) = self.settings.write() {
+            *s = settings;
+        }
+        Ok(())
+    }
+}
+
+/// Apply the saved retention settings now and then for as long as the app runs
+pub fn spawn_retention_task<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(RETENTION_STARTUP_DELAY).await;
+        loop {
+            let handle = app.clone();
+            // Compression reads whole recordings, keep it off the async runtime
+            match tokio::task::spawn_blocking(move || run_saved_retention(&handle)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("Background recording cleanup failed: {}", e),
+                Err(e) => warn!("Background recording cleanup task failed: {}", e),
+            }
+            tokio::time::sleep(RETENTION_INTERVAL).await;
+        }
+    });
+}
+
+fn run_saved_retention<R: Runtime>(app: &AppHandle<R>) -> Result<(), String> {
+    let state = app.state::<AppData>();
+    let Some(settings) = state.retention.get() else {
+        return Ok(());
+    };
+
+    let folder = state
+        .recordings_scope
+        .resolve(&settings.output_folder)
+        .map_err(|e| e.to_string())?;
+    let active_id = state.session_recording_id();
+    apply_retention(
+        &folder,
+        &settings.policy,
+        &state.recording_keys,
+        active_id.as_deref(),
+    )
+    .map(|_| ())
+    .map_err(|e| format!("Failed to clean up {:?}: {}", folder, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::FileTimes;
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::DecoderOptions;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// 16-bit mono tone, quiet enough that FLAC always beats the WAV
+    fn samples(frames: usize) -> Vec<i16> {
+        (0..frames)
+            .map(|i| ((i as f64 * 0.05).sin() * 1000.0) as i16)
+            .collect()
+    }
+
+    /// Write a WAV recording last modified `age` ago, optionally listing it in the manifest
+    fn write_recording(
+        folder: &Path,
+        id: &str,
+        frames: usize,
+        age: Duration,
+        owned: bool,
+    ) -> PathBuf {
+        let path = folder.join(format!("{}.wav", id));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in samples(frames) {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let modified = SystemTime::now() - age;
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(modified))
+            .unwrap();
+        if owned {
+            register_recording(folder, id).unwrap();
+        }
+        path
+    }
+
+    fn decode_flac(path: &Path) -> Vec<i16> {
+        let file = File::open(path).unwrap();
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut format = symphonia::default::get_probe()
+            .format(
+                Hint::new().with_extension("flac"),
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .unwrap()
+            .format;
+        let track = format.default_track().unwrap();
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .unwrap();
+
+        let mut decoded = Vec::new();
+        while let Ok(packet) = format.next_packet() {
+            let audio = decoder.decode(&packet).unwrap();
+            let mut buffer = SampleBuffer::<i16>::new(audio.capacity() as u64, *audio.spec());
+            buffer.copy_interleaved_ref(audio);
+            decoded.extend_from_slice(buffer.samples());
+        }
+        decoded
+    }
+
+    fn sorted(mut ids: Vec<String>) -> Vec<String> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn deletes_owned_recordings_past_the_age_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        write_recording(dir.path(), "old", 1000, DAY * 10, true);
+        write_recording(dir.path(), "new", 1000, DAY, true);
+        let policy = RetentionPolicy {
+            max_age_days: Some(7),
+            ..Default::default()
+        };
+
+        let report = apply_retention(dir.path(), &policy, &RecordingKeyStore::new(), None).unwrap();
+
+        assert_eq!(report.deleted, ["old"]);
+        assert!(!dir.path().join("old.wav").exists());
+        assert!(dir.path().join("new.wav").exists());
+        assert_eq!(read_manifest(dir.path()).unwrap(), ["new"]);
+    }
+
+    #[test]
+    fn leaves_files_the_recorder_did_not_write() {
+        let dir = tempfile::tempdir().unwrap();
+        write_recording(dir.path(), "song", 1000, DAY * 100, false);
+        write_recording(dir.path(), "mine", 1000, DAY * 100, true);
+        let policy = RetentionPolicy {
+            max_age_days: Some(1),
+            max_total_bytes: Some(0),
+            compress_after_days: Some(0),
+        };
+
+        let report = apply_retention(dir.path(), &policy, &RecordingKeyStore::new(), None).unwrap();
+
+        assert_eq!(report.deleted, ["mine"]);
+        assert!(dir.path().join("song.wav").exists());
+        assert_eq!(storage_stats(dir.path()).unwrap().recording_count, 0);
+    }
+
+    #[test]
+    fn deletes_oldest_recordings_until_within_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = fs::metadata(write_recording(dir.path(), "a", 1000, DAY * 3, true))
+            .unwrap()
+            .len();
+        write_recording(dir.path(), "b", 1000, DAY * 2, true);
+        write_recording(dir.path(), "c", 1000, DAY, true);
+        let policy = RetentionPolicy {
+            max_total_bytes: Some(size * 2 - 1),
+            ..Default::default()
+        };
+
+        let report = apply_retention(dir.path(), &policy, &RecordingKeyStore::new(), None).unwrap();
+
+        assert_eq!(report.deleted, ["a", "b"]);
+        assert_eq!(report.freed_bytes, size * 2);
+        assert_eq!(read_manifest(dir.path()).unwrap(), ["c"]);
+    }
+
+    #[test]
+    fn keeps_the_active_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        write_recording(dir.path(), "active", 1000, DAY * 10, true);
+        write_recording(dir.path(), "done", 1000, DAY * 10, true);
+        let policy = RetentionPolicy {
+            max_age_days: Some(1),
+            max_total_bytes: Some(0),
+            compress_after_days: Some(0),
+        };
+
+        let report = apply_retention(
+            dir.path(),
+            &policy,
+            &RecordingKeyStore::new(),
+            Some("active"),
+        )
+        .unwrap();
+
+        assert_eq!(report.deleted, ["done"]);
+        assert!(report.compressed.is_empty());
+        assert!(dir.path().join("active.wav").exists());
+        assert_eq!(read_manifest(dir.path()).unwrap(), ["active"]);
+    }
+
+    #[test]
+    fn compresses_old_recordings_to_flac_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = write_recording(dir.path(), "old", 20_000, DAY * 5, true);
+        write_recording(dir.path(), "new", 20_000, DAY, true);
+        let modified = fs::metadata(&wav).unwrap().modified().unwrap();
+        let policy = RetentionPolicy {
+            compress_after_days: Some(3),
+            ..Default::default()
+        };
+
+        let report = apply_retention(dir.path(), &policy, &RecordingKeyStore::new(), None).unwrap();
+
+        assert_eq!(report.compressed, ["old"]);
+        assert!(report.compression_saved_bytes > 0);
+        assert!(!wav.exists());
+        assert!(!dir.path().join("old.flac.tmp").exists());
+        assert!(dir.path().join("new.wav").exists());
+
+        let flac = dir.path().join("old.flac");
+        assert_eq!(fs::metadata(&flac).unwrap().modified().unwrap(), modified);
+        assert_eq!(decode_flac(&flac), samples(20_000));
+        assert_eq!(sorted(read_manifest(dir.path()).unwrap()), ["new", "old"]);
+        assert_eq!(find_recording(dir.path(), "old").unwrap(), Some(flac));
+    }
+}
@@ -0,0 +1,543 @@
+use crate::recorder::recorder::{BufferSizeRequest, Result};
+use crate::recorder::source::{resolve_buffer_size, AudioSource, SampleCallback, SourceFormat};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Block length used when no buffer size is requested
+const DEFAULT_BLOCK_MS: u64 = 10;
+
+/// Amplitude of generated sine and noise signals
+const SINE_AMPLITUDE: f32 = 0.5;
+const NOISE_AMPLITUDE: f32 = 0.1;
+
+/// Seed for the noise generator so runs are reproducible
+const NOISE_SEED: u32 = 0x9E37_79B9;
+
+/// Signal produced by a synthetic source
+#[derive(Debug, Clone, PartialEq)]
+pub enum Signal {
+    Sine {
+        frequency: f32,
+    },
+    Noise,
+    Silence,
+    /// Interleaved samples played back once, then the source goes quiet
+    Samples(Arc<Vec<f32>>),
+}
+
+/// How a synthetic source delivers its blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pacing {
+    /// A background thread delivers blocks at the rate a real device would
+    Realtime,
+    /// Blocks are only delivered through a [`ManualDriver`], on the caller's thread
+    Manual,
+}
+
+/// What a generator plays
+enum Input {
+    Signal(Signal),
+    File(WavInput),
+}
+
+/// A WAV file read block by block as it is played, so long files aren't held in memory
+struct WavInput {
+    reader: hound::WavReader<BufReader<File>>,
+    scale: Option<f32>, // Integer samples are divided by it, None for float files
+    finished: bool,
+}
+
+impl WavInput {
+    fn open(path: &PathBuf) -> Result<Self> {
+        let reader = hound::WavReader::open(path)
+            .map_err(|e| format!("Failed to open WAV file {:?}: {}", path, e))?;
+        let spec = reader.spec();
+        let scale = match spec.sample_format {
+            hound::SampleFormat::Float => None,
+            hound::SampleFormat::Int => Some((1i64 << (spec.bits_per_sample - 1)) as f32),
+        };
+        Ok(Self {
+            reader,
+            scale,
+            finished: false,
+        })
+    }
+
+    /// Append up to `samples` samples to `block`. Reading stops for good at the end of
+    /// the file or at a damaged sample.
+    fn read_into(&mut self, block: &mut Vec<f32>, samples: usize) {
+        if self.finished {
+            return;
+        }
+        let before = block.len();
+        let result = match self.scale {
+            None => self
+                .reader
+                .samples::<f32>()
+                .take(samples)
+                .try_for_each(|s| s.map(|s| block.push(s))),
+            Some(scale) => self
+                .reader
+                .samples::<i32>()
+                .take(samples)
+                .try_for_each(|s| s.map(|s| block.push(s as f32 / scale))),
+        };
+        if let Err(e) = result {
+            warn!("Failed to read WAV file source, stopping playback: {}", e);
+            self.finished = true;
+        }
+        if block.len() - before < samples {
+            self.finished = true;
+        }
+    }
+}
+
+/// Generator state shared between the source and its driver
+struct Generator {
+    input: Input,
+    sample_rate: u32,
+    channels: u16,
+    frame: u64,
+    noise_state: u32,
+    on_samples: Option<SampleCallback>,
+    block: Vec<f32>,
+}
+
+impl Generator {
+    /// Generate and deliver up to `frames` frames; returns how many were delivered
+    fn deliver(&mut self, frames: usize) -> usize {
+        let Some(on_samples) = self.on_samples.as_mut() else {
+            return 0;
+        };
+
+        let channels = self.channels as usize;
+        if let Input::File(file) = &mut self.input {
+            self.block.clear();
+            file.read_into(&mut self.block, frames * channels);
+            // A file cut mid-frame ends with the last whole frame
+            let frames = self.block.len() / channels;
+            self.block.truncate(frames * channels);
+            if frames > 0 {
+                self.frame += frames as u64;
+                on_samples(&self.block);
+            }
+            return frames;
+        }
+        let Input::Signal(signal) = &self.input else {
+            return 0;
+        };
+
+        let frames = match signal {
+            Signal::Samples(samples) => {
+                let remaining = (samples.len() / channels).saturating_sub(self.frame as usize);
+                frames.min(remaining)
+            }
+            _ => frames,
+        };
+        if frames == 0 {
+            return 0;
+        }
+
+        self.block.clear();
+        for i in 0..frames {
+            let frame = self.frame + i as u64;
+            match signal {
+                Signal::Sine { frequency } => {
+                    let t = frame as f64 / self.sample_rate as f64;
+                    let value = (2.0 * std::f64::consts::PI * *frequency as f64 * t).sin() as f32
+                        * SINE_AMPLITUDE;
+                    self.block.extend(std::iter::repeat_n(value, channels));
+                }
+                Signal::Noise => {
+                    for _ in 0..channels {
+                        // xorshift32, mapped to -1.0..1.0
+                        let mut x = self.noise_state;
+                        x ^= x << 13;
+                        x ^= x >> 17;
+                        x ^= x << 5;
+                        self.noise_state = x;
+                        let value = (x as f32 / u32::MAX as f32) * 2.0 - 1.0;
+                        self.block.push(value * NOISE_AMPLITUDE);
+                    }
+                }
+                Signal::Silence => {
+                    self.block.extend(std::iter::repeat_n(0.0, channels));
+                }
+                Signal::Samples(samples) => {
+                    let start = frame as usize * channels;
+                    self.block
+                        .extend_from_slice(&samples[start..start + channels]);
+                }
+            }
+        }
+
+        self.frame += frames as u64;
+        on_samples(&self.block);
+        frames
+    }
+}
+
+/// Generated or file-backed audio source that needs no sound card
+pub struct SyntheticSource {
+    name: String,
+    format: SourceFormat,
+    pacing: Pacing,
+    generator: Arc<Mutex<Generator>>,
+    thread: Option<JoinHandle<()>>,
+    should_stop: Arc<AtomicBool>,
+}
+
+impl SyntheticSource {
+    /// Create a source that delivers a generated signal in real time
+    pub fn new(
+        signal: Signal,
+        sample_rate: u32,
+        channels: u16,
+        buffer_size: BufferSizeRequest,
+    ) -> Result<Self> {
+        Self::with_pacing(
+            Input::Signal(signal),
+            sample_rate,
+            channels,
+            buffer_size,
+            Pacing::Realtime,
+        )
+    }
+
+    /// Create a source that only delivers audio when its driver asks for it.
+    /// Useful for exercising the recorder deterministically without a device.
+    pub fn manual(signal: Signal, sample_rate: u32, channels: u16) -> Result<(Self, ManualDriver)> {
+        Self::with_pacing(
+            Input::Signal(signal),
+            sample_rate,
+            channels,
+            BufferSizeRequest::Default,
+            Pacing::Manual,
+        )
+        .map(Self::with_driver)
+    }
+
+    fn with_driver(self) -> (Self, ManualDriver) {
+        let driver = ManualDriver {
+            generator: self.generator.clone(),
+        };
+        (self, driver)
+    }
+
+    /// Create a source that plays back a WAV file in real time, as if it were a microphone
+    pub fn from_wav_file(path: PathBuf, buffer_size: BufferSizeRequest) -> Result<Self> {
+        Self::wav_file_with_pacing(path, buffer_size, Pacing::Realtime)
+    }
+
+    fn wav_file_with_pacing(
+        path: PathBuf,
+        buffer_size: BufferSizeRequest,
+        pacing: Pacing,
+    ) -> Result<Self> {
+        let file = WavInput::open(&path)?;
+        let spec = file.reader.spec();
+        info!(
+            "Opened WAV file source {:?}: {} Hz, {} channels, {} frames",
+            path,
+            spec.sample_rate,
+            spec.channels,
+            file.reader.duration()
+        );
+
+        let mut source = Self::with_pacing(
+            Input::File(file),
+            spec.sample_rate,
+            spec.channels,
+            buffer_size,
+            pacing,
+        )?;
+        source.name = format!("File: {}", path.display());
+        Ok(source)
+    }
+
+    fn with_pacing(
+        input: Input,
+        sample_rate: u32,
+        channels: u16,
+        buffer_size: BufferSizeRequest,
+        pacing: Pacing,
+    ) -> Result<Self> {
+        if sample_rate == 0 || channels == 0 {
+            return Err(
+                "Synthetic source needs a non-zero sample rate and channel count".to_string(),
+            );
+        }
+
+        let buffer_frames = resolve_buffer_size(buffer_size, None, sample_rate)?
+            .unwrap_or((sample_rate as u64 * DEFAULT_BLOCK_MS / 1000).max(1) as u32);
+
+        let name = match &input {
+            Input::Signal(Signal::Sine { frequency }) => format!("Sine {} Hz", frequency),
+            Input::Signal(Signal::Noise) => "White noise".to_string(),
+            Input::Signal(Signal::Silence) => "Silence".to_string(),
+            Input::Signal(Signal::Samples(_)) => "Sample buffer".to_string(),
+            Input::File(_) => "WAV file".to_string(),
+        };
+
+        Ok(Self {
+            name,
+            format: SourceFormat {
+                sample_rate,
+                channels,
+                sample_format: "f32".to_string(),
+                buffer_frames: Some(buffer_frames),
+                supported_buffer_range: None,
+            },
+            pacing,
+            generator: Arc::new(Mutex::new(Generator {
+                input,
+                sample_rate,
+                channels,
+                frame: 0,
+                noise_state: NOISE_SEED,
+                on_samples: None,
+                block: Vec::new(),
+            })),
+            thread: None,
+            should_stop: Arc::new(AtomicBool::new(false)),
+        })
+    }
+}
+
+impl AudioSource for SyntheticSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn format(&self) -> &SourceFormat {
+        &self.format
+    }
+
+    fn start(&mut self, on_samples: SampleCallback) -> Result<()> {
+        self.stop();
+
+        self.generator
+            .lock()
+            .map_err(|e| format!("Failed to lock generator: {}", e))?
+            .on_samples = Some(on_samples);
+
+        if self.pacing == Pacing::Manual {
+            debug!("Started manual source '{}'", self.name);
+            return Ok(());
+        }
+
+        let should_stop = Arc::new(AtomicBool::new(false));
+        self.should_stop = should_stop.clone();
+        let generator = self.generator.clone();
+        let block_frames = self.format.buffer_frames.unwrap_or(1) as usize;
+        let sample_rate = self.format.sample_rate as f64;
+
+        // Deliver each block once a real device would have captured it, scheduled from
+        // the frame count so timing doesn't drift
+        self.thread = Some(thread::spawn(move || {
+            let started = Instant::now();
+            let mut scheduled_frames: u64 = 0;
+            while !should_stop.load(Ordering::Acquire) {
+                scheduled_frames += block_frames as u64;
+                let due = Duration::from_secs_f64(scheduled_frames as f64 / sample_rate);
+                if let Some(wait) = due.checked_sub(started.elapsed()) {
+                    thread::sleep(wait);
+                }
+                if should_stop.load(Ordering::Acquire) {
+                    break;
+                }
+                if let Ok(mut g) = generator.lock() {
+                    g.deliver(block_frames);
+                }
+            }
+        }));
+
+        debug!("Started realtime source '{}'", self.name);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.should_stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if let Ok(mut g) = self.generator.lock() {
+            g.on_samples = None;
+        }
+    }
+}
+
+impl Drop for SyntheticSource {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Delivers audio from a manually paced [`SyntheticSource`]
+#[derive(Clone)]
+pub struct ManualDriver {
+    generator: Arc<Mutex<Generator>>,
+}
+
+impl ManualDriver {
+    /// Deliver `frames` frames to the source's callback on this thread.
+    /// Returns the number of frames delivered (0 if the source isn't started).
+    pub fn push_frames(&self, frames: usize) -> usize {
+        match self.generator.lock() {
+            Ok(mut g) => g.deliver(frames),
+            Err(_) => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Open a manually paced WAV file source collecting what it delivers
+    fn play(path: &Path) -> (SyntheticSource, ManualDriver, Arc<Mutex<Vec<f32>>>) {
+        let (mut source, driver) = SyntheticSource::wav_file_with_pacing(
+            path.to_path_buf(),
+            BufferSizeRequest::Default,
+            Pacing::Manual,
+        )
+        .map(SyntheticSource::with_driver)
+        .unwrap();
+        let played = Arc::new(Mutex::new(Vec::new()));
+        let sink = played.clone();
+        source
+            .start(Box::new(move |samples: &[f32]| {
+                sink.lock().unwrap().extend_from_slice(samples)
+            }))
+            .unwrap();
+        (source, driver, played)
+    }
+
+    fn write_wav(path: &Path, spec: hound::WavSpec, samples: &[i32]) {
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn int_spec(channels: u16, bits_per_sample: u16) -> hound::WavSpec {
+        hound::WavSpec {
+            channels,
+            sample_rate: 16000,
+            bits_per_sample,
+            sample_format: hound::SampleFormat::Int,
+        }
+    }
+
+    #[test]
+    fn plays_a_wav_file_block_by_block_then_goes_quiet() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input.wav");
+        let samples: Vec<i32> = (0..50).map(|i| (i - 25) * 1000).collect();
+        write_wav(&path, int_spec(2, 16), &samples);
+
+        let (source, driver, played) = play(&path);
+        assert_eq!(source.name(), format!("File: {}", path.display()));
+        assert_eq!(source.format().channels, 2);
+        assert_eq!(
+            [10, 10, 10, 10].map(|frames| driver.push_frames(frames)),
+            [10, 10, 5, 0]
+        );
+
+        let expected: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+        assert_eq!(*played.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn scales_every_sample_format_to_unit_range() {
+        let dir = tempfile::tempdir().unwrap();
+        for bits in [8, 16, 24, 32] {
+            let path = dir.path().join(format!("int{}.wav", bits));
+            let full_scale = 1i64 << (bits - 1);
+            let samples = [
+                -full_scale,
+                -full_scale / 2,
+                0,
+                full_scale / 2,
+                full_scale - 1,
+            ];
+            let samples: Vec<i32> = samples.iter().map(|&s| s as i32).collect();
+            write_wav(&path, int_spec(1, bits), &samples);
+
+            let (_source, driver, played) = play(&path);
+            assert_eq!(driver.push_frames(100), 5);
+            let played = played.lock().unwrap();
+            assert_eq!(played[..4], [-1.0, -0.5, 0.0, 0.5], "{} bits", bits);
+            // Full scale minus one rounds to 1.0 in f32 at 32 bits
+            assert!(played[4] <= 1.0 && played[4] > 0.99, "{} bits", bits);
+        }
+
+        let path = dir.path().join("float.wav");
+        let spec = hound::WavSpec {
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+            ..int_spec(1, 32)
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in [0.25f32, -0.75, 1.5] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        let (_source, driver, played) = play(&path);
+        assert_eq!(driver.push_frames(100), 3);
+        assert_eq!(*played.lock().unwrap(), [0.25, -0.75, 1.5]);
+    }
+
+    #[test]
+    fn cut_off_file_ends_with_its_last_whole_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cut.wav");
+        write_wav(&path, int_spec(2, 16), &(0..8).collect::<Vec<_>>());
+        // Drop the last sample and a half, the header still promises all of them
+        let len = std::fs::metadata(&path).unwrap().len();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        let (_source, driver, played) = play(&path);
+        assert_eq!(driver.push_frames(2), 2);
+        assert_eq!(driver.push_frames(2), 1);
+        assert_eq!(driver.push_frames(2), 0);
+        assert_eq!(played.lock().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn default_block_is_ten_milliseconds_at_any_rate() {
+        for (sample_rate, frames) in [(48000, 480), (8000, 80), (50, 1), (u32::MAX, 42_949_672)] {
+            let (source, _driver) =
+                SyntheticSource::manual(Signal::Silence, sample_rate, 1).unwrap();
+            assert_eq!(
+                source.format().buffer_frames,
+                Some(frames),
+                "{} Hz",
+                sample_rate
+            );
+        }
+    }
+
+    #[test]
+    fn missing_file_fails_to_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = SyntheticSource::from_wav_file(
+            dir.path().join("missing.wav"),
+            BufferSizeRequest::Default,
+        );
+        assert!(result.is_err());
+    }
+}
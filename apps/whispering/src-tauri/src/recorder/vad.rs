@@ -0,0 +1,257 @@
+use crate::recorder::quality::to_dbfs;
+use crate::recorder::sink::AudioSink;
+use serde::Serialize;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Length of the frames speech is detected on
+const FRAME_MS: u32 = 30;
+
+/// Frames below this level are never speech, however quiet the room
+const MIN_SPEECH_DBFS: f32 = -55.0;
+
+/// Level above the noise floor that counts as speech
+const SPEECH_MARGIN_DB: f32 = 10.0;
+
+/// Speech has to last this long to start a segment, which ignores clicks and taps
+const SPEECH_START_MS: u32 = 90;
+
+/// Silence has to last this long to end a segment, which bridges pauses between words
+const SPEECH_HANGOVER_MS: u32 = 400;
+
+/// How quickly the noise floor follows quieter and louder frames (per frame)
+const FLOOR_FALL_RATE: f32 = 0.2;
+const FLOOR_RISE_RATE: f32 = 0.005;
+
+/// Start or end of speech - emitted to frontend
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceActivity {
+    pub speaking: bool,
+    pub time_seconds: f32, // Where speech started or ended, from the start of the recording
+}
+
+/// Called from the audio thread when speech starts or ends
+pub type VoiceActivityHandler = Arc<dyn Fn(VoiceActivity) + Send + Sync>;
+
+/// Energy based voice activity detector.
+///
+/// Tracks the noise floor of the input and reports speech while frames stay clearly
+/// above it. Good enough to spot when someone is talking, not to tell speech from music.
+pub struct VoiceActivityDetector {
+    speaking: Arc<AtomicBool>,
+    on_activity: Option<VoiceActivityHandler>,
+    frame_samples: usize,
+    frames_per_second: f32,
+    frame_position: usize,
+    frame_sum_squares: f64,
+    frames_seen: u64,
+    noise_floor_dbfs: Option<f32>,
+    in_speech: bool,
+    run_frames: u32, // Consecutive frames disagreeing with the current state
+    start_frames: u32,
+    hangover_frames: u32,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(on_activity: Option<VoiceActivityHandler>) -> Self {
+        Self {
+            speaking: Arc::new(AtomicBool::new(false)),
+            on_activity,
+            frame_samples: 0,
+            frames_per_second: 0.0,
+            frame_position: 0,
+            frame_sum_squares: 0.0,
+            frames_seen: 0,
+            noise_floor_dbfs: None,
+            in_speech: false,
+            run_frames: 0,
+            start_frames: 1,
+            hangover_frames: 1,
+        }
+    }
+
+    /// Shared flag that is set while speech is detected
+    pub fn speaking(&self) -> Arc<AtomicBool> {
+        self.speaking.clone()
+    }
+
+    fn end_frame(&mut self) {
+        let level = to_dbfs((self.frame_sum_squares / self.frame_position as f64).sqrt() as f32);
+        self.frame_position = 0;
+        self.frame_sum_squares = 0.0;
+        self.frames_seen += 1;
+
+        let floor = *self.noise_floor_dbfs.get_or_insert(level);
+        let is_speech = level > MIN_SPEECH_DBFS && level > floor + SPEECH_MARGIN_DB;
+
+        // Follow drops in the floor quickly and rises slowly, so speech doesn't raise it
+        let rate = if level < floor {
+            FLOOR_FALL_RATE
+        } else {
+            FLOOR_RISE_RATE
+        };
+        self.noise_floor_dbfs = Some(floor + (level - floor) * rate);
+
+        if is_speech == self.in_speech {
+            self.run_frames = 0;
+            return;
+        }
+        self.run_frames += 1;
+        let needed = if self.in_speech {
+            self.hangover_frames
+        } else {
+            self.start_frames
+        };
+        if self.run_frames < needed {
+            return;
+        }
+
+        // The change happened where the run started
+        self.in_speech = is_speech;
+        self.speaking.store(is_speech, Ordering::Release);
+        let activity = VoiceActivity {
+            speaking: is_speech,
+            time_seconds: (self.frames_seen - self.run_frames as u64) as f32
+                / self.frames_per_second,
+        };
+        self.run_frames = 0;
+        if let Some(handler) = &self.on_activity {
+            handler(activity);
+        }
+    }
+}
+
+impl AudioSink for VoiceActivityDetector {
+    fn name(&self) -> &str {
+        "voice activity"
+    }
+
+    fn open(&mut self, sample_rate: u32, channels: u16) -> io::Result<()> {
+        let frame_frames = (sample_rate * FRAME_MS / 1000).max(1);
+        self.frame_samples = frame_frames as usize * channels.max(1) as usize;
+        self.frames_per_second = sample_rate as f32 / frame_frames as f32;
+        self.start_frames = SPEECH_START_MS.div_ceil(FRAME_MS);
+        self.hangover_frames = SPEECH_HANGOVER_MS.div_ceil(FRAME_MS);
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            self.frame_sum_squares += (sample as f64) * (sample as f64);
+            self.frame_position += 1;
+            if self.frame_position >= self.frame_samples {
+                self.end_frame();
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.speaking.store(false, Ordering::Release);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const SAMPLE_RATE: u32 = 16000;
+    const FRAME: usize = (SAMPLE_RATE * FRAME_MS / 1000) as usize;
+    const QUIET: f32 = 0.0007; // About -63 dBFS
+    const LOUD: f32 = 0.2; // About -14 dBFS
+
+    /// A level held for a number of frames
+    type Run = (f32, usize);
+    /// Speech starting (true) or ending at a frame
+    type Change = (bool, u32);
+
+    /// Whole detector frames of a square wave with the given RMS level
+    fn frames(level: f32, count: usize) -> Vec<f32> {
+        (0..count * FRAME)
+            .map(|i| if i % 2 == 0 { level } else { -level })
+            .collect()
+    }
+
+    /// Feed `parts` to a detector, returning the reported (speaking, frame) changes
+    fn detect(parts: &[Run]) -> Vec<Change> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let handler: VoiceActivityHandler =
+            Arc::new(move |activity: VoiceActivity| sink.lock().unwrap().push(activity));
+        let mut detector = VoiceActivityDetector::new(Some(handler));
+        detector.open(SAMPLE_RATE, 1).unwrap();
+        for &(level, count) in parts {
+            detector.write(&frames(level, count)).unwrap();
+        }
+
+        let frames_per_second = SAMPLE_RATE as f32 / FRAME as f32;
+        let events = events.lock().unwrap();
+        events
+            .iter()
+            .map(|a| {
+                (
+                    a.speaking,
+                    (a.time_seconds * frames_per_second).round() as u32,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_where_speech_starts_and_ends() {
+        // (quiet and loud runs in frames, expected changes at frames)
+        let cases: &[(&[Run], &[Change])] = &[
+            (
+                &[(QUIET, 40), (LOUD, 40), (QUIET, 40)],
+                &[(true, 40), (false, 80)],
+            ),
+            // Clicks shorter than the start time are ignored
+            (&[(QUIET, 40), (LOUD, 2), (QUIET, 40)], &[]),
+            // Pauses shorter than the hangover don't end speech
+            (
+                &[
+                    (QUIET, 40),
+                    (LOUD, 20),
+                    (QUIET, 10),
+                    (LOUD, 20),
+                    (QUIET, 20),
+                ],
+                &[(true, 40), (false, 90)],
+            ),
+            // Speech still going when the input ends isn't closed
+            (&[(QUIET, 40), (LOUD, 40)], &[(true, 40)]),
+            // Hangover not yet over
+            (&[(QUIET, 40), (LOUD, 40), (QUIET, 13)], &[(true, 40)]),
+        ];
+        for &(parts, expected) in cases {
+            assert_eq!(detect(parts), expected, "{:?}", parts);
+        }
+    }
+
+    #[test]
+    fn steady_sound_is_not_speech() {
+        // However loud, a constant level is the noise floor
+        assert_eq!(detect(&[(LOUD, 100)]), []);
+        // Quiet rooms never count as speech, even above their floor
+        assert_eq!(detect(&[(0.00001, 40), (0.001, 40)]), []);
+    }
+
+    #[test]
+    fn speaking_flag_follows_detection() {
+        let mut detector = VoiceActivityDetector::new(None);
+        let speaking = detector.speaking();
+        detector.open(SAMPLE_RATE, 2).unwrap();
+
+        // Stereo frames hold twice the samples
+        detector.write(&frames(QUIET, 80)).unwrap();
+        assert!(!speaking.load(Ordering::Acquire));
+        detector.write(&frames(LOUD, 20)).unwrap();
+        assert!(speaking.load(Ordering::Acquire));
+        detector.finish().unwrap();
+        assert!(!speaking.load(Ordering::Acquire));
+    }
+}
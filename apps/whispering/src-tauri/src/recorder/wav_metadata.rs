@@ -0,0 +1,174 @@
+use crate::recorder::writer::RecordingInfo;
+use chrono::{DateTime, Local, Timelike};
+
+/// Software name embedded in recordings
+const SOFTWARE: &str = concat!("Whispering ", env!("CARGO_PKG_VERSION"));
+
+/// Size of a version 1 `bext` chunk without coding history
+const BEXT_LEN: usize = 602;
+
+/// Encode the metadata chunks written ahead of the audio data.
+///
+/// A `LIST`/`INFO` chunk carries the title, creation date, software and input device,
+/// and when enabled a Broadcast Wave `bext` chunk records the origination time and the
+/// time reference (samples since midnight) that DAWs use to place the recording on a
+/// timeline. Both are standard RIFF chunks that other tools read and keep.
+pub fn encode_metadata_chunks(
+    info: &RecordingInfo,
+    sample_rate: u32,
+    created: DateTime<Local>,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    // LIST/INFO chunk with NUL-terminated text entries
+    let mut list = Vec::new();
+    list.extend_from_slice(b"INFO");
+    if let Some(title) = &info.title {
+        push_info_entry(&mut list, b"INAM", title);
+    }
+    push_info_entry(&mut list, b"ICRD", &created.format("%Y-%m-%d").to_string());
+    push_info_entry(&mut list, b"ISFT", SOFTWARE);
+    if let Some(device) = &info.device {
+        push_info_entry(&mut list, b"ICMT", &format!("Input device: {}", device));
+    }
+    out.extend_from_slice(b"LIST");
+    out.extend_from_slice(&(list.len() as u32).to_le_bytes());
+    out.extend_from_slice(&list);
+
+    if info.broadcast_extension {
+        let midnight_offset = created.num_seconds_from_midnight() as u64 * sample_rate as u64
+            + created.nanosecond().min(999_999_999) as u64 * sample_rate as u64 / 1_000_000_000;
+
+        let mut bext = Vec::with_capacity(BEXT_LEN);
+        push_fixed_text(&mut bext, info.title.as_deref().unwrap_or(""), 256); // Description
+        push_fixed_text(&mut bext, "Whispering", 32); // Originator
+        push_fixed_text(&mut bext, info.title.as_deref().unwrap_or(""), 32); // Originator reference
+        push_fixed_text(&mut bext, &created.format("%Y-%m-%d").to_string(), 10);
+        push_fixed_text(&mut bext, &created.format("%H:%M:%S").to_string(), 8);
+        bext.extend_from_slice(&midnight_offset.to_le_bytes()); // Time reference, low then high
+        bext.extend_from_slice(&1u16.to_le_bytes()); // Version
+        bext.resize(BEXT_LEN, 0); // UMID and reserved bytes, no coding history
+
+        out.extend_from_slice(b"bext");
+        out.extend_from_slice(&(bext.len() as u32).to_le_bytes());
+        out.extend_from_slice(&bext);
+    }
+
+    out
+}
+
+/// Append an INFO entry, padded to keep chunks word aligned
+fn push_info_entry(list: &mut Vec<u8>, id: &[u8; 4], text: &str) {
+    let mut text = text.as_bytes().to_vec();
+    text.push(0);
+    list.extend_from_slice(id);
+    list.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    list.extend_from_slice(&text);
+    if text.len() % 2 == 1 {
+        list.push(0);
+    }
+}
+
+/// Append text to a fixed-size, NUL-padded field, cut at a character boundary if too long
+fn push_fixed_text(out: &mut Vec<u8>, text: &str, len: usize) {
+    let mut end = text.len().min(len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    out.extend_from_slice(&text.as_bytes()[..end]);
+    out.extend(std::iter::repeat_n(0, len - end));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn created() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 5, 14, 30, 15).unwrap() + Duration::milliseconds(500)
+    }
+
+    /// The body of the chunk at the start of `bytes`, and what follows it
+    fn split_chunk<'a>(bytes: &'a [u8], id: &[u8; 4]) -> (&'a [u8], &'a [u8]) {
+        assert_eq!(&bytes[..4], id);
+        let size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        (&bytes[8..8 + size], &bytes[8 + size..])
+    }
+
+    #[test]
+    fn info_list_holds_date_and_software_by_default() {
+        let chunks = encode_metadata_chunks(&RecordingInfo::default(), 48000, created());
+
+        let (list, rest) = split_chunk(&chunks, b"LIST");
+        assert!(rest.is_empty(), "no bext chunk unless asked for");
+        let software = format!("{}\0", SOFTWARE);
+        let mut expected = [
+            &b"INFO"[..],
+            b"ICRD",
+            &11u32.to_le_bytes(),
+            b"2024-03-05\0\0", // Padded to an even length
+            b"ISFT",
+            &(software.len() as u32).to_le_bytes(),
+            software.as_bytes(),
+        ]
+        .concat();
+        if software.len() % 2 == 1 {
+            expected.push(0);
+        }
+        assert_eq!(list, expected);
+    }
+
+    #[test]
+    fn bext_records_origination_and_time_reference() {
+        let info = RecordingInfo {
+            title: Some("Interview".to_string()),
+            device: Some("Mic".to_string()),
+            broadcast_extension: true,
+        };
+        let chunks = encode_metadata_chunks(&info, 48000, created());
+
+        let (list, rest) = split_chunk(&chunks, b"LIST");
+        assert!(list.windows(5).any(|w| w == b"INAM\x0a"));
+        assert!(list.windows(18).any(|w| w == b"Input device: Mic\0"));
+        let (bext, rest) = split_chunk(rest, b"bext");
+        assert!(rest.is_empty());
+        assert_eq!(bext.len(), BEXT_LEN);
+
+        let field = |range: std::ops::Range<usize>| {
+            std::str::from_utf8(&bext[range])
+                .unwrap()
+                .trim_end_matches('\0')
+                .to_string()
+        };
+        assert_eq!(field(0..256), "Interview"); // Description
+        assert_eq!(field(256..288), "Whispering"); // Originator
+        assert_eq!(field(288..320), "Interview"); // Originator reference
+        assert_eq!(field(320..330), "2024-03-05");
+        assert_eq!(field(330..338), "14:30:15");
+        let samples_since_midnight: u64 = (14 * 3600 + 30 * 60 + 15) * 48000 + 24000;
+        assert_eq!(bext[338..346], samples_since_midnight.to_le_bytes());
+        assert_eq!(bext[346..348], 1u16.to_le_bytes());
+        assert!(bext[348..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn long_titles_are_cut_at_a_character_boundary() {
+        let info = RecordingInfo {
+            title: Some(format!("a{}", "é".repeat(200))),
+            device: None,
+            broadcast_extension: true,
+        };
+        let chunks = encode_metadata_chunks(&info, 16000, created());
+        let (_, rest) = split_chunk(&chunks, b"LIST");
+        let (bext, _) = split_chunk(rest, b"bext");
+
+        // 'a' and 127 two-byte characters, the half character that would fit is dropped
+        let description = std::str::from_utf8(&bext[..255]).unwrap();
+        assert_eq!(description.chars().count(), 128);
+        assert_eq!(bext[255], 0);
+        let reference = std::str::from_utf8(&bext[288..319]).unwrap();
+        assert_eq!(reference.chars().count(), 16);
+        assert_eq!(bext[319], 0);
+        assert_eq!(bext.len(), BEXT_LEN);
+    }
+}
@@ -1,4 +1,10 @@
-use std::fs::File;
+use crate::recorder::encryption::RecordingFile;
+use crate::recorder::live_reader::LiveRecording;
+use crate::recorder::markers::encode_marker_chunks;
+use crate::recorder::wav_metadata::encode_metadata_chunks;
+use crate::recorder::writer::{Ditherer, OutputSampleFormat, WriterOptions};
+use crate::recorder::writer_stats::WriterStats;
+use chrono::Local;
 use std::io::{self, BufWriter, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 use std::time::Instant;
@@ -6,27 +12,37 @@ use tracing::{debug, info};
 
 /// WAV file writer that supports progressive writing with header updates
 pub struct WavWriter {
-    writer: BufWriter<File>,
-    sample_rate: u32,
-    channels: u16,
-    #[allow(dead_code)]
-    bits_per_sample: u16,
+    writer: BufWriter<RecordingFile>,
+    sample_format: OutputSampleFormat,
     bytes_per_sample: u16,
+    dither: Ditherer,
     data_chunk_size_pos: u64,
     riff_chunk_size_pos: u64,
-    samples_written: u64,
     last_header_update: Instant,
-    file_path: PathBuf,
+    stats: WriterStats,
+    trailer_len: u64, // Bytes of chunks written after the data chunk by finalize
+    live: LiveRecording,
 }
 
 impl WavWriter {
     /// Create a new WAV file and write initial headers
     pub fn new(file_path: PathBuf, sample_rate: u32, channels: u16) -> io::Result<Self> {
-        let file = File::create(&file_path)?;
+        Self::with_options(file_path, sample_rate, channels, WriterOptions::default())
+    }
+
+    /// Create a new WAV file with the given options and write initial headers
+    pub fn with_options(
+        file_path: PathBuf,
+        sample_rate: u32,
+        channels: u16,
+        options: WriterOptions,
+    ) -> io::Result<Self> {
+        let file = RecordingFile::create(&file_path, options.encryption_key.as_ref())?;
+        let encrypted = file.is_encrypted();
         let mut writer = BufWriter::new(file);
 
-        // We'll use 32-bit float format for consistency with the current implementation
-        let bits_per_sample = 32;
+        let sample_format = options.sample_format;
+        let bits_per_sample = sample_format.bits_per_sample();
         let bytes_per_sample = bits_per_sample / 8;
 
         // Write initial WAV header with placeholder sizes
@@ -41,7 +57,8 @@ impl WavWriter {
         // fmt chunk
         writer.write_all(b"fmt ")?;
         writer.write_all(&16u32.to_le_bytes())?; // Subchunk1Size (16 for PCM)
-        writer.write_all(&3u16.to_le_bytes())?; // AudioFormat (3 for IEEE Float)
+        let audio_format: u16 = if sample_format.is_float() { 3 } else { 1 };
+        writer.write_all(&audio_format.to_le_bytes())?; // AudioFormat (1 for PCM, 3 for IEEE Float)
         writer.write_all(&channels.to_le_bytes())?;
         writer.write_all(&sample_rate.to_le_bytes())?;
         let byte_rate = sample_rate * channels as u32 * bytes_per_sample as u32;
@@ -50,6 +67,13 @@ impl WavWriter {
         writer.write_all(&block_align.to_le_bytes())?;
         writer.write_all(&bits_per_sample.to_le_bytes())?;
 
+        // LIST/INFO and bext chunks, so the file describes itself in other tools
+        writer.write_all(&encode_metadata_chunks(
+            &options.info,
+            sample_rate,
+            Local::now(),
+        ))?;
+
         // data chunk
         writer.write_all(b"data")?;
         let data_chunk_size_pos = writer.stream_position()?;
@@ -57,73 +81,71 @@ impl WavWriter {
 
         writer.flush()?;
 
+        let live = LiveRecording::new(
+            file_path.clone(),
+            data_chunk_size_pos + 4,
+            sample_rate,
+            channels,
+            sample_format,
+        );
+
         info!(
-            "Created WAV file at {:?}: {}Hz, {} channels, {}-bit float",
-            file_path, sample_rate, channels, bits_per_sample
+            "Created WAV file at {:?}: {}Hz, {} channels, {:?}{}",
+            file_path,
+            sample_rate,
+            channels,
+            sample_format,
+            if encrypted { ", encrypted" } else { "" }
         );
 
         Ok(Self {
             writer,
-            sample_rate,
-            channels,
-            bits_per_sample,
+            sample_format,
             bytes_per_sample,
+            dither: Ditherer::new(),
             data_chunk_size_pos,
             riff_chunk_size_pos,
-            samples_written: 0,
             last_header_update: Instant::now(),
-            file_path,
+            stats: WriterStats::new(file_path, sample_rate, channels, options.encryption_key),
+            trailer_len: 0,
+            live,
         })
     }
 
     /// Write f32 samples to the WAV file
     pub fn write_samples_f32(&mut self, samples: &[f32]) -> io::Result<()> {
-        // Write samples as little-endian f32
-        for sample in samples {
-            self.writer.write_all(&sample.to_le_bytes())?;
-        }
-
-        self.samples_written += samples.len() as u64;
-
-        // Update headers periodically (every second)
-        if self.last_header_update.elapsed().as_secs() >= 1 {
-            self.update_headers()?;
-            self.last_header_update = Instant::now();
-        }
-
-        Ok(())
-    }
-
-    /// Write i16 samples to the WAV file (converting to f32)
-    pub fn write_samples_i16(&mut self, samples: &[i16]) -> io::Result<()> {
-        // Convert i16 to f32 and write
         for &sample in samples {
-            let f32_sample = sample as f32 / i16::MAX as f32;
-            self.writer.write_all(&f32_sample.to_le_bytes())?;
+            self.write_float(sample)?;
+            self.stats.analyze(sample);
         }
 
-        self.samples_written += samples.len() as u64;
+        self.on_samples_written(samples.len())
+    }
 
-        // Update headers periodically
-        if self.last_header_update.elapsed().as_secs() >= 1 {
-            self.update_headers()?;
-            self.last_header_update = Instant::now();
+    /// Encode a float sample in the output format, dithering when it loses precision
+    fn write_float(&mut self, sample: f32) -> io::Result<()> {
+        match self.sample_format {
+            OutputSampleFormat::Float32 => self.writer.write_all(&sample.to_le_bytes()),
+            OutputSampleFormat::Pcm16 => {
+                let value = self.dither.quantize(sample, 16);
+                self.writer.write_all(&(value as i16).to_le_bytes())
+            }
+            OutputSampleFormat::Pcm24 => {
+                let value = self.dither.quantize(sample, 24);
+                self.writer.write_all(&value.to_le_bytes()[..3])
+            }
         }
-
-        Ok(())
     }
 
-    /// Write u16 samples to the WAV file (converting to f32)
-    pub fn write_samples_u16(&mut self, samples: &[u16]) -> io::Result<()> {
-        // Convert u16 to f32 and write
-        for &sample in samples {
-            let f32_sample = (sample as f32 / u16::MAX as f32) * 2.0 - 1.0;
-            self.writer.write_all(&f32_sample.to_le_bytes())?;
-        }
+    /// Account for written samples and update headers periodically (every second)
+    fn on_samples_written(&mut self, count: usize) -> io::Result<()> {
+        self.stats.add_samples(count);
 
-        self.samples_written += samples.len() as u64;
+        // New samples overwrite any trailer written by a previous finalize
+        if count > 0 {
+            self.trailer_len = 0;
+        }
 
-        // Update headers periodically
         if self.last_header_update.elapsed().as_secs() >= 1 {
             self.update_headers()?;
             self.last_header_update = Instant::now();
@@ -137,8 +159,9 @@ impl WavWriter {
         let current_pos = self.writer.stream_position()?;
 
         // Calculate sizes
-        let data_size = self.samples_written * self.bytes_per_sample as u64;
-        let file_size = 36 + data_size; // 36 = header size minus RIFF header
+        let data_size = self.stats.samples_written() * self.bytes_per_sample as u64;
+        let data_start = self.data_chunk_size_pos + 4;
+        let file_size = data_start - 8 + data_size + self.trailer_len; // Minus RIFF header
 
         // Update RIFF chunk size
         self.writer
@@ -154,43 +177,71 @@ impl WavWriter {
         self.writer.seek(SeekFrom::Start(current_pos))?;
         self.writer.flush()?;
 
+        // Everything written so far is on disk now, let readers see it
+        self.live.publish(self.stats.frames_written());
+
         debug!(
             "Updated WAV headers: {} samples written ({:.2} seconds)",
-            self.samples_written,
-            self.get_duration_seconds()
+            self.stats.samples_written(),
+            self.stats.duration_seconds()
         );
 
         Ok(())
     }
 
+    /// Write the chunks that follow the audio data (markers).
+    /// The write position stays at the end of the data, so any further samples
+    /// overwrite the trailer and the next finalize writes it again.
+    fn write_trailer(&mut self) -> io::Result<()> {
+        let data_end = self.writer.stream_position()?;
+        let data_size = self.stats.samples_written() * self.bytes_per_sample as u64;
+
+        let mut trailer = Vec::new();
+        if data_size % 2 == 1 {
+            trailer.push(0); // Pad byte, chunks are word aligned
+        }
+        trailer.extend_from_slice(&encode_marker_chunks(self.stats.markers()));
+
+        self.writer.write_all(&trailer)?;
+        self.writer.flush()?;
+        self.writer
+            .get_mut()
+            .set_len(data_end + trailer.len() as u64)?;
+        self.writer.seek(SeekFrom::Start(data_end))?;
+        self.trailer_len = trailer.len() as u64;
+
+        Ok(())
+    }
+
     /// Finalize the WAV file with correct headers
     pub fn finalize(&mut self) -> io::Result<()> {
+        self.write_trailer()?;
         self.update_headers()?;
         self.writer.flush()?;
 
+        self.stats.write_peaks();
+
         info!(
             "Finalized WAV file {:?}: {} samples, {:.2} seconds",
-            self.file_path,
-            self.samples_written,
-            self.get_duration_seconds()
+            self.stats.file_path(),
+            self.stats.samples_written(),
+            self.stats.duration_seconds()
         );
 
         Ok(())
     }
 
-    /// Get the current duration in seconds
-    pub fn get_duration_seconds(&self) -> f32 {
-        self.samples_written as f32 / (self.sample_rate as f32 * self.channels as f32)
+    pub fn stats(&self) -> &WriterStats {
+        &self.stats
     }
 
-    /// Get the file path
-    pub fn get_file_path(&self) -> &PathBuf {
-        &self.file_path
+    pub fn stats_mut(&mut self) -> &mut WriterStats {
+        &mut self.stats
     }
 
-    /// Get audio metadata
-    pub fn get_metadata(&self) -> (u32, u16, f32) {
-        (self.sample_rate, self.channels, self.get_duration_seconds())
+    /// Handle for reading the file while it is still being written
+    pub fn live_recording(&self) -> LiveRecording {
+        self.live.clone()
     }
 
     /// Flush any buffered data to disk
@@ -208,3 +259,241 @@ impl Drop for WavWriter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::writer::RecordingInfo;
+    use std::path::Path;
+
+    /// A chunk id and body
+    type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+    /// Split a sequence of RIFF chunks, checking every size adds up
+    fn parse_chunks(mut bytes: &[u8]) -> Vec<Chunk<'_>> {
+        let mut chunks = Vec::new();
+        while !bytes.is_empty() {
+            let id = bytes[..4].try_into().unwrap();
+            let size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+            chunks.push((id, &bytes[8..8 + size]));
+            // Chunks are word aligned, odd ones are followed by a pad byte
+            bytes = &bytes[(8 + size + size % 2).min(bytes.len())..];
+        }
+        chunks
+    }
+
+    /// The chunks of a WAV file, checking the RIFF header covers exactly the file
+    fn wav_chunks(bytes: &[u8]) -> Vec<Chunk<'_>> {
+        assert_eq!(&bytes[..4], b"RIFF");
+        let riff_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(riff_size, bytes.len() - 8);
+        assert_eq!(&bytes[8..12], b"WAVE");
+        parse_chunks(&bytes[12..])
+    }
+
+    fn ids(chunks: &[Chunk]) -> Vec<String> {
+        chunks
+            .iter()
+            .map(|(id, _)| String::from_utf8_lossy(id).to_string())
+            .collect()
+    }
+
+    fn list_type<'a>(chunks: &[Chunk<'a>], list_type: &[u8; 4]) -> Vec<Chunk<'a>> {
+        let (_, body) = chunks
+            .iter()
+            .find(|(id, body)| id == b"LIST" && &body[..4] == list_type)
+            .expect("LIST chunk");
+        parse_chunks(&body[4..])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn pcm16_writer(path: &Path) -> WavWriter {
+        let options = WriterOptions {
+            sample_format: OutputSampleFormat::Pcm16,
+            ..WriterOptions::default()
+        };
+        WavWriter::with_options(path.to_path_buf(), 16000, 1, options).unwrap()
+    }
+
+    #[test]
+    fn header_describes_every_sample_format() {
+        let dir = tempfile::tempdir().unwrap();
+        // (format, audio format tag, bits per sample)
+        let formats = [
+            (OutputSampleFormat::Pcm16, 1, 16),
+            (OutputSampleFormat::Pcm24, 1, 24),
+            (OutputSampleFormat::Float32, 3, 32),
+        ];
+        for (sample_format, tag, bits) in formats {
+            let path = dir.path().join(format!("{:?}.wav", sample_format));
+            let options = WriterOptions {
+                sample_format,
+                ..WriterOptions::default()
+            };
+            let mut writer = WavWriter::with_options(path.clone(), 44100, 2, options).unwrap();
+            writer.write_samples_f32(&[0.25; 64]).unwrap();
+            writer.finalize().unwrap();
+            drop(writer);
+
+            let bytes = std::fs::read(&path).unwrap();
+            let chunks = wav_chunks(&bytes);
+            assert_eq!(ids(&chunks), ["fmt ", "LIST", "data"]);
+            let fmt = chunks[0].1;
+            let block_align = 2 * bits / 8;
+            assert_eq!(fmt[..2], (tag as u16).to_le_bytes());
+            assert_eq!(fmt[2..4], 2u16.to_le_bytes());
+            assert_eq!(u32_at(fmt, 4), 44100);
+            assert_eq!(u32_at(fmt, 8), 44100 * block_align);
+            assert_eq!(fmt[12..14], (block_align as u16).to_le_bytes());
+            assert_eq!(fmt[14..16], (bits as u16).to_le_bytes());
+            assert_eq!(chunks[2].1.len() as u32, 64 * bits / 8);
+
+            let spec = hound::WavReader::open(&path).unwrap().spec();
+            assert_eq!(spec.bits_per_sample as u32, bits, "{:?}", sample_format);
+            assert_eq!((spec.sample_rate, spec.channels), (44100, 2));
+        }
+    }
+
+    #[test]
+    fn metadata_chunks_precede_the_audio() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("described.wav");
+        let options = WriterOptions {
+            info: RecordingInfo {
+                title: Some("Standup".to_string()),
+                device: Some("USB Mic".to_string()),
+                broadcast_extension: true,
+            },
+            ..WriterOptions::default()
+        };
+        let mut writer = WavWriter::with_options(path.clone(), 48000, 1, options).unwrap();
+        writer.write_samples_f32(&[0.1; 480]).unwrap();
+        writer.stats_mut().add_marker("Point".to_string());
+        writer.finalize().unwrap();
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let chunks = wav_chunks(&bytes);
+        assert_eq!(
+            ids(&chunks),
+            ["fmt ", "LIST", "bext", "data", "cue ", "LIST"]
+        );
+        let info = list_type(&chunks, b"INFO");
+        assert_eq!(ids(&info), ["INAM", "ICRD", "ISFT", "ICMT"]);
+        assert_eq!(info[0].1, b"Standup\0");
+        assert_eq!(info[3].1, b"Input device: USB Mic\0");
+        assert_eq!(chunks[2].1.len(), 602);
+        assert_eq!(&chunks[2].1[..8], b"Standup\0");
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration(), 480);
+        assert!(reader.samples::<f32>().all(|s| s.unwrap() == 0.1));
+    }
+
+    #[test]
+    fn markers_are_written_as_cue_points_with_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("markers.wav");
+        let mut writer = pcm16_writer(&path);
+        writer.write_samples_f32(&[0.25; 1000]).unwrap();
+        writer.stats_mut().add_marker("Intro".to_string());
+        writer.write_samples_f32(&[0.25; 500]).unwrap();
+        writer.stats_mut().add_marker(" ".to_string());
+        writer.finalize().unwrap();
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let chunks = wav_chunks(&bytes);
+        assert_eq!(ids(&chunks), ["fmt ", "LIST", "data", "cue ", "LIST"]);
+        assert_eq!(chunks[2].1.len(), 1500 * 2);
+
+        let cue = chunks[3].1;
+        assert_eq!(u32_at(cue, 0), 2);
+        for (i, (id, position)) in [(1, 1000), (2, 1500)].into_iter().enumerate() {
+            let point = &cue[4 + 24 * i..4 + 24 * (i + 1)];
+            assert_eq!(u32_at(point, 0), id);
+            assert_eq!(u32_at(point, 4), position);
+            assert_eq!(&point[8..12], b"data");
+            assert_eq!(u32_at(point, 20), position);
+        }
+
+        let labels = list_type(&chunks, b"adtl");
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[0].0, *b"labl");
+        assert_eq!(u32_at(labels[0].1, 0), 1);
+        assert_eq!(&labels[0].1[4..], b"Intro\0");
+        // Blank labels are named, odd lengths are padded
+        assert_eq!(u32_at(labels[1].1, 0), 2);
+        assert_eq!(&labels[1].1[4..], b"Marker 2\0");
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration(), 1500);
+    }
+
+    #[test]
+    fn recording_after_finalize_rewrites_the_trailer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("resumed.wav");
+        let mut writer = pcm16_writer(&path);
+        writer.write_samples_f32(&[0.5; 100]).unwrap();
+        writer.finalize().unwrap();
+        assert_eq!(
+            ids(&wav_chunks(&std::fs::read(&path).unwrap())),
+            ["fmt ", "LIST", "data"]
+        );
+
+        writer.stats_mut().add_marker("First".to_string());
+        writer.finalize().unwrap();
+        writer.write_samples_f32(&[-0.5; 200]).unwrap();
+        writer.stats_mut().add_marker("Second".to_string());
+        writer.finalize().unwrap();
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let chunks = wav_chunks(&bytes);
+        assert_eq!(ids(&chunks), ["fmt ", "LIST", "data", "cue ", "LIST"]);
+        assert_eq!(u32_at(chunks[3].1, 0), 2);
+        assert_eq!(list_type(&chunks, b"adtl").len(), 2);
+
+        // The audio written after the first trailer replaced it
+        let samples: Vec<i16> = hound::WavReader::open(&path)
+            .unwrap()
+            .samples::<i16>()
+            .map(|s| s.unwrap())
+            .collect();
+        assert_eq!(samples.len(), 300);
+        assert!(samples[..100].iter().all(|&s| s == 16384));
+        assert!(samples[100..].iter().all(|&s| s == -16384));
+    }
+
+    #[test]
+    fn odd_sized_data_is_padded_before_the_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pcm24.wav");
+        let options = WriterOptions {
+            sample_format: OutputSampleFormat::Pcm24,
+            ..WriterOptions::default()
+        };
+        let mut writer = WavWriter::with_options(path.clone(), 48000, 1, options).unwrap();
+        writer.write_samples_f32(&[0.5, -0.5, 0.0]).unwrap();
+        writer.stats_mut().add_marker("End".to_string());
+        writer.finalize().unwrap();
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let chunks = wav_chunks(&bytes);
+        assert_eq!(ids(&chunks), ["fmt ", "LIST", "data", "cue ", "LIST"]);
+        assert_eq!(chunks[2].1.len(), 9);
+        assert_eq!(u32_at(chunks[3].1, 8), 3);
+
+        let samples: Vec<i32> = hound::WavReader::open(&path)
+            .unwrap()
+            .samples::<i32>()
+            .map(|s| s.unwrap())
+            .collect();
+        assert_eq!(samples, [1 << 22, -(1 << 22), 0]);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::recorder::encryption::{RecordingKey, ENCRYPTED_EXTENSION};
+use crate::recorder::flac_writer::FlacWriter;
+use crate::recorder::live_reader::LiveRecording;
+use crate::recorder::markers::RecordingMarker;
+use crate::recorder::opus_writer::{OpusWriter, ENCRYPTED_OPUS_EXTENSION, OPUS_EXTENSION};
+use crate::recorder::peaks::WaveformPeaks;
+use crate::recorder::quality::{SignalQuality, SignalWarning};
+use crate::recorder::storage::{COMPRESSED_EXTENSION, ENCRYPTED_COMPRESSED_EXTENSION};
+use crate::recorder::wav_writer::WavWriter;
+use crate::recorder::writer_stats::WriterStats;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+/// File format a recording is written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingFormat {
+    #[default]
+    Wav,
+    Flac,
+    Opus,
+}
+
+impl RecordingFormat {
+    /// File extension for recordings in this format
+    pub fn extension(self, encrypted: bool) -> &'static str {
+        match (self, encrypted) {
+            (Self::Wav, false) => "wav",
+            (Self::Wav, true) => ENCRYPTED_EXTENSION,
+            (Self::Flac, false) => COMPRESSED_EXTENSION,
+            (Self::Flac, true) => ENCRYPTED_COMPRESSED_EXTENSION,
+            (Self::Opus, false) => OPUS_EXTENSION,
+            (Self::Opus, true) => ENCRYPTED_OPUS_EXTENSION,
+        }
+    }
+}
+
+/// Sample format of the audio data in the recording
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputSampleFormat {
+    Pcm16,
+    Pcm24,
+    #[default]
+    Float32,
+}
+
+impl OutputSampleFormat {
+    pub fn bits_per_sample(self) -> u16 {
+        match self {
+            Self::Pcm16 => 16,
+            Self::Pcm24 => 24,
+            Self::Float32 => 32,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        self == Self::Float32
+    }
+}
+
+/// Options for how a recording is written to disk
+#[derive(Debug, Clone, Default)]
+pub struct WriterOptions {
+    /// Encrypt the file at rest with this key; audio never reaches disk unencrypted
+    pub encryption_key: Option<RecordingKey>,
+    /// Sample format written to the file (32-bit float unless set)
+    pub sample_format: OutputSampleFormat,
+    /// Container the recording is written in
+    pub format: RecordingFormat,
+    /// Descriptive metadata embedded in formats that support it (WAV)
+    pub info: RecordingInfo,
+}
+
+/// Descriptive metadata embedded in a recording
+#[derive(Debug, Clone, Default)]
+pub struct RecordingInfo {
+    /// Title of the recording
+    pub title: Option<String>,
+    /// Name of the input device
+    pub device: Option<String>,
+    /// Also write a Broadcast Wave `bext` chunk with the origination time
+    pub broadcast_extension: bool,
+}
+
+/// TPDF dither for reducing float samples to integer PCM.
+///
+/// Adds triangular noise of +/-1 LSB before rounding, which turns quantization
+/// distortion on quiet passages into a constant, inaudible noise floor.
+pub(crate) struct Ditherer {
+    state: u32,
+}
+
+impl Ditherer {
+    pub fn new() -> Self {
+        Self { state: 0x9E37_79B9 }
+    }
+
+    /// Quantize a sample to a signed integer of the given bit depth
+    pub fn quantize(&mut self, sample: f32, bits: u16) -> i32 {
+        let full_scale = (1i64 << (bits - 1)) as f64;
+        let scaled = sample.clamp(-1.0, 1.0) as f64 * full_scale;
+        // Audio from integer devices is scaled by 2^15 on capture, so at 16 bits and above
+        // it is already exact and passes through untouched
+        let value = if scaled.fract() == 0.0 {
+            scaled
+        } else {
+            (scaled + self.next_uniform() - self.next_uniform()).round()
+        };
+        value.clamp(-full_scale, full_scale - 1.0) as i32
+    }
+
+    /// Uniform value in [0, 1) from a xorshift generator
+    fn next_uniform(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f64 / (u32::MAX as f64 + 1.0)
+    }
+}
+
+/// File writer for a recording session, in the format the session asked for
+pub enum RecordingWriter {
+    Wav(WavWriter),
+    Flac(FlacWriter),
+    Opus(OpusWriter),
+}
+
+impl RecordingWriter {
+    /// Create the recording file and write its headers
+    pub fn create(
+        file_path: PathBuf,
+        sample_rate: u32,
+        channels: u16,
+        options: WriterOptions,
+    ) -> io::Result<Self> {
+        match options.format {
+            RecordingFormat::Wav => Ok(Self::Wav(WavWriter::with_options(
+                file_path,
+                sample_rate,
+                channels,
+                options,
+            )?)),
+            RecordingFormat::Flac => Ok(Self::Flac(FlacWriter::with_options(
+                file_path,
+                sample_rate,
+                channels,
+                options,
+            )?)),
+            RecordingFormat::Opus => Ok(Self::Opus(OpusWriter::with_options(
+                file_path,
+                sample_rate,
+                channels,
+                options,
+            )?)),
+        }
+    }
+
+    pub fn write_samples_f32(&mut self, samples: &[f32]) -> io::Result<()> {
+        match self {
+            Self::Wav(w) => w.write_samples_f32(samples),
+            Self::Flac(w) => w.write_samples_f32(samples),
+            Self::Opus(w) => w.write_samples_f32(samples),
+        }
+    }
+
+    pub fn finalize(&mut self) -> io::Result<()> {
+        match self {
+            Self::Wav(w) => w.finalize(),
+            Self::Flac(w) => w.finalize(),
+            Self::Opus(w) => w.finalize(),
+        }
+    }
+
+    /// Sample count, peaks, signal quality and markers of the recording
+    pub fn stats(&self) -> &WriterStats {
+        match self {
+            Self::Wav(w) => w.stats(),
+            Self::Flac(w) => w.stats(),
+            Self::Opus(w) => w.stats(),
+        }
+    }
+
+    fn stats_mut(&mut self) -> &mut WriterStats {
+        match self {
+            Self::Wav(w) => w.stats_mut(),
+            Self::Flac(w) => w.stats_mut(),
+            Self::Opus(w) => w.stats_mut(),
+        }
+    }
+
+    pub fn get_metadata(&self) -> (u32, u16, f32) {
+        self.stats().metadata()
+    }
+
+    /// Add a marker at the current position. WAV files get a cue point and FLAC files
+    /// a CUESHEET track on the next finalize or header update; Opus files don't hold markers.
+    pub fn add_marker(&mut self, label: String) -> io::Result<RecordingMarker> {
+        match self {
+            Self::Flac(w) => w.add_marker(label),
+            Self::Wav(_) | Self::Opus(_) => Ok(self.stats_mut().add_marker(label)),
+        }
+    }
+
+    pub fn get_markers(&self) -> &[RecordingMarker] {
+        self.stats().markers()
+    }
+
+    pub fn get_waveform_peaks(&self) -> WaveformPeaks {
+        self.stats().waveform_peaks()
+    }
+
+    pub fn get_signal_quality(&self) -> SignalQuality {
+        self.stats().signal_quality()
+    }
+
+    /// Handle for reading the recording while it is written. Only WAV files support this.
+    pub fn live_recording(&self) -> Option<LiveRecording> {
+        match self {
+            Self::Wav(w) => Some(w.live_recording()),
+            Self::Flac(_) | Self::Opus(_) => None,
+        }
+    }
+
+    pub fn take_signal_warning(&mut self) -> Option<SignalWarning> {
+        self.stats_mut().take_signal_warning()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_from_integer_devices_pass_through_exactly() {
+        let mut ditherer = Ditherer::new();
+        for value in (-32768..32768).step_by(7).chain([-32768, -1, 0, 1, 32767]) {
+            let sample = value as f32 / 32768.0;
+            assert_eq!(ditherer.quantize(sample, 16), value);
+            assert_eq!(ditherer.quantize(sample, 24), value * 256);
+        }
+    }
+
+    #[test]
+    fn out_of_range_samples_are_clipped() {
+        let mut ditherer = Ditherer::new();
+        // (sample, 16-bit value, 24-bit value)
+        let cases = [
+            (1.0, 32767, 8_388_607),
+            (1.5, 32767, 8_388_607),
+            (f32::INFINITY, 32767, 8_388_607),
+            (-1.0, -32768, -8_388_608),
+            (-3.0, -32768, -8_388_608),
+            (f32::NEG_INFINITY, -32768, -8_388_608),
+        ];
+        for (sample, pcm16, pcm24) in cases {
+            assert_eq!(ditherer.quantize(sample, 16), pcm16, "{}", sample);
+            assert_eq!(ditherer.quantize(sample, 24), pcm24, "{}", sample);
+        }
+    }
+
+    #[test]
+    fn dither_stays_within_one_step_and_averages_to_the_input() {
+        let mut ditherer = Ditherer::new();
+        for lsb in [0.3, -0.3, 0.5, 100.25, -2000.75] {
+            let sample = (lsb / 32768.0) as f32;
+            let exact = sample as f64 * 32768.0;
+            let values: Vec<i32> = (0..20_000).map(|_| ditherer.quantize(sample, 16)).collect();
+            assert!(
+                values.iter().all(|&v| (v as f64 - exact).abs() <= 1.5),
+                "{} LSB",
+                lsb
+            );
+            let mean = values.iter().map(|&v| v as f64).sum::<f64>() / values.len() as f64;
+            assert!((mean - exact).abs() < 0.02, "{} LSB averaged {}", lsb, mean);
+        }
+    }
+
+    #[test]
+    fn dither_is_reproducible() {
+        let quantized = || {
+            let mut ditherer = Ditherer::new();
+            (0..100)
+                .map(|i| ditherer.quantize(i as f32 * 0.001 + 0.0001, 16))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(quantized(), quantized());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::recorder::encryption::RecordingKey;
+use crate::recorder::markers::RecordingMarker;
+use crate::recorder::peaks::{write_peaks_file, PeakAccumulator, WaveformPeaks};
+use crate::recorder::quality::{SignalAnalyzer, SignalQuality, SignalWarning};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Bookkeeping every recording writer keeps next to the audio it encodes:
+/// sample count, waveform peaks, signal quality and markers.
+pub struct WriterStats {
+    file_path: PathBuf,
+    sample_rate: u32,
+    channels: u16,
+    samples_written: u64,
+    encryption_key: Option<RecordingKey>, // Also encrypts the peaks file
+    peaks: PeakAccumulator,
+    peaks_written_at: Option<u64>,
+    signal: SignalAnalyzer,
+    markers: Vec<RecordingMarker>,
+}
+
+impl WriterStats {
+    pub fn new(
+        file_path: PathBuf,
+        sample_rate: u32,
+        channels: u16,
+        encryption_key: Option<RecordingKey>,
+    ) -> Self {
+        Self {
+            file_path,
+            sample_rate,
+            channels,
+            samples_written: 0,
+            encryption_key,
+            peaks: PeakAccumulator::new(sample_rate, channels),
+            peaks_written_at: None,
+            signal: SignalAnalyzer::new(sample_rate, channels),
+            markers: Vec::new(),
+        }
+    }
+
+    /// Feed a written sample to the waveform and signal quality trackers
+    #[inline]
+    pub fn analyze(&mut self, sample: f32) {
+        self.peaks.push(sample);
+        self.signal.push(sample);
+    }
+
+    /// Count samples (across all channels) that reached the writer
+    pub fn add_samples(&mut self, count: usize) {
+        self.samples_written += count as u64;
+    }
+
+    /// Samples written so far, across all channels
+    pub fn samples_written(&self) -> u64 {
+        self.samples_written
+    }
+
+    /// Frames (samples per channel) written so far
+    pub fn frames_written(&self) -> u64 {
+        self.samples_written / self.channels as u64
+    }
+
+    pub fn file_path(&self) -> &Path {
+        &self.file_path
+    }
+
+    /// Get the current duration in seconds
+    pub fn duration_seconds(&self) -> f32 {
+        self.samples_written as f32 / (self.sample_rate as f32 * self.channels as f32)
+    }
+
+    /// Get audio metadata
+    pub fn metadata(&self) -> (u32, u16, f32) {
+        (self.sample_rate, self.channels, self.duration_seconds())
+    }
+
+    /// Persist waveform peaks next to the file (skipped if nothing changed since last time)
+    pub fn write_peaks(&mut self) {
+        if self.peaks_written_at == Some(self.samples_written) {
+            return;
+        }
+        match write_peaks_file(
+            &self.file_path,
+            &self.peaks.peaks(),
+            self.encryption_key.as_ref(),
+        ) {
+            Ok(()) => self.peaks_written_at = Some(self.samples_written),
+            Err(e) => warn!(
+                "Failed to write waveform peaks for {:?}: {}",
+                self.file_path, e
+            ),
+        }
+    }
+
+    /// Add a marker at the current position
+    pub fn add_marker(&mut self, label: String) -> RecordingMarker {
+        let id = self.markers.len() as u32 + 1;
+        let label = if label.trim().is_empty() {
+            format!("Marker {}", id)
+        } else {
+            label
+        };
+        let marker = RecordingMarker {
+            id,
+            label,
+            sample_position: self.frames_written(),
+            time_seconds: self.duration_seconds(),
+        };
+        self.markers.push(marker.clone());
+        marker
+    }
+
+    /// Get all markers added so far
+    pub fn markers(&self) -> &[RecordingMarker] {
+        &self.markers
+    }
+
+    /// Get the min/max waveform peaks accumulated so far
+    pub fn waveform_peaks(&self) -> WaveformPeaks {
+        self.peaks.peaks()
+    }
+
+    /// Get the signal quality statistics accumulated so far
+    pub fn signal_quality(&self) -> SignalQuality {
+        self.signal.quality()
+    }
+
+    /// Take a signal warning raised by the most recent writes, if any
+    pub fn take_signal_warning(&mut self) -> Option<SignalWarning> {
+        self.signal.take_warning()
+    }
+}
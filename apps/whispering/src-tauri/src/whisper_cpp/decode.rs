@@ -0,0 +1,387 @@
+use super::error::WhisperCppError;
+use crate::recorder::opus::decode_ogg_opus;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tracing::debug;
+
+/// Sample rate whisper models expect
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// Decode audio in memory into 16kHz mono f32 samples, without FFmpeg.
+///
+/// Covers WAV (any sample format), FLAC, MP3, Ogg Vorbis, Ogg Opus and AAC in MP4/M4A
+/// or ADTS. Returns None for anything else, which is left to FFmpeg, and an error for a
+/// file in one of these formats that is damaged.
+pub fn decode_for_whisper(audio_data: &[u8]) -> Result<Option<Vec<f32>>, WhisperCppError> {
+    if let Some(samples) = decode_wav(audio_data)? {
+        return Ok(Some(samples));
+    }
+    if let Some(samples) = decode_opus(audio_data) {
+        return Ok(Some(samples));
+    }
+    // symphonia panics on some malformed headers, such as a zero sample rate
+    std::panic::catch_unwind(|| decode_with_symphonia(audio_data)).unwrap_or_else(|_| {
+        Err(WhisperCppError::AudioReadError {
+            message: "Failed to decode audio: malformed header".to_string(),
+        })
+    })
+}
+
+/// Decode a WAV file hound can read.
+///
+/// Recordings are WAV files at the device sample rate, and hound reads them even while
+/// they are still being written, so they take this path.
+fn decode_wav(audio_data: &[u8]) -> Result<Option<Vec<f32>>, WhisperCppError> {
+    let Ok(mut reader) = hound::WavReader::new(std::io::Cursor::new(audio_data)) else {
+        return Ok(None);
+    };
+    let spec = reader.spec();
+    if spec.sample_rate == 0 || spec.channels == 0 {
+        return Err(WhisperCppError::AudioReadError {
+            message: format!(
+                "Invalid WAV file: {} Hz, {} channels",
+                spec.sample_rate, spec.channels
+            ),
+        });
+    }
+
+    let interleaved: Result<Vec<f32>, _> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|sample| sample as f32 / scale))
+                .collect()
+        }
+    };
+    // Formats hound reads the header of but not the samples are left to the other decoders
+    let Ok(interleaved) = interleaved else {
+        return Ok(None);
+    };
+
+    Ok(Some(to_whisper_samples(
+        &interleaved,
+        spec.channels as usize,
+        spec.sample_rate,
+    )))
+}
+
+/// Decode an Ogg Opus file, which symphonia has no decoder for
+fn decode_opus(audio_data: &[u8]) -> Option<Vec<f32>> {
+    // The first page of an Opus stream carries the OpusHead packet
+    let is_opus = audio_data.starts_with(b"OggS")
+        && audio_data
+            .get(..128)
+            .unwrap_or(audio_data)
+            .windows(8)
+            .any(|w| w == b"OpusHead");
+    if !is_opus {
+        return None;
+    }
+
+    match decode_ogg_opus(audio_data) {
+        Ok(decoded) => Some(to_whisper_samples(
+            &decoded.samples,
+            decoded.channels as usize,
+            decoded.sample_rate,
+        )),
+        Err(e) => {
+            debug!("Failed to decode Opus audio: {}", e);
+            None
+        }
+    }
+}
+
+/// Decode any container and codec symphonia is built with. Fails rather than returning
+/// part of the audio when the file turns out to be damaged.
+fn decode_with_symphonia(audio_data: &[u8]) -> Result<Option<Vec<f32>>, WhisperCppError> {
+    let failed = |e: SymphoniaError| WhisperCppError::AudioReadError {
+        message: format!("Failed to decode audio: {}", e),
+    };
+
+    let source = MediaSourceStream::new(
+        Box::new(std::io::Cursor::new(audio_data.to_vec())),
+        Default::default(),
+    );
+    let format_options = FormatOptions {
+        enable_gapless: true, // Trim encoder delay and padding (MP3, AAC)
+        ..Default::default()
+    };
+    let probed = match symphonia::default::get_probe().format(
+        &Hint::new(),
+        source,
+        &format_options,
+        &MetadataOptions::default(),
+    ) {
+        Ok(probed) => probed,
+        Err(e) => {
+            debug!("Unrecognized audio format: {}", e);
+            return Ok(None);
+        }
+    };
+    let mut reader = probed.format;
+
+    let Some(track) = reader
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+    else {
+        return Ok(None);
+    };
+    let track_id = track.id;
+    let mut decoder = match symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+    {
+        Ok(decoder) => decoder,
+        Err(e) => {
+            debug!("Unsupported audio codec: {}", e);
+            return Ok(None);
+        }
+    };
+
+    // Downmix as we go, so long files don't hold every channel in memory
+    let mut mono = Vec::new();
+    let mut sample_rate = track.codec_params.sample_rate;
+    let expected_frames = track.codec_params.n_frames;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    let mut next_ts = None;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            // The stream simply ends, including recordings still being written
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(failed(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        // Readers skip over damaged frames to the next one they can find
+        if let Some(expected) = next_ts.filter(|&ts| packet.ts() > ts) {
+            return Err(WhisperCppError::AudioReadError {
+                message: format!("Failed to decode audio: damaged after frame {}", expected),
+            });
+        }
+        next_ts = Some(packet.ts() + packet.dur());
+
+        let decoded = decoder.decode(&packet).map_err(failed)?;
+        let spec = *decoded.spec();
+        sample_rate.get_or_insert(spec.rate);
+        if buffer
+            .as_ref()
+            .is_none_or(|b| b.capacity() < decoded.capacity())
+        {
+            buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buffer = buffer.as_mut().expect("sample buffer is allocated above");
+        buffer.copy_interleaved_ref(decoded);
+
+        let channels = spec.channels.count().max(1);
+        mono.extend(
+            buffer
+                .samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+
+    // The header knows how long the audio is; a file cut short ends early
+    if let Some(expected) = expected_frames.filter(|&frames| (mono.len() as u64) < frames) {
+        return Err(WhisperCppError::AudioReadError {
+            message: format!(
+                "Failed to decode audio: ends after {} of {} frames",
+                mono.len(),
+                expected
+            ),
+        });
+    }
+
+    match sample_rate {
+        Some(rate) if rate > 0 => Ok(Some(resample(&mono, rate, WHISPER_SAMPLE_RATE))),
+        _ if mono.is_empty() => Ok(Some(mono)),
+        _ => Err(WhisperCppError::AudioReadError {
+            message: "Audio has no sample rate".to_string(),
+        }),
+    }
+}
+
+/// Downmix interleaved samples to mono and resample them to 16kHz
+pub fn to_whisper_samples(interleaved: &[f32], channels: usize, sample_rate: u32) -> Vec<f32> {
+    let channels = channels.max(1);
+    let mono: Vec<f32> = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    resample(&mono, sample_rate, WHISPER_SAMPLE_RATE)
+}
+
+/// Resample mono audio: averages when downsampling (to limit aliasing),
+/// interpolates linearly when upsampling. Plenty for speech recognition.
+fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let out_len = (samples.len() as f64 / ratio).floor() as usize;
+
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let start = pos as usize;
+            if ratio > 1.0 {
+                let end = (((i + 1) as f64 * ratio) as usize).clamp(start + 1, samples.len());
+                samples[start..end].iter().sum::<f32>() / (end - start) as f32
+            } else {
+                let frac = (pos - start as f64) as f32;
+                let a = samples[start];
+                let b = samples.get(start + 1).copied().unwrap_or(a);
+                a + (b - a) * frac
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::flac::FlacEncoder;
+    use std::io::Cursor;
+
+    fn wav(sample_rate: u32, channels: u16, samples: &[i16]) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    /// 16kHz mono FLAC of a noisy tone, long enough for many frames
+    fn flac(frames: usize) -> Vec<u8> {
+        let samples: Vec<i32> = (0..frames)
+            .map(|i| ((i as f64 * 0.05).sin() * 8000.0) as i32 + (i * 7919 % 64) as i32)
+            .collect();
+        let mut encoder = FlacEncoder::new(Cursor::new(Vec::new()), 16000, 1, 16).unwrap();
+        encoder.write_samples(&samples).unwrap();
+        encoder.finish().unwrap().into_inner()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!((a - e).abs() < 1e-5, "sample {}: {} != {}", i, a, e);
+        }
+    }
+
+    #[test]
+    fn resample_keeps_audio_at_the_same_rate() {
+        assert_eq!(resample(&[0.1, 0.2, 0.3], 16000, 16000), [0.1, 0.2, 0.3]);
+        assert!(resample(&[], 48000, 16000).is_empty());
+    }
+
+    #[test]
+    fn resample_averages_when_downsampling() {
+        let ramp: Vec<f32> = (0..12).map(|i| i as f32).collect();
+        assert_close(&resample(&ramp, 48000, 16000), &[1.0, 4.0, 7.0, 10.0]);
+
+        // 44.1kHz doesn't divide evenly; a constant signal must stay constant
+        let constant = vec![0.25; 44100];
+        let resampled = resample(&constant, 44100, 16000);
+        assert_eq!(resampled.len(), 16000);
+        assert!(resampled.iter().all(|&s| (s - 0.25).abs() < 1e-6));
+    }
+
+    #[test]
+    fn resample_interpolates_when_upsampling() {
+        let ramp: Vec<f32> = (0..4).map(|i| i as f32).collect();
+        assert_close(
+            &resample(&ramp, 8000, 16000),
+            &[0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.0],
+        );
+    }
+
+    #[test]
+    fn to_whisper_samples_downmixes_to_mono() {
+        let stereo = [1.0, -1.0, 0.5, 0.5, 0.0, 1.0];
+        assert_close(&to_whisper_samples(&stereo, 2, 16000), &[0.0, 0.5, 0.5]);
+        assert_close(&to_whisper_samples(&[0.1, 0.2], 0, 16000), &[0.1, 0.2]);
+        assert_eq!(
+            to_whisper_samples(&vec![0.0; 4800 * 2], 2, 48000).len(),
+            1600
+        );
+    }
+
+    #[test]
+    fn decodes_wav_at_whisper_rate() {
+        let samples: Vec<i16> = (0..9600).map(|i| (i % 100) as i16 * 100).collect();
+        let decoded = decode_for_whisper(&wav(48000, 2, &samples))
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.len(), 1600);
+    }
+
+    #[test]
+    fn rejects_wav_without_a_sample_rate() {
+        // Sample rate field of the fmt chunk, then the byte rate hound checks it against
+        let mut bytes = wav(16000, 1, &[0; 100]);
+        bytes[24..28].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(
+            decode_for_whisper(&bytes),
+            Err(WhisperCppError::AudioReadError { .. })
+        ));
+
+        bytes[28..32].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(
+            decode_for_whisper(&bytes),
+            Err(WhisperCppError::AudioReadError { .. })
+        ));
+    }
+
+    #[test]
+    fn decodes_flac_with_symphonia() {
+        let decoded = decode_for_whisper(&flac(40_000)).unwrap().unwrap();
+        assert_eq!(decoded.len(), 40_000);
+    }
+
+    #[test]
+    fn fails_on_damaged_audio_instead_of_truncating() {
+        let mut bytes = flac(40_000);
+        let middle = bytes.len() / 2;
+        for byte in &mut bytes[middle..middle + 64] {
+            *byte ^= 0x5A;
+        }
+        assert!(matches!(
+            decode_for_whisper(&bytes),
+            Err(WhisperCppError::AudioReadError { .. })
+        ));
+
+        let bytes = flac(40_000);
+        assert!(matches!(
+            decode_for_whisper(&bytes[..bytes.len() / 2]),
+            Err(WhisperCppError::AudioReadError { .. })
+        ));
+    }
+
+    #[test]
+    fn leaves_unknown_formats_to_ffmpeg() {
+        assert!(decode_for_whisper(b"definitely not audio")
+            .unwrap()
+            .is_none());
+    }
+}
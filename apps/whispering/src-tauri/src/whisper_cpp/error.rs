@@ -7,6 +7,9 @@ pub enum WhisperCppError {
     #[error("{message}")]
     AudioReadError { message: String },
 
+    #[error("{message}")]
+    FfmpegRequired { message: String },
+
     #[error("{message}")]
     ModelLoadError { message: String },
 
@@ -15,4 +18,10 @@ pub enum WhisperCppError {
 
     #[error("{message}")]
     TranscriptionError { message: String },
-}
\ No newline at end of file
+
+    #[error("{message}")]
+    Cancelled { message: String },
+
+    #[error("{message}")]
+    StreamingUnsupported { message: String },
+}
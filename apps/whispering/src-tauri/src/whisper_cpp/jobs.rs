@@ -0,0 +1,173 @@
+use super::error::WhisperCppError;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter};
+use tracing::{info, warn};
+
+/// Event carrying transcription progress
+pub const TRANSCRIPTION_PROGRESS_EVENT: &str = "whisper-transcription-progress";
+
+/// Identifies a transcription so it can be followed and cancelled
+pub type TranscriptionJobId = u32;
+
+/// How far a transcription has come - emitted to frontend
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionProgress {
+    pub job_id: TranscriptionJobId,
+    pub percent: i32,
+}
+
+/// Transcriptions that can still be cancelled.
+///
+/// The frontend asks for a job id before it starts a transcription and passes it along,
+/// so it can cancel the transcription and match progress events to it while the
+/// transcription command is still running.
+#[derive(Default)]
+pub struct TranscriptionJobs {
+    next_id: AtomicU32,
+    cancel_flags: Mutex<HashMap<TranscriptionJobId, Arc<AtomicBool>>>,
+}
+
+impl TranscriptionJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserve an id for a transcription about to start
+    pub fn create(&self) -> Result<TranscriptionJobId, WhisperCppError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.lock()?.insert(id, Arc::new(AtomicBool::new(false)));
+        Ok(id)
+    }
+
+    /// Handle for the transcription running as `id`
+    pub fn job(
+        &self,
+        id: TranscriptionJobId,
+        app_handle: AppHandle,
+    ) -> Result<TranscriptionJob, WhisperCppError> {
+        let cancelled =
+            self.lock()?
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| WhisperCppError::TranscriptionError {
+                    message: format!("Unknown transcription job {}", id),
+                })?;
+        Ok(TranscriptionJob {
+            id,
+            cancelled,
+            app_handle,
+        })
+    }
+
+    /// Ask a transcription to stop, returns whether it was known
+    pub fn cancel(&self, id: TranscriptionJobId) -> Result<bool, WhisperCppError> {
+        let jobs = self.lock()?;
+        let Some(cancelled) = jobs.get(&id) else {
+            return Ok(false);
+        };
+        cancelled.store(true, Ordering::Release);
+        info!("Cancelling transcription job {}", id);
+        Ok(true)
+    }
+
+    /// Forget a transcription that ended
+    pub fn finish(&self, id: TranscriptionJobId) {
+        if let Ok(mut jobs) = self.lock() {
+            jobs.remove(&id);
+        }
+    }
+
+    /// Forget the transcription, if any, once the returned guard is dropped, however
+    /// the transcription ends
+    pub fn finish_on_drop(&self, id: Option<TranscriptionJobId>) -> FinishJobGuard<'_> {
+        FinishJobGuard { jobs: self, id }
+    }
+
+    fn lock(
+        &self,
+    ) -> Result<MutexGuard<'_, HashMap<TranscriptionJobId, Arc<AtomicBool>>>, WhisperCppError> {
+        self.cancel_flags
+            .lock()
+            .map_err(|_| WhisperCppError::TranscriptionError {
+                message: "Transcription jobs lock poisoned".to_string(),
+            })
+    }
+}
+
+/// Finishes a transcription job when dropped
+pub struct FinishJobGuard<'a> {
+    jobs: &'a TranscriptionJobs,
+    id: Option<TranscriptionJobId>,
+}
+
+impl Drop for FinishJobGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.jobs.finish(id);
+        }
+    }
+}
+
+/// A running transcription's link to the frontend
+#[derive(Clone)]
+pub struct TranscriptionJob {
+    id: TranscriptionJobId,
+    cancelled: Arc<AtomicBool>,
+    app_handle: AppHandle,
+}
+
+impl TranscriptionJob {
+    pub fn id(&self) -> TranscriptionJobId {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Fail with a cancellation error if the job was cancelled
+    pub fn check_cancelled(&self) -> Result<(), WhisperCppError> {
+        if self.is_cancelled() {
+            return Err(WhisperCppError::Cancelled {
+                message: "Transcription cancelled".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn report_progress(&self, percent: i32) {
+        let progress = TranscriptionProgress {
+            job_id: self.id,
+            percent: percent.clamp(0, 100),
+        };
+        if let Err(e) = self.app_handle.emit(TRANSCRIPTION_PROGRESS_EVENT, progress) {
+            warn!("Failed to emit transcription progress: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_finishes_job_on_every_exit() {
+        let jobs = TranscriptionJobs::new();
+        let id = jobs.create().unwrap();
+
+        let failed = || -> Result<(), WhisperCppError> {
+            let _finish_job = jobs.finish_on_drop(Some(id));
+            Err(WhisperCppError::AudioReadError {
+                message: "Failed to decrypt recording".to_string(),
+            })
+        };
+        assert!(failed().is_err());
+
+        assert!(!jobs.cancel(id).unwrap());
+        assert!(jobs.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+use super::decode::WHISPER_SAMPLE_RATE;
+use super::error::WhisperCppError;
+use serde::Serialize;
+use whisper_rs::{WhisperContext, WhisperState};
+
+/// Audio listened to unless the caller asks for more or less, whisper's whole window
+pub const DEFAULT_DETECTION_SECONDS: f32 = 30.0;
+
+/// How likely the speech is in one language - returned to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageProbability {
+    pub code: String, // As accepted by the `language` transcription argument
+    pub name: String,
+    pub probability: f32,
+}
+
+/// Identify the spoken language from the first `seconds` of 16kHz mono samples.
+/// Returns every language the model knows, most likely first.
+pub fn detect_language(
+    context: &WhisperContext,
+    state: &mut WhisperState,
+    samples: &[f32],
+    seconds: f32,
+    threads: usize,
+) -> Result<Vec<LanguageProbability>, WhisperCppError> {
+    if !context.is_multilingual() {
+        return Err(WhisperCppError::TranscriptionError {
+            message: "English-only models can't detect the language, use a multilingual model"
+                .to_string(),
+        });
+    }
+
+    let len = (seconds.max(1.0) * WHISPER_SAMPLE_RATE as f32) as usize;
+    let samples = &samples[..samples.len().min(len)];
+
+    state
+        .pcm_to_mel(samples, threads)
+        .map_err(|e| WhisperCppError::TranscriptionError {
+            message: format!("Failed to compute spectrogram: {}", e),
+        })?;
+    let (_, probabilities) =
+        state
+            .lang_detect(0, threads)
+            .map_err(|e| WhisperCppError::TranscriptionError {
+                message: format!("Failed to detect language: {}", e),
+            })?;
+
+    let mut languages: Vec<LanguageProbability> = probabilities
+        .into_iter()
+        .enumerate()
+        .filter_map(|(id, probability)| {
+            let id = i32::try_from(id).ok()?;
+            Some(LanguageProbability {
+                code: whisper_rs::get_lang_str(id)?.to_string(),
+                name: whisper_rs::get_lang_str_full(id)?.to_string(),
+                probability,
+            })
+        })
+        .collect();
+    languages.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+    Ok(languages)
+}
@@ -1,186 +1,579 @@
+mod decode;
 mod error;
+mod jobs;
+mod language;
+mod model_cache;
+mod options;
+mod streaming;
+mod subtitles;
+mod transcript;
 
+use crate::recorder::commands::{resolve_recording_id, resolve_recording_path, save_file, AppData};
+use crate::recorder::scope::DEFAULT_RECORDINGS_DIR;
+use decode::decode_for_whisper;
 use error::WhisperCppError;
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+pub use jobs::{
+    TranscriptionJob, TranscriptionJobId, TranscriptionJobs, TranscriptionProgress,
+    TRANSCRIPTION_PROGRESS_EVENT,
+};
+pub use model_cache::{
+    spawn_model_eviction_task, LoadedModel, ModelCache, ModelCacheSettings, ModelOptions,
+};
+pub use streaming::{
+    StreamingOptions, StreamingTranscriptKind, StreamingTranscriptUpdate, StreamingTranscription,
+    STREAMING_TRANSCRIPT_EVENT,
+};
+pub use options::TranscriptionOptions;
+use options::WhisperRequest;
+use streaming::StreamingRequest;
+pub use subtitles::{render_transcript, SubtitleOptions, TranscriptFormat};
+pub use language::LanguageProbability;
+use language::DEFAULT_DETECTION_SECONDS;
+pub use transcript::{Transcript, TranscriptSegment, TranscriptWord, TranscriptionText};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use tracing::info;
+use whisper_rs::{WhisperContext, WhisperState};
 use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
 
-/// Check if audio is already in whisper-compatible format (16kHz, mono, 16-bit PCM)
-fn is_valid_wav_format(audio_data: &[u8]) -> bool {
-    let cursor = std::io::Cursor::new(audio_data);
-    
-    if let Ok(reader) = hound::WavReader::new(cursor) {
-        let spec = reader.spec();
-        spec.sample_format == hound::SampleFormat::Int &&
-        spec.channels == 1 &&          // Must be mono
-        spec.sample_rate == 16000 &&   // Must be 16kHz
-        spec.bits_per_sample == 16     // Must be 16-bit
-    } else {
-        false
-    }
-}
-
-/// Convert audio to whisper-compatible format (16kHz mono PCM WAV) using FFmpeg
-/// 
-/// Whisper models require audio in a specific format:
-/// - Sample rate: 16,000 Hz (not the typical 44.1kHz or 48kHz)
-/// - Channels: Mono (1 channel)  
-/// - Format: 16-bit PCM WAV
-/// 
-/// This function:
-/// 1. Checks if audio is already in the correct format
-/// 2. If not, uses FFmpeg to convert from any format (MP3, M4A, etc.) to the required format
-/// 3. Returns the audio ready for whisper transcription
-fn convert_audio_for_whisper(audio_data: Vec<u8>) -> Result<Vec<u8>, WhisperCppError> {
-    // Skip conversion if already in correct format
-    if is_valid_wav_format(&audio_data) {
-        return Ok(audio_data);
-    }
-    
-    // Create temp files for conversion
-    let mut input_file = tempfile::Builder::new()
-        .suffix(".audio")
-        .tempfile()
-        .map_err(|e| WhisperCppError::AudioReadError {
-            message: format!("Failed to create temp file: {}", e),
-        })?;
-    
-    input_file.write_all(&audio_data).map_err(|e| {
-        WhisperCppError::AudioReadError {
-            message: format!("Failed to write audio data: {}", e),
-        }
-    })?;
-    
-    let output_file = tempfile::Builder::new()
-        .suffix(".wav")
-        .tempfile()
-        .map_err(|e| WhisperCppError::AudioReadError {
-            message: format!("Failed to create output file: {}", e),
-        })?;
-    
-    // Use FFmpeg to convert to whisper-compatible format
-    let output = std::process::Command::new("ffmpeg")
-        .args(&[
-            "-i", &input_file.path().to_string_lossy(),
-            "-ar", "16000",        // 16kHz sample rate
-            "-ac", "1",            // Mono
-            "-c:a", "pcm_s16le",   // 16-bit PCM
-            "-y",                  // Overwrite output
-            &output_file.path().to_string_lossy(),
+/// Convert audio to whisper's format (16kHz mono) using FFmpeg.
+/// Only used for formats the built-in decoders don't cover, so FFmpeg is optional.
+///
+/// The audio is piped through FFmpeg's stdin and stdout rather than temp files, so
+/// decrypted recordings never touch the disk.
+fn convert_audio_for_whisper(audio_data: Vec<u8>) -> Result<Vec<f32>, WhisperCppError> {
+    let mut child = std::process::Command::new("ffmpeg")
+        .args([
+            "-i", "pipe:0",
+            "-ar", "16000",      // 16kHz sample rate
+            "-ac", "1",          // Mono
+            "-f", "s16le",       // Raw 16-bit PCM, no header to patch on a pipe
+            "pipe:1",
         ])
-        .output()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                WhisperCppError::FfmpegRequired {
+                    message: "Unsupported audio format. Install FFmpeg to transcribe this file."
+                        .to_string(),
+                }
+            } else {
+                WhisperCppError::AudioReadError {
+                    message: format!("Failed to run ffmpeg: {}", e),
+                }
+            }
+        })?;
+
+    // Feed the input from another thread so a full stdout pipe can't deadlock FFmpeg
+    let mut stdin = child.stdin.take().expect("ffmpeg stdin is piped");
+    let feeder = std::thread::spawn(move || stdin.write_all(&audio_data));
+
+    let output = child
+        .wait_with_output()
         .map_err(|e| WhisperCppError::AudioReadError {
             message: format!("Failed to run ffmpeg: {}", e),
         })?;
-    
+    // FFmpeg may stop reading early on invalid input, its exit status tells why
+    let _ = feeder.join();
+
     if !output.status.success() {
         return Err(WhisperCppError::AudioReadError {
             message: format!("FFmpeg conversion failed: {}", String::from_utf8_lossy(&output.stderr)),
         });
     }
-    
-    std::fs::read(output_file.path()).map_err(|e| {
-        WhisperCppError::AudioReadError {
-            message: format!("Failed to read converted audio: {}", e),
-        }
-    })
+
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+        .collect())
 }
 
-/// Load Whisper model with automatic GPU support based on compiled features
-fn load_whisper_model(model_path: &str) -> Result<WhisperContext, WhisperCppError> {
-    // GPU acceleration is automatically enabled based on compile-time features:
-    // - macOS: Metal + CoreML
-    // - Windows: CUDA + Vulkan  
-    // - Linux: CUDA + Vulkan + HipBLAS
-    // The whisper-rs library automatically selects the best available backend
-    
-    WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
-        .map_err(|e| WhisperCppError::ModelLoadError {
-            message: format!("Failed to load model: {}", e)
-        })
+/// Load a model into the cache ahead of time, so the next transcription starts immediately
+#[tauri::command]
+pub async fn preload_model(
+    model_path: String,
+    model_options: Option<ModelOptions>,
+    model_cache: State<'_, ModelCache>,
+) -> Result<(), WhisperCppError> {
+    model_cache.get(&model_path, &model_options.unwrap_or_default())?;
+    Ok(())
 }
 
+/// Unload a cached model (or every model when no path is given) to free its memory
 #[tauri::command]
+pub async fn unload_model(
+    model_path: Option<String>,
+    model_cache: State<'_, ModelCache>,
+) -> Result<bool, WhisperCppError> {
+    match model_path {
+        Some(model_path) => model_cache.unload(&model_path),
+        None => model_cache.unload_all().map(|()| true),
+    }
+}
+
+/// Models currently held by the cache
+#[tauri::command]
+pub async fn get_loaded_models(
+    model_cache: State<'_, ModelCache>,
+) -> Result<Vec<LoadedModel>, WhisperCppError> {
+    model_cache.loaded()
+}
+
+/// Get the idle timeout and memory budget of the model cache
+#[tauri::command]
+pub async fn get_model_cache_settings(
+    model_cache: State<'_, ModelCache>,
+) -> Result<ModelCacheSettings, WhisperCppError> {
+    Ok(model_cache.settings())
+}
+
+/// Change the idle timeout and memory budget of the model cache
+#[tauri::command]
+pub async fn set_model_cache_settings(
+    settings: ModelCacheSettings,
+    model_cache: State<'_, ModelCache>,
+) -> Result<(), WhisperCppError> {
+    model_cache.set_settings(settings)
+}
+
+/// Transcribe audio in any supported format. Returns the text along with the language
+/// it was transcribed as, detected unless one was given.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn transcribe_with_whisper_cpp(
     audio_data: Vec<u8>,
     model_path: String,
+    model_options: Option<ModelOptions>,
     language: Option<String>,
     prompt: String,
     temperature: f32,
-) -> Result<String, WhisperCppError> {
-    // Convert audio to 16kHz mono format that whisper requires
-    let wav_data = convert_audio_for_whisper(audio_data)?;
-    
-    // Parse WAV and extract samples
-    let cursor = std::io::Cursor::new(wav_data);
-    let mut reader = hound::WavReader::new(cursor).map_err(|e| {
-        WhisperCppError::AudioReadError {
-            message: format!("Failed to parse WAV: {}", e),
+    options: Option<TranscriptionOptions>,
+    job_id: Option<TranscriptionJobId>,
+    app_data: State<'_, AppData>,
+    model_cache: State<'_, ModelCache>,
+    jobs: State<'_, TranscriptionJobs>,
+    app_handle: AppHandle,
+) -> Result<TranscriptionText, WhisperCppError> {
+    transcribe_source(
+        TranscriptionAudio::Bytes(audio_data),
+        TranscriptTiming::TextOnly,
+        model_path,
+        model_options,
+        language,
+        prompt,
+        temperature,
+        options,
+        job_id,
+        &app_data,
+        &model_cache,
+        &jobs,
+        app_handle,
+    )
+    .map(TranscriptionText::from)
+}
+
+/// Like `transcribe_with_whisper_cpp`, but keeps the timing: returns the segments with
+/// their start and end times and probabilities, and the language that was used. With the
+/// `wordTimestamps` option, each segment also lists its words with their own timing.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn transcribe_segments_with_whisper_cpp(
+    audio_data: Vec<u8>,
+    model_path: String,
+    model_options: Option<ModelOptions>,
+    language: Option<String>,
+    prompt: String,
+    temperature: f32,
+    options: Option<TranscriptionOptions>,
+    job_id: Option<TranscriptionJobId>,
+    app_data: State<'_, AppData>,
+    model_cache: State<'_, ModelCache>,
+    jobs: State<'_, TranscriptionJobs>,
+    app_handle: AppHandle,
+) -> Result<Transcript, WhisperCppError> {
+    transcribe_source(
+        TranscriptionAudio::Bytes(audio_data),
+        TranscriptTiming::Segments,
+        model_path,
+        model_options,
+        language,
+        prompt,
+        temperature,
+        options,
+        job_id,
+        &app_data,
+        &model_cache,
+        &jobs,
+        app_handle,
+    )
+}
+
+/// Transcribe a recording the recorder wrote to disk, given its file path or its id in
+/// an output folder (the default recordings folder when none is given). The file is
+/// read directly instead of being sent over IPC, and must lie inside the allowed
+/// recordings locations. Returns the text and its language, like
+/// `transcribe_with_whisper_cpp`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn transcribe_recording_with_whisper_cpp(
+    file_path: Option<String>,
+    recording_id: Option<String>,
+    output_folder: Option<String>,
+    model_path: String,
+    model_options: Option<ModelOptions>,
+    language: Option<String>,
+    prompt: String,
+    temperature: f32,
+    options: Option<TranscriptionOptions>,
+    job_id: Option<TranscriptionJobId>,
+    app_data: State<'_, AppData>,
+    model_cache: State<'_, ModelCache>,
+    jobs: State<'_, TranscriptionJobs>,
+    app_handle: AppHandle,
+) -> Result<TranscriptionText, WhisperCppError> {
+    let path = match (file_path, recording_id) {
+        (Some(file_path), None) => resolve_recording_path(&app_data, &file_path),
+        (None, Some(recording_id)) => {
+            let output_folder = match output_folder {
+                Some(folder) => PathBuf::from(folder),
+                None => app_handle
+                    .path()
+                    .app_data_dir()
+                    .map(|dir| dir.join(DEFAULT_RECORDINGS_DIR))
+                    .map_err(|e| WhisperCppError::AudioReadError {
+                        message: format!("Failed to resolve recordings folder: {}", e),
+                    })?,
+            };
+            resolve_recording_id(&app_data, &output_folder, &recording_id)
         }
+        _ => Err("Specify either a recording file path or a recording id"
+            .to_string()
+            .into()),
+    }
+    .map_err(|e| WhisperCppError::AudioReadError {
+        message: e.to_string(),
     })?;
-    
-    let samples: Vec<f32> = reader
-        .samples::<i16>()
-        .map(|s| s.map(|sample| sample as f32 / 32768.0))
-        .collect::<Result<Vec<_>, _>>()
+
+    transcribe_source(
+        TranscriptionAudio::Recording(path),
+        TranscriptTiming::TextOnly,
+        model_path,
+        model_options,
+        language,
+        prompt,
+        temperature,
+        options,
+        job_id,
+        &app_data,
+        &model_cache,
+        &jobs,
+        app_handle,
+    )
+    .map(TranscriptionText::from)
+}
+
+/// Identify the language spoken in the first `seconds` of the audio (30 by default)
+/// without transcribing it. Returns every language the model knows with its
+/// probability, most likely first.
+#[tauri::command]
+pub async fn detect_language(
+    audio_data: Vec<u8>,
+    model_path: String,
+    model_options: Option<ModelOptions>,
+    seconds: Option<f32>,
+    app_data: State<'_, AppData>,
+    model_cache: State<'_, ModelCache>,
+) -> Result<Vec<LanguageProbability>, WhisperCppError> {
+    // Recordings may be encrypted at rest, decrypt them in memory only
+    let audio_data = app_data
+        .recording_keys
+        .decrypt_bytes(audio_data)
         .map_err(|e| WhisperCppError::AudioReadError {
-            message: format!("Failed to read samples: {}", e),
+            message: format!("Failed to decrypt recording: {}", e),
+        })?;
+
+    let samples = decode_audio(audio_data)?;
+    if samples.is_empty() {
+        return Err(WhisperCppError::AudioReadError {
+            message: "No audio to detect the language of".to_string(),
+        });
+    }
+
+    let context = model_cache.get(&model_path, &model_options.unwrap_or_default())?;
+    let mut state = context
+        .create_state()
+        .map_err(|e| WhisperCppError::TranscriptionError {
+            message: format!("Failed to create whisper state: {}", e),
         })?;
+
+    // Same thread count whisper.cpp uses for transcription by default
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get().min(4))
+        .unwrap_or(1);
+    language::detect_language(
+        &context,
+        &mut state,
+        &samples,
+        seconds.unwrap_or(DEFAULT_DETECTION_SECONDS),
+        threads,
+    )
+}
+
+/// Transcribe the recording in progress as it is recorded, emitting partial and final
+/// text as `whisper-streaming-transcript` events. The stream finalizes its text and
+/// ends by itself when the recording stops. Returns the id of the recording.
+///
+/// Only WAV recordings can be read while they are written, so a session recording FLAC
+/// or Opus fails with `StreamingUnsupported`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn start_streaming_transcription(
+    model_path: String,
+    model_options: Option<ModelOptions>,
+    language: Option<String>,
+    prompt: String,
+    temperature: f32,
+    options: Option<StreamingOptions>,
+    transcription_options: Option<TranscriptionOptions>,
+    streaming: State<'_, StreamingTranscription>,
+    app_handle: AppHandle,
+) -> Result<String, WhisperCppError> {
+    streaming.start(
+        app_handle,
+        StreamingRequest {
+            model_path,
+            model_options: model_options.unwrap_or_default(),
+            whisper: WhisperRequest {
+                language,
+                prompt,
+                temperature,
+                timestamps: true,
+                options: transcription_options.unwrap_or_default(),
+            },
+            options: options.unwrap_or_default(),
+        },
+    )
+}
+
+/// End the streaming transcription early, finalizing the text heard so far.
+/// Returns the id of the recording it was transcribing, if one was running.
+#[tauri::command]
+pub async fn stop_streaming_transcription(
+    streaming: State<'_, StreamingTranscription>,
+) -> Result<Option<String>, WhisperCppError> {
+    streaming.stop()
+}
+
+/// Save a timestamped transcript as subtitles (SRT, WebVTT), plain text or whisper.cpp
+/// JSON where the user picks. Segments are cut into cues following `options`.
+/// Returns the exported path, or None if the dialog was cancelled.
+#[tauri::command]
+pub async fn export_transcript(
+    transcript: Transcript,
+    format: TranscriptFormat,
+    options: Option<SubtitleOptions>,
+    file_name: Option<String>,
+    app_handle: AppHandle,
+) -> Result<Option<String>, String> {
+    let extension = format.extension();
+    let dialog = app_handle
+        .dialog()
+        .file()
+        .set_title("Export Transcript")
+        .set_file_name(format!(
+            "{}.{}",
+            file_name.as_deref().unwrap_or("transcript"),
+            extension
+        ))
+        .add_filter(format.description(), &[extension]);
+    let Some(picked) = save_file(dialog).await else {
+        return Ok(None);
+    };
+    let destination = picked
+        .into_path()
+        .map_err(|e| format!("Invalid export location: {}", e))?;
+
+    let contents = render_transcript(&transcript, format, &options.unwrap_or_default());
+    std::fs::write(&destination, contents)
+        .map_err(|e| format!("Failed to export transcript: {}", e))?;
+
+    info!("Exported transcript to {:?}", destination);
+    Ok(Some(destination.to_string_lossy().to_string()))
+}
+
+/// Reserve a job id for a transcription, to pass to it so its progress can be followed
+/// (`whisper-transcription-progress` events) and it can be cancelled
+#[tauri::command]
+pub async fn create_transcription_job(
+    jobs: State<'_, TranscriptionJobs>,
+) -> Result<TranscriptionJobId, WhisperCppError> {
+    jobs.create()
+}
+
+/// Abort a running transcription, which then fails with a `Cancelled` error.
+/// Returns false if no such transcription is running.
+#[tauri::command]
+pub async fn cancel_transcription(
+    job_id: TranscriptionJobId,
+    jobs: State<'_, TranscriptionJobs>,
+) -> Result<bool, WhisperCppError> {
+    jobs.cancel(job_id)
+}
+
+/// Audio handed to a transcription command
+enum TranscriptionAudio {
+    /// File contents sent by the frontend, possibly an encrypted recording
+    Bytes(Vec<u8>),
+    /// A recording file already resolved inside the allowed recordings locations
+    Recording(PathBuf),
+}
+
+/// What a transcription command returns of the segments whisper produces
+#[derive(Clone, Copy)]
+enum TranscriptTiming {
+    /// Only the text, so whisper can skip predicting timestamps
+    TextOnly,
+    /// The segments with their start and end times
+    Segments,
+}
+
+/// Read the audio, decrypting recordings in memory, and transcribe it as the given job,
+/// if any, forgetting the job once it ends
+#[allow(clippy::too_many_arguments)]
+fn transcribe_source(
+    source: TranscriptionAudio,
+    timing: TranscriptTiming,
+    model_path: String,
+    model_options: Option<ModelOptions>,
+    language: Option<String>,
+    prompt: String,
+    temperature: f32,
+    options: Option<TranscriptionOptions>,
+    job_id: Option<TranscriptionJobId>,
+    app_data: &AppData,
+    model_cache: &ModelCache,
+    jobs: &TranscriptionJobs,
+    app_handle: AppHandle,
+) -> Result<Transcript, WhisperCppError> {
+    // Forget the job however this returns, decrypting or reading the audio can fail too
+    let _finish_job = jobs.finish_on_drop(job_id);
+
+    // Recordings may be encrypted at rest, decrypt them in memory only
+    let audio_data = match source {
+        TranscriptionAudio::Bytes(audio_data) => app_data
+            .recording_keys
+            .decrypt_bytes(audio_data)
+            .map_err(|e| WhisperCppError::AudioReadError {
+                message: format!("Failed to decrypt recording: {}", e),
+            })?,
+        TranscriptionAudio::Recording(path) => app_data
+            .recording_keys
+            .read_recording(&path)
+            .map_err(|e| WhisperCppError::AudioReadError {
+                message: format!("Failed to read recording: {}", e),
+            })?,
+    };
+
+    let job = job_id.map(|id| jobs.job(id, app_handle)).transpose()?;
+    let request = WhisperRequest {
+        language,
+        prompt,
+        temperature,
+        timestamps: matches!(timing, TranscriptTiming::Segments),
+        options: options.unwrap_or_default(),
+    };
+    transcribe_audio(
+        audio_data,
+        &model_path,
+        &model_options.unwrap_or_default(),
+        &request,
+        job.as_ref(),
+        model_cache,
+    )
+}
+
+/// Decode audio in any supported format and transcribe it
+fn transcribe_audio(
+    audio_data: Vec<u8>,
+    model_path: &str,
+    model_options: &ModelOptions,
+    request: &WhisperRequest,
+    job: Option<&TranscriptionJob>,
+    model_cache: &ModelCache,
+) -> Result<Transcript, WhisperCppError> {
+    let samples = decode_audio(audio_data)?;
     
     // Return early if audio is empty
     if samples.is_empty() {
-        return Ok(String::new());
+        return Ok(Transcript::default());
     }
     
-    // Load model with automatic GPU acceleration based on compiled features
-    let context = load_whisper_model(&model_path)?;
+    // Reuse the loaded model when possible, loading it takes seconds for larger models
+    let context = model_cache.get(model_path, model_options)?;
     
-    // Create state and configure parameters
+    // Create state and configure parameters. Each transcription has its own state, so
+    // cancelling one leaves the cached model ready for the next.
     let mut state = context
         .create_state()
         .map_err(|e| WhisperCppError::TranscriptionError {
             message: format!("Failed to create whisper state: {}", e),
         })?;
     
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    params.set_translate(false);
-    params.set_no_timestamps(true);
-    params.set_temperature(temperature);
-    params.set_no_speech_thold(0.2);  // Better silence detection
-    params.set_suppress_nst(true);  // Prevent hallucinations (non-speech tokens)
-    
-    // Set language if specified
-    if let Some(ref lang) = language {
-        if !lang.is_empty() && lang != "auto" {
-            params.set_language(Some(lang));
-        }
+    run_whisper(&context, &mut state, &samples, request, job)
+}
+
+/// Decode audio in any supported format into 16kHz mono samples
+fn decode_audio(audio_data: Vec<u8>) -> Result<Vec<f32>, WhisperCppError> {
+    // Common formats are decoded in memory, FFmpeg (when installed) handles the rest
+    if let Some(samples) = decode_for_whisper(&audio_data)? {
+        return Ok(samples);
     }
-    
-    // Set initial prompt if provided
-    if !prompt.trim().is_empty() {
-        params.set_initial_prompt(&prompt);
+
+    // Convert audio to 16kHz mono format that whisper requires
+    convert_audio_for_whisper(audio_data)
+}
+
+/// Run whisper over 16kHz mono samples, reusing a state of the model's context.
+/// A job gets progress reports and can abort the run.
+fn run_whisper(
+    context: &WhisperContext,
+    state: &mut WhisperState,
+    samples: &[f32],
+    request: &WhisperRequest,
+    job: Option<&TranscriptionJob>,
+) -> Result<Transcript, WhisperCppError> {
+    let mut params = request.full_params();
+
+    if let Some(job) = job {
+        // Cancelled while the audio was decoded or the model loaded
+        job.check_cancelled()?;
+
+        let progress_job = job.clone();
+        params.set_progress_callback_safe(move |percent: i32| {
+            progress_job.report_progress(percent)
+        });
+
+        // whisper-rs casts the callback data back to the closure's own type, which only
+        // matches what it stored when the closure is already a boxed trait object
+        let abort_job = job.clone();
+        let should_abort: Box<dyn FnMut() -> bool> = Box::new(move || abort_job.is_cancelled());
+        params.set_abort_callback_safe(should_abort);
     }
-    
+
     // Run transcription
-    state.full(params, &samples)
-        .map_err(|e| WhisperCppError::TranscriptionError {
-            message: e.to_string(),
-        })?;
-    
-    // Collect transcribed text from all segments
-    let num_segments = state.full_n_segments();
-    
-    let mut text = String::new();
-    for i in 0..num_segments {
-        let segment = state.get_segment(i)
-            .ok_or_else(|| WhisperCppError::TranscriptionError {
-                message: format!("Failed to get segment {}", i),
-            })?;
-        let segment_text = segment.to_str()
-            .map_err(|e| WhisperCppError::TranscriptionError {
-                message: format!("Failed to get segment {} text: {}", i, e),
-            })?;
-        text.push_str(segment_text);
+    let result = state.full(params, samples);
+    if let Some(job) = job {
+        job.check_cancelled()?;
     }
+    result.map_err(|e| WhisperCppError::TranscriptionError {
+        message: e.to_string(),
+    })?;
     
-    Ok(text.trim().to_string())
+    Transcript::from_state(context, state, request.options.word_timestamps)
 }
@@ -0,0 +1,462 @@
+use super::error::WhisperCppError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{debug, info, warn};
+use whisper_rs::{WhisperContext, WhisperContextParameters};
+
+/// How long a model may sit unused before it is unloaded
+const DEFAULT_IDLE_TIMEOUT_SECONDS: u64 = 10 * 60;
+
+/// Total size of loaded models before the least recently used ones are unloaded
+const DEFAULT_MEMORY_BUDGET_MB: u64 = 4096;
+
+/// How often idle models are looked for
+const EVICTION_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Context parameters a model is loaded with. Models loaded with different
+/// parameters are cached separately.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelOptions {
+    pub use_gpu: Option<bool>, // None uses the whisper.cpp default (enabled)
+    pub flash_attn: Option<bool>,
+    pub gpu_device: Option<i32>,
+}
+
+impl ModelOptions {
+    fn context_parameters(&self) -> WhisperContextParameters<'static> {
+        let mut params = WhisperContextParameters::default();
+        if let Some(use_gpu) = self.use_gpu {
+            params.use_gpu = use_gpu;
+        }
+        if let Some(flash_attn) = self.flash_attn {
+            params.flash_attn = flash_attn;
+        }
+        if let Some(gpu_device) = self.gpu_device {
+            params.gpu_device = gpu_device;
+        }
+        params
+    }
+}
+
+/// Limits of the model cache - exchanged with frontend
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelCacheSettings {
+    pub idle_timeout_seconds: u64, // 0 keeps models loaded until unloaded or evicted
+    pub memory_budget_mb: u64,
+}
+
+impl ModelCacheSettings {
+    fn memory_budget_bytes(&self) -> u64 {
+        self.memory_budget_mb.saturating_mul(1024 * 1024)
+    }
+}
+
+impl Default for ModelCacheSettings {
+    fn default() -> Self {
+        Self {
+            idle_timeout_seconds: DEFAULT_IDLE_TIMEOUT_SECONDS,
+            memory_budget_mb: DEFAULT_MEMORY_BUDGET_MB,
+        }
+    }
+}
+
+/// A model held by the cache - returned to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedModel {
+    pub model_path: String,
+    pub size_bytes: u64,
+    pub idle_seconds: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ModelKey {
+    path: PathBuf,
+    options: ModelOptions,
+    file: FileStamp,
+}
+
+/// Size and modification time of a model file, so a model replaced in place (e.g. a
+/// re-download) is loaded again instead of served from the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+struct CachedModel {
+    context: Arc<WhisperContext>,
+    size_bytes: u64, // Model file size, close to what loading it allocates
+    last_used: Instant,
+}
+
+/// Whisper models kept loaded between transcriptions.
+///
+/// Loading a medium or large model takes seconds and gigabytes, so models stay loaded
+/// until they sit idle past the timeout, get unloaded explicitly, or have to make room
+/// for another model within the memory budget. A transcription holds its own reference,
+/// so evicting a model that is in use frees it once that transcription finishes.
+pub struct ModelCache {
+    models: Mutex<HashMap<ModelKey, CachedModel>>,
+    load_lock: Mutex<()>, // One load at a time, so the same model isn't loaded twice
+    settings: Mutex<ModelCacheSettings>,
+}
+
+impl ModelCache {
+    pub fn new() -> Self {
+        Self {
+            models: Mutex::new(HashMap::new()),
+            load_lock: Mutex::new(()),
+            settings: Mutex::new(ModelCacheSettings::default()),
+        }
+    }
+
+    /// Get a loaded model, loading it first if it isn't cached
+    pub fn get(
+        &self,
+        model_path: &str,
+        options: &ModelOptions,
+    ) -> Result<Arc<WhisperContext>, WhisperCppError> {
+        let key = model_key(model_path, options)?;
+        if let Some(context) = self.touch(&key)? {
+            return Ok(context);
+        }
+
+        let _loading = self.load_lock.lock().map_err(|_| lock_error())?;
+        // Another caller may have loaded it while we waited
+        if let Some(context) = self.touch(&key)? {
+            return Ok(context);
+        }
+
+        // An older version of the file is never used again
+        self.lock_models()?.retain(|cached, _| {
+            let stale = cached.path == key.path && cached.file != key.file;
+            if stale {
+                info!(
+                    "Whisper model {:?} changed on disk, unloading the old one",
+                    key.path
+                );
+            }
+            !stale
+        });
+
+        let size_bytes = key.file.len;
+        self.make_room(size_bytes)?;
+
+        let started = Instant::now();
+        let context = load_whisper_model(&key.path, options)?;
+        let context = Arc::new(context);
+        info!(
+            "Loaded whisper model {:?} ({} MB) in {:.2}s",
+            key.path,
+            size_bytes / (1024 * 1024),
+            started.elapsed().as_secs_f32()
+        );
+
+        self.lock_models()?.insert(
+            key,
+            CachedModel {
+                context: context.clone(),
+                size_bytes,
+                last_used: Instant::now(),
+            },
+        );
+        Ok(context)
+    }
+
+    /// Unload every cached variant of a model, returns whether any was loaded.
+    /// Also works once the model file was deleted or moved, as long as `model_path`
+    /// is spelled the way it was loaded.
+    pub fn unload(&self, model_path: &str) -> Result<bool, WhisperCppError> {
+        // Keys hold canonical paths, which can't be resolved for a missing file
+        let path = canonical_model_path(model_path).unwrap_or_else(|_| PathBuf::from(model_path));
+        let mut models = self.lock_models()?;
+        let before = models.len();
+        models.retain(|key, _| key.path != path);
+        let unloaded = models.len() < before;
+        if unloaded {
+            info!("Unloaded whisper model {:?}", path);
+        }
+        Ok(unloaded)
+    }
+
+    /// Unload all models
+    pub fn unload_all(&self) -> Result<(), WhisperCppError> {
+        self.lock_models()?.clear();
+        Ok(())
+    }
+
+    /// Models currently loaded
+    pub fn loaded(&self) -> Result<Vec<LoadedModel>, WhisperCppError> {
+        let models = self.lock_models()?;
+        Ok(models
+            .iter()
+            .map(|(key, model)| LoadedModel {
+                model_path: key.path.to_string_lossy().to_string(),
+                size_bytes: model.size_bytes,
+                idle_seconds: model.last_used.elapsed().as_secs_f32(),
+            })
+            .collect())
+    }
+
+    pub fn settings(&self) -> ModelCacheSettings {
+        self.settings.lock().map(|s| *s).unwrap_or_default()
+    }
+
+    /// Change the limits, applying a lower budget right away
+    pub fn set_settings(&self, settings: ModelCacheSettings) -> Result<(), WhisperCppError> {
+        *self.settings.lock().map_err(|_| lock_error())? = settings;
+        self.make_room(0)?;
+        self.evict_idle()
+    }
+
+    /// Unload models that have been idle longer than the timeout
+    pub fn evict_idle(&self) -> Result<(), WhisperCppError> {
+        let timeout = self.settings().idle_timeout_seconds;
+        if timeout == 0 {
+            return Ok(());
+        }
+        let timeout = Duration::from_secs(timeout);
+
+        let mut models = self.lock_models()?;
+        models.retain(|key, model| {
+            let keep = model.last_used.elapsed() < timeout;
+            if !keep {
+                info!("Unloading idle whisper model {:?}", key.path);
+            }
+            keep
+        });
+        Ok(())
+    }
+
+    /// Return a cached model and mark it as used
+    fn touch(&self, key: &ModelKey) -> Result<Option<Arc<WhisperContext>>, WhisperCppError> {
+        let mut models = self.lock_models()?;
+        Ok(models.get_mut(key).map(|model| {
+            model.last_used = Instant::now();
+            model.context.clone()
+        }))
+    }
+
+    /// Unload least recently used models until `incoming_bytes` more fit in the budget
+    fn make_room(&self, incoming_bytes: u64) -> Result<(), WhisperCppError> {
+        let budget = self.settings().memory_budget_bytes();
+        if incoming_bytes > budget {
+            warn!(
+                "Whisper model of {} MB exceeds the {} MB model memory budget",
+                incoming_bytes / (1024 * 1024),
+                budget / (1024 * 1024)
+            );
+        }
+
+        let mut models = self.lock_models()?;
+        let cached = models
+            .iter()
+            .map(|(key, model)| (key.clone(), model.size_bytes, model.last_used));
+        for key in eviction_order(cached, incoming_bytes, budget) {
+            debug!(
+                "Unloading whisper model {:?} to stay within the memory budget",
+                key.path
+            );
+            models.remove(&key);
+        }
+        Ok(())
+    }
+
+    fn lock_models(
+        &self,
+    ) -> Result<MutexGuard<'_, HashMap<ModelKey, CachedModel>>, WhisperCppError> {
+        self.models.lock().map_err(|_| lock_error())
+    }
+}
+
+/// Periodically unload models that have been idle too long
+pub fn spawn_model_eviction_task<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(EVICTION_INTERVAL).await;
+            if let Err(e) = app.state::<ModelCache>().evict_idle() {
+                warn!("Failed to unload idle whisper models: {}", e);
+            }
+        }
+    });
+}
+
+/// Load Whisper model with automatic GPU support based on compiled features
+fn load_whisper_model(
+    model_path: &Path,
+    options: &ModelOptions,
+) -> Result<WhisperContext, WhisperCppError> {
+    // GPU acceleration is automatically enabled based on compile-time features:
+    // - macOS: Metal + CoreML
+    // - Windows: CUDA + Vulkan
+    // - Linux: CUDA + Vulkan + HipBLAS
+    // The whisper-rs library automatically selects the best available backend
+
+    WhisperContext::new_with_params(model_path, options.context_parameters()).map_err(|e| {
+        WhisperCppError::ModelLoadError {
+            message: format!("Failed to load model: {}", e),
+        }
+    })
+}
+
+/// Least recently used models to unload, given as (key, size, last used), until
+/// `incoming_bytes` more fit in `budget`. Everything goes when that's not enough.
+fn eviction_order<K>(
+    cached: impl Iterator<Item = (K, u64, Instant)>,
+    incoming_bytes: u64,
+    budget: u64,
+) -> Vec<K> {
+    let mut cached: Vec<_> = cached.collect();
+    cached.sort_by_key(|(_, _, last_used)| *last_used);
+
+    // Sizes come from file metadata, u128 can't overflow summing them
+    let mut total: u128 = cached.iter().map(|(_, size, _)| *size as u128).sum();
+    total += incoming_bytes as u128;
+    cached
+        .into_iter()
+        .take_while(|(_, size, _)| {
+            let over = total > budget as u128;
+            total -= *size as u128;
+            over
+        })
+        .map(|(key, _, _)| key)
+        .collect()
+}
+
+fn model_key(model_path: &str, options: &ModelOptions) -> Result<ModelKey, WhisperCppError> {
+    let path = canonical_model_path(model_path)?;
+    let metadata = std::fs::metadata(&path).map_err(|e| WhisperCppError::ModelLoadError {
+        message: format!("Failed to read model file {}: {}", model_path, e),
+    })?;
+    Ok(ModelKey {
+        path,
+        options: options.clone(),
+        file: FileStamp {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        },
+    })
+}
+
+/// Resolve a model path so different spellings of it share a cache entry
+fn canonical_model_path(model_path: &str) -> Result<PathBuf, WhisperCppError> {
+    std::fs::canonicalize(model_path).map_err(|e| WhisperCppError::ModelLoadError {
+        message: format!("Model file {} not found: {}", model_path, e),
+    })
+}
+
+fn lock_error() -> WhisperCppError {
+    WhisperCppError::ModelLoadError {
+        message: "Whisper model cache lock poisoned".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    /// Models named by their age in seconds, the oldest first
+    fn models(sizes_mb: &[u64]) -> Vec<(usize, u64, Instant)> {
+        let now = Instant::now();
+        sizes_mb
+            .iter()
+            .enumerate()
+            .map(|(i, size)| {
+                let age = Duration::from_secs((sizes_mb.len() - i) as u64);
+                (i, size * MB, now - age)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn evicts_least_recently_used_models_until_the_new_one_fits() {
+        // (sizes in MB, oldest first, incoming MB, budget MB, evicted)
+        let cases: &[(&[u64], u64, u64, &[usize])] = &[
+            (&[1000, 1000], 1000, 4096, &[]),
+            (&[1000, 1000, 1000], 1500, 4096, &[0]),
+            (&[500, 3000, 500], 1000, 4096, &[0, 1]),
+            (&[1000, 1000], 3000, 4096, &[0]),
+            (&[1000, 1000], 3500, 4096, &[0, 1]),
+            // A model bigger than the budget still loads, alone
+            (&[1000, 1000], 5000, 4096, &[0, 1]),
+            // Lowering the budget makes room for nothing
+            (&[1000, 1000, 1000], 0, 2000, &[0]),
+            (&[], 5000, 4096, &[]),
+        ];
+        for &(sizes, incoming, budget, evicted) in cases {
+            assert_eq!(
+                eviction_order(models(sizes).into_iter(), incoming * MB, budget * MB),
+                evicted,
+                "models {:?}, loading {} MB in {} MB",
+                sizes,
+                incoming,
+                budget
+            );
+        }
+    }
+
+    #[test]
+    fn eviction_follows_last_use_not_insertion_order() {
+        let now = Instant::now();
+        let cached = vec![
+            ("recent", 2000 * MB, now),
+            ("oldest", 2000 * MB, now - Duration::from_secs(60)),
+            ("older", 2000 * MB, now - Duration::from_secs(30)),
+        ];
+        assert_eq!(
+            eviction_order(cached.into_iter(), 2000 * MB, 5000 * MB),
+            ["oldest", "older"]
+        );
+    }
+
+    #[test]
+    fn huge_budgets_and_sizes_do_not_overflow() {
+        let settings = ModelCacheSettings {
+            idle_timeout_seconds: 0,
+            memory_budget_mb: u64::MAX,
+        };
+        assert_eq!(settings.memory_budget_bytes(), u64::MAX);
+
+        let cached = || {
+            models(&[1000, 1000])
+                .into_iter()
+                .map(|(key, _, used)| (key, u64::MAX, used))
+        };
+        assert_eq!(eviction_order(cached(), 1, u64::MAX), [0, 1]);
+        assert_eq!(eviction_order(cached(), 0, u64::MAX), [0]);
+    }
+
+    #[test]
+    fn model_replaced_in_place_gets_a_new_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ggml-base.bin");
+        std::fs::write(&path, [0u8; 16]).unwrap();
+        let spelled = dir.path().join(".").join("ggml-base.bin");
+        let options = ModelOptions::default();
+
+        let key = model_key(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(key.file.len, 16);
+        assert_eq!(model_key(spelled.to_str().unwrap(), &options).unwrap(), key);
+
+        std::fs::write(&path, [0u8; 32]).unwrap();
+        let replaced = model_key(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(replaced.path, key.path);
+        assert_ne!(replaced, key);
+
+        let gpu = ModelOptions {
+            use_gpu: Some(false),
+            ..ModelOptions::default()
+        };
+        assert_ne!(model_key(path.to_str().unwrap(), &gpu).unwrap(), replaced);
+        assert!(model_key(dir.path().join("missing.bin").to_str().unwrap(), &options).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use whisper_rs::{FullParams, SamplingStrategy};
+
+/// Silence threshold used unless the options set one
+const DEFAULT_NO_SPEECH_THRESHOLD: f32 = 0.2;
+
+/// Decoding options for power users, trading accuracy against speed - exchanged with
+/// frontend. Options left unset keep whisper.cpp's defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranscriptionOptions {
+    pub translate: bool,                    // Translate the speech into English
+    pub beam_size: Option<i32>,             // Beam search instead of greedy sampling
+    pub patience: Option<f32>,              // Beam search patience
+    pub best_of: Option<i32>,               // Greedy candidates per temperature
+    pub temperature_increment: Option<f32>, // Fallback step, 0 disables fallback
+    pub entropy_threshold: Option<f32>,     // Retry decodes more repetitive than this
+    pub logprob_threshold: Option<f32>,     // Retry decodes less likely than this
+    pub no_speech_threshold: Option<f32>,
+    pub max_segment_length: Option<i32>, // In characters, 0 for no limit
+    pub split_on_word: bool,             // Only end segments on word boundaries
+    pub word_timestamps: bool,           // Time and score each word of the segments
+    pub suppress_blank: Option<bool>,
+    pub threads: Option<i32>,
+}
+
+/// Everything a whisper run needs besides the audio
+#[derive(Debug, Clone, Default)]
+pub struct WhisperRequest {
+    pub language: Option<String>,
+    pub prompt: String,
+    pub temperature: f32,
+    pub timestamps: bool, // Predict segment times, otherwise segments only carry text
+    pub options: TranscriptionOptions,
+}
+
+impl WhisperRequest {
+    pub fn full_params(&self) -> FullParams<'_, '_> {
+        let options = &self.options;
+        let strategy = match options.beam_size {
+            Some(beam_size) => SamplingStrategy::BeamSearch {
+                beam_size,
+                patience: options.patience.unwrap_or(-1.0),
+            },
+            None => SamplingStrategy::Greedy {
+                best_of: options.best_of.unwrap_or(1),
+            },
+        };
+
+        let mut params = FullParams::new(strategy);
+        params.set_translate(options.translate);
+        params.set_no_timestamps(!self.timestamps);
+        params.set_temperature(self.temperature);
+        params.set_no_speech_thold(
+            options
+                .no_speech_threshold
+                .unwrap_or(DEFAULT_NO_SPEECH_THRESHOLD),
+        );
+        params.set_suppress_nst(true); // Prevent hallucinations (non-speech tokens)
+
+        if let Some(increment) = options.temperature_increment {
+            params.set_temperature_inc(increment);
+        }
+        if let Some(threshold) = options.entropy_threshold {
+            params.set_entropy_thold(threshold);
+        }
+        if let Some(threshold) = options.logprob_threshold {
+            params.set_logprob_thold(threshold);
+        }
+        if let Some(max_len) = options.max_segment_length.filter(|&len| len > 0) {
+            // whisper.cpp only splits segments using token timestamps
+            params.set_max_len(max_len);
+            params.set_token_timestamps(true);
+        }
+        params.set_split_on_word(options.split_on_word);
+        if options.word_timestamps {
+            params.set_token_timestamps(true);
+        }
+        if let Some(suppress_blank) = options.suppress_blank {
+            params.set_suppress_blank(suppress_blank);
+        }
+        if let Some(threads) = options.threads.filter(|&threads| threads > 0) {
+            params.set_n_threads(threads);
+        }
+
+        // Set language if specified, otherwise detect it (whisper-rs defaults to English)
+        match self.language.as_deref() {
+            Some(lang) if !lang.is_empty() && lang != "auto" => params.set_language(Some(lang)),
+            _ => params.set_language(None),
+        }
+
+        // Set initial prompt if provided
+        if !self.prompt.trim().is_empty() {
+            params.set_initial_prompt(&self.prompt);
+        }
+
+        params
+    }
+}
@@ -0,0 +1,526 @@
+use super::decode::{to_whisper_samples, WHISPER_SAMPLE_RATE};
+use super::error::WhisperCppError;
+use super::model_cache::{ModelCache, ModelOptions};
+use super::options::WhisperRequest;
+use super::run_whisper;
+use super::transcript::TranscriptSegment;
+use crate::recorder::commands::AppData;
+use crate::recorder::live_reader::LiveRecording;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{debug, info, warn};
+use whisper_rs::{WhisperContext, WhisperState};
+
+/// Event carrying streaming transcription updates
+pub const STREAMING_TRANSCRIPT_EVENT: &str = "whisper-streaming-transcript";
+
+/// How often the recording is checked for new audio
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whisper works on 30 second chunks, a longer window would be cut
+const MAX_WINDOW_SECONDS: f32 = 28.0;
+
+/// Segments whisper considers this likely to be silence are dropped,
+/// it tends to invent text for quiet stretches
+const NO_SPEECH_THRESHOLD: f32 = 0.6;
+
+/// Finalized text carried into the next window's prompt, for continuity
+const CONTEXT_PROMPT_CHARS: usize = 200;
+
+/// How the recording is windowed while streaming - exchanged with frontend
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StreamingOptions {
+    pub step_seconds: f32, // New audio needed before the window is transcribed again
+    pub window_seconds: f32, // Window length at which finished segments are finalized
+}
+
+impl Default for StreamingOptions {
+    fn default() -> Self {
+        Self {
+            step_seconds: 1.0,
+            window_seconds: 15.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamingTranscriptKind {
+    Partial, // Text of the audio after the finalized part, replaced by the next update
+    Final,   // Text that won't change any more
+    Done,    // The stream ended, `text` holds the whole transcript
+    Error,   // The stream stopped, `text` holds the reason
+}
+
+/// A streaming transcription update - emitted to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamingTranscriptUpdate {
+    pub recording_id: String,
+    pub kind: StreamingTranscriptKind,
+    pub text: String,
+    pub start_seconds: f64, // From the start of the recording
+    pub end_seconds: f64,
+}
+
+/// What the transcription needs besides the audio
+pub struct StreamingRequest {
+    pub model_path: String,
+    pub model_options: ModelOptions,
+    pub whisper: WhisperRequest, // With timestamps, the windows are cut at segment ends
+    pub options: StreamingOptions,
+}
+
+struct StreamingSession {
+    recording_id: String,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// The streaming transcription of the recording in progress, at most one at a time
+#[derive(Default)]
+pub struct StreamingTranscription {
+    session: Mutex<Option<StreamingSession>>,
+}
+
+impl StreamingTranscription {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start transcribing the active recording as it is written.
+    ///
+    /// The model is loaded before this returns, so a bad model path fails here rather
+    /// than in an event. Returns the id of the recording being transcribed.
+    pub fn start(
+        &self,
+        app_handle: AppHandle,
+        request: StreamingRequest,
+    ) -> Result<String, WhisperCppError> {
+        // Checked again once the model is loaded. Loading can take a while, and stop()
+        // shouldn't wait on it.
+        drop(self.lock_idle()?);
+
+        let (recording_id, live) = {
+            let app_data = app_handle.state::<AppData>();
+            let recorder = app_data
+                .recorder
+                .lock()
+                .map_err(|e| audio_error(format!("Failed to lock recorder: {}", e)))?;
+            let recording_id = recorder
+                .get_current_recording_id()
+                .ok_or_else(|| audio_error("No recording in progress".to_string()))?;
+            let live = recorder
+                .get_live_recording()
+                .map_err(audio_error)?
+                .ok_or_else(|| WhisperCppError::StreamingUnsupported {
+                    message: "Only WAV recordings can be transcribed while recording".to_string(),
+                })?;
+            (recording_id, live)
+        };
+
+        let context = app_handle
+            .state::<ModelCache>()
+            .get(&request.model_path, &request.model_options)?;
+        let state = context
+            .create_state()
+            .map_err(|e| WhisperCppError::TranscriptionError {
+                message: format!("Failed to create whisper state: {}", e),
+            })?;
+
+        let mut session = self.lock_idle()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stream = Stream {
+            app_handle,
+            recording_id: recording_id.clone(),
+            live,
+            context,
+            state,
+            request,
+            stop: stop.clone(),
+            committed_frame: 0,
+            committed_text: String::new(),
+            last_pass_frames: 0,
+        };
+        let thread = thread::spawn(move || stream.run());
+
+        info!(
+            "Started streaming transcription of recording {}",
+            recording_id
+        );
+        *session = Some(StreamingSession {
+            recording_id: recording_id.clone(),
+            stop,
+            thread,
+        });
+        Ok(recording_id)
+    }
+
+    /// Ask the running stream to finalize what it has and end.
+    /// Returns the id of the recording it was transcribing, if one was running.
+    pub fn stop(&self) -> Result<Option<String>, WhisperCppError> {
+        let session = self.lock()?;
+        Ok(session
+            .as_ref()
+            .filter(|s| !s.thread.is_finished())
+            .map(|s| {
+                s.stop.store(true, Ordering::Release);
+                s.recording_id.clone()
+            }))
+    }
+
+    /// Lock the session, failing if a stream is still running
+    fn lock_idle(&self) -> Result<MutexGuard<'_, Option<StreamingSession>>, WhisperCppError> {
+        let session = self.lock()?;
+        if session.as_ref().is_some_and(|s| !s.thread.is_finished()) {
+            return Err(WhisperCppError::TranscriptionError {
+                message: "A streaming transcription is already running".to_string(),
+            });
+        }
+        Ok(session)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Option<StreamingSession>>, WhisperCppError> {
+        self.session
+            .lock()
+            .map_err(|_| WhisperCppError::TranscriptionError {
+                message: "Streaming transcription lock poisoned".to_string(),
+            })
+    }
+}
+
+/// The worker tailing one recording.
+///
+/// Each pass transcribes the audio from the end of the finalized text up to the newest
+/// audio on disk and reports it as partial text. Once that window reaches
+/// `window_seconds`, every segment but the last is finalized and the window moves up to
+/// the end of them, so the last segment (which may be cut mid-word) is transcribed again
+/// as the start of the next window. When the recording stops, the rest is finalized.
+struct Stream {
+    app_handle: AppHandle,
+    recording_id: String,
+    live: LiveRecording,
+    context: Arc<WhisperContext>,
+    state: WhisperState,
+    request: StreamingRequest,
+    stop: Arc<AtomicBool>,
+    committed_frame: u64,   // Frames covered by finalized text
+    committed_text: String, // All finalized text
+    last_pass_frames: u64,  // Frames available at the previous pass
+}
+
+impl Stream {
+    fn run(mut self) {
+        match self.transcribe_until_stopped() {
+            Ok(()) => {
+                info!(
+                    "Finished streaming transcription of recording {}",
+                    self.recording_id
+                );
+                let end = self.frames_to_seconds(self.committed_frame);
+                let text = self.committed_text.clone();
+                self.emit(StreamingTranscriptKind::Done, text, 0.0, end);
+            }
+            Err(e) => {
+                warn!("Streaming transcription failed: {}", e);
+                self.emit(StreamingTranscriptKind::Error, e.to_string(), 0.0, 0.0);
+            }
+        }
+    }
+
+    fn transcribe_until_stopped(&mut self) -> Result<(), WhisperCppError> {
+        let sample_rate = self.live.sample_rate() as f32;
+        let step_frames = (self.request.options.step_seconds.max(0.1) * sample_rate) as u64;
+        let window_seconds = self
+            .request
+            .options
+            .window_seconds
+            .clamp(1.0, MAX_WINDOW_SECONDS);
+        let window_frames = (window_seconds * sample_rate) as u64;
+
+        loop {
+            let finishing = self.stop.load(Ordering::Acquire) || !self.still_recording();
+            let available = self.live.available_frames();
+
+            if !finishing && available < self.last_pass_frames + step_frames {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            self.last_pass_frames = available;
+
+            let frames = available
+                .saturating_sub(self.committed_frame)
+                .min(window_frames);
+            let last_window = finishing && self.committed_frame + frames >= available;
+            let segments = self.transcribe_window(frames)?;
+
+            match segments_to_finalize(segments.len(), last_window, frames >= window_frames) {
+                Some(count) => self.commit(&segments, frames, count),
+                None => self.emit_partial(&segments, frames),
+            }
+            if last_window {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Whether the recording this stream follows is still being recorded
+    fn still_recording(&self) -> bool {
+        let app_data = self.app_handle.state::<AppData>();
+        let recording_id = match app_data.recorder.lock() {
+            Ok(recorder) => recorder.get_current_recording_id(),
+            Err(_) => None,
+        };
+        recording_id.as_deref() == Some(self.recording_id.as_str())
+    }
+
+    /// Transcribe `frames` frames from the end of the finalized text
+    fn transcribe_window(
+        &mut self,
+        frames: u64,
+    ) -> Result<Vec<TranscriptSegment>, WhisperCppError> {
+        if frames == 0 {
+            return Ok(Vec::new());
+        }
+        let audio = {
+            let app_data = self.app_handle.state::<AppData>();
+            self.live
+                .read(&app_data.recording_keys, self.committed_frame, frames)
+                .map_err(|e| audio_error(format!("Failed to read active recording: {}", e)))?
+        };
+        let mut samples =
+            to_whisper_samples(&audio.samples, audio.channels as usize, audio.sample_rate);
+        // whisper skips anything shorter than a second, which a final word can be
+        samples.resize(samples.len().max(WHISPER_SAMPLE_RATE as usize), 0.0);
+
+        let request = WhisperRequest {
+            prompt: self.prompt(),
+            ..self.request.whisper.clone()
+        };
+        let started = std::time::Instant::now();
+        let transcript = run_whisper(&self.context, &mut self.state, &samples, &request, None)?;
+        debug!(
+            "Transcribed {:.1}s streaming window in {:.2}s",
+            samples.len() as f32 / WHISPER_SAMPLE_RATE as f32,
+            started.elapsed().as_secs_f32()
+        );
+
+        Ok(transcript
+            .segments
+            .into_iter()
+            .filter(|s| !s.text.is_empty() && s.no_speech_probability < NO_SPEECH_THRESHOLD)
+            .collect())
+    }
+
+    /// Finalize the first `count` segments of a window of `frames` frames
+    fn commit(&mut self, segments: &[TranscriptSegment], frames: u64, count: usize) {
+        let window_start = self.committed_frame;
+        let (count, span) = finalized_span(segments, frames, count, self.live.sample_rate());
+        let end_frame = window_start + span;
+        let (finalized, open) = segments.split_at(count);
+
+        let text = join_segments(finalized);
+        if !text.is_empty() {
+            if !self.committed_text.is_empty() {
+                self.committed_text.push(' ');
+            }
+            self.committed_text.push_str(&text);
+            let start = self.frames_to_seconds(window_start);
+            let end = self.frames_to_seconds(end_frame);
+            self.emit(StreamingTranscriptKind::Final, text, start, end);
+        }
+        self.committed_frame = end_frame;
+
+        if !open.is_empty() {
+            let open_start = self.committed_frame;
+            let text = join_segments(open);
+            let start = self.frames_to_seconds(open_start);
+            let end = self.frames_to_seconds(window_start + frames);
+            self.emit(StreamingTranscriptKind::Partial, text, start, end);
+        }
+    }
+
+    fn emit_partial(&self, segments: &[TranscriptSegment], frames: u64) {
+        let start = self.frames_to_seconds(self.committed_frame);
+        let end = self.frames_to_seconds(self.committed_frame + frames);
+        self.emit(
+            StreamingTranscriptKind::Partial,
+            join_segments(segments),
+            start,
+            end,
+        );
+    }
+
+    /// The user's prompt followed by the end of the finalized text
+    fn prompt(&self) -> String {
+        let chars = self.committed_text.chars().count();
+        let context: String = self
+            .committed_text
+            .chars()
+            .skip(chars.saturating_sub(CONTEXT_PROMPT_CHARS))
+            .collect();
+        format!("{} {}", self.request.whisper.prompt.trim(), context.trim())
+            .trim()
+            .to_string()
+    }
+
+    fn emit(&self, kind: StreamingTranscriptKind, text: String, start: f64, end: f64) {
+        let update = StreamingTranscriptUpdate {
+            recording_id: self.recording_id.clone(),
+            kind,
+            text,
+            start_seconds: start,
+            end_seconds: end,
+        };
+        if let Err(e) = self.app_handle.emit(STREAMING_TRANSCRIPT_EVENT, update) {
+            warn!("Failed to emit streaming transcript: {}", e);
+        }
+    }
+
+    fn frames_to_seconds(&self, frames: u64) -> f64 {
+        frames as f64 / self.live.sample_rate() as f64
+    }
+}
+
+/// How many of a window's segments to finalize: all of them in the last window, all but
+/// the last (which may be cut off mid-word) once the window is full, and none yet
+/// (None) while it is still growing
+fn segments_to_finalize(segments: usize, last_window: bool, full_window: bool) -> Option<usize> {
+    if last_window {
+        Some(segments)
+    } else if full_window {
+        Some(segments.saturating_sub(1))
+    } else {
+        None
+    }
+}
+
+/// Finalizing the first `count` segments of a window of `frames` frames moves the window
+/// to the end of the last of them. When every segment is finalized, or that segment
+/// ends where the window starts (the window would never move), everything is finalized
+/// and the window moves to its end. Returns the number of segments finalized and the
+/// frames they cover.
+fn finalized_span(
+    segments: &[TranscriptSegment],
+    frames: u64,
+    count: usize,
+    sample_rate: u32,
+) -> (usize, u64) {
+    let segment_end = segments[..count].last().map_or(0, |s| {
+        let end = (s.end_seconds.max(0.0) * sample_rate as f64).round() as u64;
+        end.min(frames)
+    });
+    if count < segments.len() && segment_end > 0 {
+        (count, segment_end)
+    } else {
+        (segments.len(), frames)
+    }
+}
+
+fn join_segments(segments: &[TranscriptSegment]) -> String {
+    segments
+        .iter()
+        .map(|s| s.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn audio_error(message: String) -> WhisperCppError {
+    WhisperCppError::AudioReadError { message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn segments(ends: &[f64]) -> Vec<TranscriptSegment> {
+        let mut start = 0.0;
+        ends.iter()
+            .map(|&end| {
+                let segment = TranscriptSegment {
+                    start_seconds: start,
+                    end_seconds: end,
+                    text: format!("until {}", end),
+                    avg_token_probability: 0.9,
+                    no_speech_probability: 0.0,
+                    words: Vec::new(),
+                };
+                start = end;
+                segment
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finalizes_segments_once_the_window_is_full_or_last() {
+        // (segments, last window, full window, expected)
+        let cases = [
+            (3, false, false, None),
+            (3, false, true, Some(2)),
+            (3, true, false, Some(3)),
+            (3, true, true, Some(3)),
+            (0, false, true, Some(0)),
+            (0, true, false, Some(0)),
+        ];
+        for (count, last, full, expected) in cases {
+            assert_eq!(
+                segments_to_finalize(count, last, full),
+                expected,
+                "{} segments, last {}, full {}",
+                count,
+                last,
+                full
+            );
+        }
+    }
+
+    #[test]
+    fn window_moves_to_the_end_of_the_finalized_segments() {
+        let window = segments(&[4.0, 9.5, 15.0]);
+        let frames = 15 * RATE as u64;
+
+        // The last segment stays open and is transcribed again with the next window
+        assert_eq!(
+            finalized_span(&window, frames, 2, RATE),
+            (2, 9 * RATE as u64 + RATE as u64 / 2)
+        );
+        assert_eq!(
+            finalized_span(&window, frames, 1, RATE),
+            (1, 4 * RATE as u64)
+        );
+        // Finalizing everything covers the whole window, past the last segment end
+        assert_eq!(
+            finalized_span(&window, frames + 100, 3, RATE),
+            (3, frames + 100)
+        );
+    }
+
+    #[test]
+    fn window_always_moves_forward() {
+        let frames = 15 * RATE as u64;
+
+        // Nothing to keep open for: all of the window is final
+        assert_eq!(finalized_span(&[], frames, 0, RATE), (0, frames));
+        assert_eq!(
+            finalized_span(&segments(&[15.0]), frames, 0, RATE),
+            (1, frames)
+        );
+        // A finalized segment ending at the window start would never move it
+        assert_eq!(
+            finalized_span(&segments(&[0.0, 15.0]), frames, 1, RATE),
+            (2, frames)
+        );
+        // Segment ends past the audio (whisper pads short windows) are clamped
+        assert_eq!(
+            finalized_span(&segments(&[20.0, 25.0]), frames, 1, RATE),
+            (1, frames)
+        );
+    }
+}
@@ -0,0 +1,555 @@
+use super::transcript::{Transcript, TranscriptWord};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt::Write;
+
+/// File format a transcript is exported in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Srt,
+    Vtt,
+    Txt,
+    Json, // Same layout as whisper.cpp's --output-json
+}
+
+impl TranscriptFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Srt => "srt",
+            TranscriptFormat::Vtt => "vtt",
+            TranscriptFormat::Txt => "txt",
+            TranscriptFormat::Json => "json",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Srt => "SubRip subtitles",
+            TranscriptFormat::Vtt => "WebVTT subtitles",
+            TranscriptFormat::Txt => "Plain text",
+            TranscriptFormat::Json => "JSON transcript",
+        }
+    }
+}
+
+/// How segments are cut into subtitle cues - exchanged with frontend
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SubtitleOptions {
+    pub max_line_length: usize, // Characters per line
+    pub max_lines: usize,       // Lines per cue
+    pub max_cue_seconds: f64,   // Longer segments are split over several cues
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        // Common broadcast guidelines
+        Self {
+            max_line_length: 42,
+            max_lines: 2,
+            max_cue_seconds: 7.0,
+        }
+    }
+}
+
+/// A subtitle on screen from `start` to `end` seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub lines: Vec<String>,
+}
+
+/// Render a transcript in the given format
+pub fn render_transcript(
+    transcript: &Transcript,
+    format: TranscriptFormat,
+    options: &SubtitleOptions,
+) -> String {
+    match format {
+        TranscriptFormat::Srt => render_srt(&build_cues(transcript, options)),
+        TranscriptFormat::Vtt => render_vtt(&build_cues(transcript, options)),
+        TranscriptFormat::Txt => render_txt(transcript),
+        TranscriptFormat::Json => render_json(transcript),
+    }
+}
+
+/// Cut the transcript's segments into cues that respect the line and duration limits.
+///
+/// Each segment is first split into parts no longer than `max_cue_seconds`, then each
+/// part is wrapped into lines and grouped `max_lines` to a cue. With word timings, parts
+/// are cut where the next word would run past the limit and a cue lasts from its first
+/// word's start to its last word's end. Without them, parts are balanced by length and a
+/// segment's time is shared out between its cues by character count.
+pub fn build_cues(transcript: &Transcript, options: &SubtitleOptions) -> Vec<Cue> {
+    let max_line_length = options.max_line_length.max(1);
+    let max_lines = options.max_lines.max(1);
+    let max_cue_seconds = if options.max_cue_seconds > 0.0 {
+        options.max_cue_seconds
+    } else {
+        f64::INFINITY
+    };
+
+    let mut cues = Vec::new();
+    for segment in &transcript.segments {
+        let timed = !segment.words.is_empty();
+        let tokens = if timed {
+            tokenize_words(&segment.words, max_line_length)
+        } else {
+            tokenize(&segment.text, max_line_length)
+        };
+        if tokens.is_empty() {
+            continue;
+        }
+        let duration = (segment.end_seconds - segment.start_seconds).max(0.0);
+
+        let parts = if timed {
+            split_by_time(&tokens, max_cue_seconds)
+        } else {
+            let count = (duration / max_cue_seconds).ceil().max(1.0) as usize;
+            balance(&tokens, count)
+        };
+        let segment_cues: Vec<Vec<&[Token]>> = parts
+            .into_iter()
+            .flat_map(|part| {
+                wrap(part, max_line_length)
+                    .chunks(max_lines)
+                    .map(<[&[Token]]>::to_vec)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        if timed {
+            for lines in segment_cues {
+                let start = lines[0][0].timing.0;
+                let end = lines
+                    .last()
+                    .and_then(|line| line.last())
+                    .map_or(start, |t| t.timing.1);
+                cues.push(Cue {
+                    start,
+                    end: end.max(start),
+                    lines: lines.into_iter().map(line_text).collect(),
+                });
+            }
+            continue;
+        }
+
+        let segment_cues: Vec<Vec<String>> = segment_cues
+            .into_iter()
+            .map(|lines| lines.into_iter().map(line_text).collect())
+            .collect();
+        let total_chars: usize = segment_cues.iter().map(|lines| cue_chars(lines)).sum();
+        let mut start = segment.start_seconds;
+        let mut consumed = 0;
+        for lines in segment_cues {
+            consumed += cue_chars(&lines);
+            let end = segment.start_seconds + duration * consumed as f64 / total_chars as f64;
+            cues.push(Cue {
+                start,
+                // Text too short to split further leaves the screen early instead
+                end: end.min(start + max_cue_seconds),
+                lines,
+            });
+            start = end;
+        }
+    }
+    cues
+}
+
+fn render_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{}\n{} --> {}\n{}\n",
+            i + 1,
+            timestamp(cue.start, ','),
+            timestamp(cue.end, ','),
+            cue.lines.join("\n")
+        );
+    }
+    out
+}
+
+fn render_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        let _ = writeln!(
+            out,
+            "{} --> {}\n{}\n",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.'),
+            cue.lines.join("\n")
+        );
+    }
+    out
+}
+
+/// One segment per line, like whisper.cpp's --output-txt
+fn render_txt(transcript: &Transcript) -> String {
+    let mut out = String::new();
+    for segment in &transcript.segments {
+        if !segment.text.is_empty() {
+            out.push_str(&segment.text);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn render_json(transcript: &Transcript) -> String {
+    let transcription: Vec<_> = transcript
+        .segments
+        .iter()
+        .map(|segment| {
+            json!({
+                "timestamps": {
+                    "from": timestamp(segment.start_seconds, ','),
+                    "to": timestamp(segment.end_seconds, ','),
+                },
+                "offsets": {
+                    "from": (segment.start_seconds * 1000.0).round() as i64,
+                    "to": (segment.end_seconds * 1000.0).round() as i64,
+                },
+                "text": segment.text,
+            })
+        })
+        .collect();
+    let document = json!({
+        "result": { "language": transcript.language },
+        "transcription": transcription,
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_default()
+}
+
+/// HH:MM:SS followed by `separator` and milliseconds
+fn timestamp(seconds: f64, separator: char) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        total_ms / 3_600_000,
+        total_ms / 60_000 % 60,
+        total_ms / 1000 % 60,
+        separator,
+        total_ms % 1000
+    )
+}
+
+/// A word, or a character of a word that was broken up
+struct Token {
+    text: String,
+    glued: bool,        // Follows the previous token without a space
+    timing: (f64, f64), // Start and end seconds, only known with word timings
+}
+
+impl Token {
+    /// Characters this token adds to a line after another token
+    fn width(&self) -> usize {
+        self.text.chars().count() + usize::from(!self.glued)
+    }
+}
+
+/// Split text into words. Words too long for a line, and text in scripts written
+/// without spaces (which comes through as one long word), are split into characters.
+fn tokenize(text: &str, max_line_length: usize) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for word in text.split_whitespace() {
+        if word.chars().count() > max_line_length || word.chars().any(is_unspaced_script) {
+            tokens.extend(word.chars().enumerate().map(|(i, c)| Token {
+                text: c.to_string(),
+                glued: i > 0,
+                timing: (0.0, 0.0),
+            }));
+        } else {
+            tokens.push(Token {
+                text: word.to_string(),
+                glued: false,
+                timing: (0.0, 0.0),
+            });
+        }
+    }
+    tokens
+}
+
+/// Like `tokenize`, for timed words. A word broken into characters shares its time out
+/// between them, and words of unspaced scripts follow each other without a space.
+fn tokenize_words(words: &[TranscriptWord], max_line_length: usize) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut previous_unspaced = false;
+    for word in words {
+        let mut tokenized = tokenize(&word.text, max_line_length);
+        let Some(first) = tokenized.first_mut() else {
+            continue;
+        };
+        first.glued =
+            previous_unspaced && first.text.chars().next().is_some_and(is_unspaced_script);
+        previous_unspaced = word.text.chars().last().is_some_and(is_unspaced_script);
+
+        let count = tokenized.len() as f64;
+        let duration = (word.end_seconds - word.start_seconds).max(0.0);
+        for (i, mut token) in tokenized.into_iter().enumerate() {
+            token.timing = (
+                word.start_seconds + duration * i as f64 / count,
+                word.start_seconds + duration * (i + 1) as f64 / count,
+            );
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+/// Chinese, Japanese and Thai and similar scripts don't put spaces between words
+pub fn is_unspaced_script(c: char) -> bool {
+    matches!(c,
+        '\u{0E00}'..='\u{0EFF}' // Thai, Lao
+        | '\u{1000}'..='\u{109F}' // Myanmar
+        | '\u{1780}'..='\u{17FF}' // Khmer
+        | '\u{3000}'..='\u{30FF}' // CJK punctuation, Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{FF00}'..='\u{FFEF}' // Fullwidth forms
+    )
+}
+
+/// Split tokens into `parts` consecutive runs of roughly equal length
+fn balance(tokens: &[Token], parts: usize) -> Vec<&[Token]> {
+    let parts = parts.min(tokens.len()).max(1);
+    let total: usize = tokens.iter().map(Token::width).sum();
+
+    let mut runs = Vec::with_capacity(parts);
+    let mut run_start = 0;
+    let mut length = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        length += token.width();
+        let runs_left = parts - runs.len();
+        let tokens_left = tokens.len() - i - 1;
+        // Close the run at its share of the text, keeping a token for each remaining run
+        if runs_left > 1
+            && tokens_left >= runs_left - 1
+            && (length * parts >= total * (runs.len() + 1) || tokens_left == runs_left - 1)
+        {
+            runs.push(&tokens[run_start..=i]);
+            run_start = i + 1;
+        }
+    }
+    runs.push(&tokens[run_start..]);
+    runs
+}
+
+/// Split timed tokens into runs that each last at most `max_cue_seconds`, a token that
+/// is longer on its own getting a run of its own
+fn split_by_time(tokens: &[Token], max_cue_seconds: f64) -> Vec<&[Token]> {
+    let mut runs = Vec::new();
+    let mut run_start = 0;
+    for (i, token) in tokens.iter().enumerate().skip(1) {
+        if token.timing.1 - tokens[run_start].timing.0 > max_cue_seconds {
+            runs.push(&tokens[run_start..i]);
+            run_start = i;
+        }
+    }
+    runs.push(&tokens[run_start..]);
+    runs
+}
+
+/// Greedily fill lines of at most `max_line_length` characters
+fn wrap(tokens: &[Token], max_line_length: usize) -> Vec<&[Token]> {
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut line_length = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if line_length > 0 && line_length + token.width() > max_line_length {
+            lines.push(&tokens[line_start..i]);
+            line_start = i;
+            line_length = 0;
+        }
+        line_length += if line_length > 0 {
+            token.width()
+        } else {
+            token.text.chars().count()
+        };
+    }
+    if line_start < tokens.len() {
+        lines.push(&tokens[line_start..]);
+    }
+    lines
+}
+
+/// Join a line's tokens, spacing the ones that aren't glued to the previous one
+fn line_text(tokens: &[Token]) -> String {
+    let mut line = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 && !token.glued {
+            line.push(' ');
+        }
+        line.push_str(&token.text);
+    }
+    line
+}
+
+fn cue_chars(lines: &[String]) -> usize {
+    lines
+        .iter()
+        .map(|line| line.chars().count())
+        .sum::<usize>()
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::whisper_cpp::transcript::TranscriptSegment;
+
+    fn segment(start_seconds: f64, end_seconds: f64, text: &str) -> TranscriptSegment {
+        TranscriptSegment {
+            start_seconds,
+            end_seconds,
+            text: text.to_string(),
+            avg_token_probability: 1.0,
+            no_speech_probability: 0.0,
+            words: Vec::new(),
+        }
+    }
+
+    fn word(start_seconds: f64, end_seconds: f64, text: &str) -> TranscriptWord {
+        TranscriptWord {
+            start_seconds,
+            end_seconds,
+            text: text.to_string(),
+            probability: 1.0,
+        }
+    }
+
+    fn transcript(segments: Vec<TranscriptSegment>) -> Transcript {
+        Transcript {
+            segments,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn wraps_lines_within_limits() {
+        let text = "the quick brown fox jumps over the lazy dog and keeps running far away";
+        let options = SubtitleOptions {
+            max_line_length: 20,
+            max_lines: 2,
+            max_cue_seconds: 0.0,
+        };
+        let cues = build_cues(&transcript(vec![segment(0.0, 4.0, text)]), &options);
+
+        assert!(cues.len() > 1);
+        for cue in &cues {
+            assert!(cue.lines.len() <= 2);
+            assert!(cue.lines.iter().all(|line| line.chars().count() <= 20));
+        }
+        let lines: Vec<&str> = cues
+            .iter()
+            .flat_map(|c| &c.lines)
+            .map(String::as_str)
+            .collect();
+        assert_eq!(lines.join(" "), text);
+        assert_eq!(cues[0].start, 0.0);
+        assert_eq!(cues[cues.len() - 1].end, 4.0);
+    }
+
+    #[test]
+    fn splits_long_segments_into_short_cues() {
+        let text = "one two three four five six seven eight nine ten eleven twelve";
+        let options = SubtitleOptions {
+            max_cue_seconds: 7.0,
+            ..Default::default()
+        };
+        let cues = build_cues(&transcript(vec![segment(10.0, 30.0, text)]), &options);
+
+        assert_eq!(cues.len(), 3);
+        assert_eq!(cues[0].start, 10.0);
+        for pair in cues.windows(2) {
+            assert!(pair[0].end <= pair[1].start + 1e-9);
+        }
+        assert!(cues.iter().all(|cue| cue.end - cue.start <= 7.0 + 1e-9));
+    }
+
+    #[test]
+    fn breaks_unspaced_scripts_between_characters() {
+        let text = "東京は日本の首都で人口が最も多い都市です";
+        let options = SubtitleOptions {
+            max_line_length: 8,
+            max_lines: 1,
+            max_cue_seconds: 0.0,
+        };
+        let cues = build_cues(&transcript(vec![segment(0.0, 5.0, text)]), &options);
+
+        let lines: Vec<&str> = cues
+            .iter()
+            .flat_map(|c| &c.lines)
+            .map(String::as_str)
+            .collect();
+        assert_eq!(lines, ["東京は日本の首都", "で人口が最も多い", "都市です"]);
+    }
+
+    #[test]
+    fn times_cues_by_their_words() {
+        let mut timed = segment(0.0, 10.0, "hello there general kenobi");
+        timed.words = vec![
+            word(0.5, 1.0, "hello"),
+            word(1.0, 1.5, "there"),
+            word(6.0, 7.0, "general"),
+            word(7.0, 9.5, "kenobi"),
+        ];
+        let options = SubtitleOptions {
+            max_cue_seconds: 5.0,
+            ..Default::default()
+        };
+        let cues = build_cues(&transcript(vec![timed]), &options);
+
+        assert_eq!(
+            cues,
+            [
+                Cue {
+                    start: 0.5,
+                    end: 1.5,
+                    lines: vec!["hello there".to_string()],
+                },
+                Cue {
+                    start: 6.0,
+                    end: 9.5,
+                    lines: vec!["general kenobi".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_timed_words_of_unspaced_scripts() {
+        let mut timed = segment(0.0, 2.0, "東京です");
+        timed.words = vec![word(0.0, 1.0, "東京"), word(1.0, 2.0, "です")];
+        let cues = build_cues(&transcript(vec![timed]), &SubtitleOptions::default());
+
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].lines, ["東京です"]);
+        assert_eq!((cues[0].start, cues[0].end), (0.0, 2.0));
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(timestamp(0.0, ','), "00:00:00,000");
+        assert_eq!(timestamp(3723.4567, ','), "01:02:03,457");
+        assert_eq!(timestamp(59.9996, '.'), "00:01:00.000");
+        assert_eq!(timestamp(-1.0, '.'), "00:00:00.000");
+
+        let cues = [Cue {
+            start: 1.5,
+            end: 62.25,
+            lines: vec!["first".to_string(), "second".to_string()],
+        }];
+        assert_eq!(
+            render_srt(&cues),
+            "1\n00:00:01,500 --> 00:01:02,250\nfirst\nsecond\n\n"
+        );
+        assert_eq!(
+            render_vtt(&cues),
+            "WEBVTT\n\n00:00:01.500 --> 00:01:02.250\nfirst\nsecond\n\n"
+        );
+    }
+}
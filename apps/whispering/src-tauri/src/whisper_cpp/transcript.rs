@@ -0,0 +1,348 @@
+use super::error::WhisperCppError;
+use super::subtitles::is_unspaced_script;
+use serde::{Deserialize, Serialize};
+use whisper_rs::{WhisperContext, WhisperSegment, WhisperState, WhisperTokenId};
+
+/// A transcription with timing - exchanged with frontend
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+    pub text: String,
+    pub language: Option<String>, // Detected, or the one requested
+    pub segments: Vec<TranscriptSegment>,
+}
+
+/// A stretch of speech whisper decoded in one go - exchanged with frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptSegment {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub text: String,
+    pub avg_token_probability: f32, // Over text tokens, timestamps and markers left out
+    pub no_speech_probability: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<TranscriptWord>, // Only with word timestamps enabled
+}
+
+/// A word with its own timing - exchanged with frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptWord {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub text: String,
+    pub probability: f32, // Of its least likely token, so one shaky piece flags the word
+}
+
+/// The text of a transcription and the language it was in - returned to frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionText {
+    pub text: String,
+    pub language: Option<String>, // Detected, or the one requested
+}
+
+impl From<Transcript> for TranscriptionText {
+    fn from(transcript: Transcript) -> Self {
+        Self {
+            text: transcript.text,
+            language: transcript.language,
+        }
+    }
+}
+
+impl Transcript {
+    /// Read the segments and language out of a finished transcription. `words` splits
+    /// segments into timed words, which needs the run to compute token timestamps.
+    pub fn from_state(
+        context: &WhisperContext,
+        state: &WhisperState,
+        words: bool,
+    ) -> Result<Self, WhisperCppError> {
+        // Special tokens (end of text, timestamps, language tags) all follow end of text
+        let token_eot = context.token_eot();
+
+        let mut text = String::new();
+        let mut segments = Vec::new();
+        for i in 0..state.full_n_segments() {
+            let segment =
+                state
+                    .get_segment(i)
+                    .ok_or_else(|| WhisperCppError::TranscriptionError {
+                        message: format!("Failed to get segment {}", i),
+                    })?;
+            let segment_text =
+                segment
+                    .to_str()
+                    .map_err(|e| WhisperCppError::TranscriptionError {
+                        message: format!("Failed to get segment {} text: {}", i, e),
+                    })?;
+
+            let probabilities: Vec<f32> = (0..segment.n_tokens())
+                .filter_map(|t| segment.get_token(t))
+                .filter(|token| token.token_id() < token_eot)
+                .map(|token| token.token_probability())
+                .collect();
+            let avg_token_probability = if probabilities.is_empty() {
+                0.0
+            } else {
+                probabilities.iter().sum::<f32>() / probabilities.len() as f32
+            };
+
+            segments.push(TranscriptSegment {
+                // whisper.cpp counts in centiseconds
+                start_seconds: segment.start_timestamp() as f64 / 100.0,
+                end_seconds: segment.end_timestamp() as f64 / 100.0,
+                text: segment_text.trim().to_string(),
+                avg_token_probability,
+                no_speech_probability: segment.no_speech_probability(),
+                words: if words {
+                    segment_words(&segment, token_eot)
+                } else {
+                    Vec::new()
+                },
+            });
+            // Segments carry their own leading space where the language uses one
+            text.push_str(segment_text);
+        }
+
+        let language = whisper_rs::get_lang_str(state.full_lang_id_from_state()).map(String::from);
+
+        Ok(Self {
+            text: text.trim().to_string(),
+            language,
+            segments,
+        })
+    }
+}
+
+/// The parts of a whisper token that word merging looks at
+struct WordToken<'a> {
+    id: WhisperTokenId,
+    bytes: &'a [u8], // Tokens are bytes, a character can be split over two of them
+    start: i64,      // whisper.cpp counts in centiseconds
+    end: i64,
+    probability: f32,
+}
+
+/// Split a segment into timed words
+fn segment_words(segment: &WhisperSegment<'_>, token_eot: WhisperTokenId) -> Vec<TranscriptWord> {
+    let tokens: Vec<_> = (0..segment.n_tokens())
+        .filter_map(|t| segment.get_token(t))
+        .collect();
+    let tokens = tokens.iter().filter_map(|token| {
+        let data = token.token_data();
+        Some(WordToken {
+            id: token.token_id(),
+            bytes: token.to_bytes().ok()?,
+            start: data.t0,
+            end: data.t1,
+            probability: token.token_probability(),
+        })
+    });
+    merge_words(tokens, token_eot)
+}
+
+/// Merge subword tokens into words. A token starting with a space starts a new word, as
+/// does every character of scripts written without spaces. Special tokens are skipped.
+fn merge_words<'a>(
+    tokens: impl IntoIterator<Item = WordToken<'a>>,
+    token_eot: WhisperTokenId,
+) -> Vec<TranscriptWord> {
+    let mut words = Vec::new();
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut word: Option<TranscriptWord> = None;
+
+    for token in tokens {
+        if token.id >= token_eot {
+            continue;
+        }
+
+        let starts_word = token.bytes.first() == Some(&b' ')
+            || String::from_utf8_lossy(token.bytes)
+                .chars()
+                .next()
+                .is_some_and(is_unspaced_script)
+            || String::from_utf8_lossy(&bytes)
+                .chars()
+                .last()
+                .is_some_and(is_unspaced_script);
+        if let Some(current) = word.as_mut() {
+            if !starts_word {
+                bytes.extend_from_slice(token.bytes);
+                current.end_seconds = token.end as f64 / 100.0;
+                current.probability = current.probability.min(token.probability);
+                continue;
+            }
+            finish_word(&mut words, word.take(), &mut bytes);
+        }
+
+        bytes.extend_from_slice(token.bytes);
+        word = Some(TranscriptWord {
+            start_seconds: token.start as f64 / 100.0,
+            end_seconds: token.end as f64 / 100.0,
+            text: String::new(),
+            probability: token.probability,
+        });
+    }
+    finish_word(&mut words, word, &mut bytes);
+    words
+}
+
+/// Give a word the text gathered for it, dropping words that turned out blank
+fn finish_word(words: &mut Vec<TranscriptWord>, word: Option<TranscriptWord>, bytes: &mut Vec<u8>) {
+    let text = String::from_utf8_lossy(bytes).trim().to_string();
+    bytes.clear();
+    if let Some(word) = word.filter(|_| !text.is_empty()) {
+        words.push(TranscriptWord { text, ..word });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EOT: WhisperTokenId = 50257;
+    const TIMESTAMP: WhisperTokenId = EOT + 1000;
+
+    /// A text token as (bytes, start, end, probability)
+    type TokenSpec<'a> = (&'a [u8], i64, i64, f32);
+
+    /// A word as (text, start, end, probability)
+    type WordSpec<'a> = (&'a str, f64, f64, f32);
+
+    /// Text tokens, each given the next id
+    fn tokens<'a>(specs: &[TokenSpec<'a>]) -> Vec<WordToken<'a>> {
+        specs
+            .iter()
+            .enumerate()
+            .map(|(i, &(bytes, start, end, probability))| WordToken {
+                id: i as WhisperTokenId,
+                bytes,
+                start,
+                end,
+                probability,
+            })
+            .collect()
+    }
+
+    fn special(id: WhisperTokenId, bytes: &[u8]) -> WordToken<'_> {
+        WordToken {
+            id,
+            bytes,
+            start: 0,
+            end: 0,
+            probability: 1.0,
+        }
+    }
+
+    fn summary(words: &[TranscriptWord]) -> Vec<WordSpec<'_>> {
+        words
+            .iter()
+            .map(|w| {
+                (
+                    w.text.as_str(),
+                    w.start_seconds,
+                    w.end_seconds,
+                    w.probability,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_tokens_into_words() {
+        let cases: &[(&str, &[TokenSpec], &[WordSpec])] = &[
+            (
+                "leading space starts a word",
+                &[
+                    (b" Hel", 0, 10, 0.9),
+                    (b"lo", 10, 20, 0.8),
+                    (b" world", 20, 45, 0.95),
+                ],
+                &[("Hello", 0.0, 0.2, 0.8), ("world", 0.2, 0.45, 0.95)],
+            ),
+            (
+                "first token starts a word without a space",
+                &[(b"Hi", 0, 10, 0.5), (b"!", 10, 12, 0.6)],
+                &[("Hi!", 0.0, 0.12, 0.5)],
+            ),
+            (
+                "probability is the least likely token",
+                &[
+                    (b" un", 0, 10, 0.9),
+                    (b"believ", 10, 30, 0.4),
+                    (b"able", 30, 50, 0.7),
+                ],
+                &[("unbelievable", 0.0, 0.5, 0.4)],
+            ),
+            (
+                "every unspaced character is a word",
+                &[
+                    ("你".as_bytes(), 0, 10, 0.9),
+                    ("好".as_bytes(), 10, 20, 0.8),
+                ],
+                &[("你", 0.0, 0.1, 0.9), ("好", 0.1, 0.2, 0.8)],
+            ),
+            (
+                "unspaced script next to a spaced one",
+                &[
+                    (b" Tokyo", 0, 30, 0.9),
+                    ("は".as_bytes(), 30, 40, 0.8),
+                    (b"OK", 40, 50, 0.7),
+                ],
+                &[
+                    ("Tokyo", 0.0, 0.3, 0.9),
+                    ("は", 0.3, 0.4, 0.8),
+                    ("OK", 0.4, 0.5, 0.7),
+                ],
+            ),
+            (
+                "character split across tokens",
+                &[
+                    (&[0xE4, 0xBD], 0, 5, 0.6),
+                    (&[0xA0], 5, 10, 0.9),
+                    ("好".as_bytes(), 10, 20, 0.8),
+                ],
+                &[("你", 0.0, 0.1, 0.6), ("好", 0.1, 0.2, 0.8)],
+            ),
+            (
+                "blank words are dropped",
+                &[(b" ", 0, 5, 0.1), (b" Hi", 5, 10, 0.9), (b" ", 10, 15, 0.1)],
+                &[("Hi", 0.05, 0.1, 0.9)],
+            ),
+            ("no tokens", &[], &[]),
+        ];
+
+        for &(name, specs, expected) in cases {
+            let words = merge_words(tokens(specs), EOT);
+            assert_eq!(summary(&words), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn skips_special_and_timestamp_tokens() {
+        let text = tokens(&[
+            (b" Hel", 0, 10, 0.9),
+            (b"lo", 10, 20, 0.8),
+            (b" there", 20, 40, 0.7),
+        ]);
+        let mut text = text.into_iter();
+        let with_specials = [
+            special(EOT + 1, b"[_SOT_]"),
+            special(TIMESTAMP, b"[_TT_0]"),
+            text.next().unwrap(),
+            special(TIMESTAMP + 5, b"[_TT_10]"),
+            text.next().unwrap(),
+            text.next().unwrap(),
+            special(EOT, b"[_EOT_]"),
+        ];
+
+        let words = merge_words(with_specials, EOT);
+        assert_eq!(
+            summary(&words),
+            [("Hello", 0.0, 0.2, 0.8), ("there", 0.2, 0.4, 0.7)]
+        );
+    }
+}
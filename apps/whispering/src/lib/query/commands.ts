@@ -102,7 +102,7 @@ const stopManualRecording = defineMutation({
 			title: '⏸️ Stopping recording...',
 			description: 'Finalizing your audio capture...',
 		});
-		const { data: blob, error: stopRecordingError } =
+		const { data: recordedAudio, error: stopRecordingError } =
 			await recorder.stopRecording.execute({ toastId });
 		if (stopRecordingError) {
 			notify.error.execute({ id: toastId, ...stopRecordingError });
@@ -125,12 +125,12 @@ const stopManualRecording = defineMutation({
 		}
 		rpc.analytics.logEvent.execute({
 			type: 'manual_recording_completed',
-			blob_size: blob.size,
+			blob_size: recordedAudio.blob?.size,
 			duration,
 		});
 
 		await processRecordingPipeline({
-			blob,
+			...recordedAudio,
 			toastId,
 			completionTitle: '✨ Recording Complete!',
 			completionDescription: 'Recording saved and session closed successfully',
@@ -418,7 +418,8 @@ export const commands = {
  * Processes a recording through the full pipeline: save → transcribe → transform
  *
  * This function handles the complete flow from recording creation through transcription:
- * 1. Creates recording metadata and saves to database
+ * 1. Creates recording metadata and saves to database (encrypted desktop
+ *    recordings are saved by file path only, never as a decrypted blob)
  * 2. Handles database save errors
  * 3. Shows completion toast
  * 4. Executes transcription flow
@@ -426,11 +427,13 @@ export const commands = {
  */
 async function processRecordingPipeline({
 	blob,
+	filePath,
 	toastId,
 	completionTitle,
 	completionDescription,
 }: {
-	blob: Blob;
+	blob: Blob | undefined;
+	filePath?: string;
 	toastId: string;
 	completionTitle: string;
 	completionDescription: string;
@@ -448,6 +451,7 @@ async function processRecordingPipeline({
 			timestamp: now,
 			transcribedText: '',
 			blob,
+			filePath,
 			transcriptionStatus: 'UNPROCESSED',
 		});
 
@@ -1,9 +1,14 @@
-import { WhisperingErr, type WhisperingError } from '$lib/result';
+import {
+	fromTaggedErr,
+	WhisperingErr,
+	type WhisperingError,
+} from '$lib/result';
 import * as services from '$lib/services';
 import type { Recording } from '$lib/services/db';
 import type { DownloadServiceError } from '$lib/services/download';
 import type { Result } from 'wellcrafted/result';
 import { defineMutation } from './_client';
+import { loadRecordingAudio } from './recordings';
 
 export const download = {
 	downloadRecording: defineMutation({
@@ -11,7 +16,15 @@ export const download = {
 		resultMutationFn: async (
 			recording: Recording,
 		): Promise<Result<void, WhisperingError | DownloadServiceError>> => {
-			if (!recording.blob) {
+			const { data: blob, error: loadAudioError } =
+				await loadRecordingAudio(recording);
+			if (loadAudioError) {
+				return fromTaggedErr(loadAudioError, {
+					title: '⚠️ Unable to read recording',
+					action: { type: 'more-details', error: loadAudioError },
+				});
+			}
+			if (!blob) {
 				return WhisperingErr({
 					title: '⚠️ Recording blob not found',
 					description: "Your recording doesn't have a blob to download.",
@@ -20,7 +33,7 @@ export const download = {
 
 			return await services.download.downloadBlob({
 				name: `whispering_recording_${recording.id}`,
-				blob: recording.blob,
+				blob,
 			});
 		},
 	}),
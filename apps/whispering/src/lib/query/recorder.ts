@@ -96,6 +96,11 @@ export const recorder = {
 					selectedDeviceId: settings.value['recording.cpal.deviceId'],
 					outputFolder,
 					sampleRate: settings.value['recording.cpal.sampleRate'],
+					encryptRecordings:
+						settings.value['recording.cpal.encryptRecordings'],
+					sampleFormat: settings.value['recording.cpal.sampleFormat'],
+					fileFormat: settings.value['recording.cpal.fileFormat'],
+					broadcastWave: settings.value['recording.cpal.broadcastWave'],
 				},
 			} as const;
 
@@ -126,7 +131,7 @@ export const recorder = {
 	stopRecording: defineMutation({
 		mutationKey: recorderKeys.stopRecording,
 		resultMutationFn: async ({ toastId }: { toastId: string }) => {
-			const { data: blob, error: stopRecordingError } =
+			const { data: recordedAudio, error: stopRecordingError } =
 				await recorderService().stopRecording({
 					sendStatus: (options) =>
 						notify.loading.execute({ id: toastId, ...options }),
@@ -139,7 +144,7 @@ export const recorder = {
 				});
 			}
 
-			return Ok(blob);
+			return Ok(recordedAudio);
 		},
 		onSettled: invalidateRecorderState,
 	}),
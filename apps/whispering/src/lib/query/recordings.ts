@@ -1,15 +1,33 @@
 import * as services from '$lib/services';
 import type { Recording } from '$lib/services/db';
+import {
+	type RecorderServiceError,
+	readRecordingFile,
+} from '$lib/services/recorder';
 import type { Accessor } from '@tanstack/svelte-query';
-import { Err, Ok } from 'wellcrafted/result';
+import { Err, Ok, type Result } from 'wellcrafted/result';
 import { defineMutation, defineQuery, queryClient } from './_client';
 
 const recordingKeys = {
 	all: ['recordings'] as const,
 	latest: ['recordings', 'latest'] as const,
 	byId: (id: Accessor<string>) => [...recordingKeys.all, id()] as const,
+	audio: (id: Accessor<string>) =>
+		[...recordingKeys.all, id(), 'audio'] as const,
 };
 
+/**
+ * The recording's audio, read from its file when it isn't stored in the
+ * database. Encrypted recordings are decrypted in memory and never saved.
+ */
+export async function loadRecordingAudio(
+	recording: Recording,
+): Promise<Result<Blob | null, RecorderServiceError>> {
+	if (recording.blob) return Ok(recording.blob);
+	if (!recording.filePath) return Ok(null);
+	return await readRecordingFile(recording.filePath);
+}
+
 export const recordings = {
 	getAllRecordings: defineQuery({
 		queryKey: recordingKeys.all,
@@ -42,6 +60,12 @@ export const recordings = {
 				queryClient.getQueryState(recordingKeys.all)?.dataUpdatedAt,
 		}),
 
+	getRecordingAudio: (recording: Accessor<Recording>) =>
+		defineQuery({
+			queryKey: recordingKeys.audio(() => recording().id),
+			resultQueryFn: () => loadRecordingAudio(recording()),
+		}),
+
 	createRecording: defineMutation({
 		mutationKey: ['recordings', 'createRecording'] as const,
 		resultMutationFn: async (recording: Recording) => {
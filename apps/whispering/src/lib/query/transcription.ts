@@ -1,11 +1,16 @@
-import { WhisperingErr, type WhisperingError } from '$lib/result';
+import {
+	fromTaggedErr,
+	WhisperingErr,
+	type WhisperingError,
+} from '$lib/result';
 import * as services from '$lib/services';
 import type { Recording } from '$lib/services/db';
+import type { Settings } from '$lib/settings';
 import { settings } from '$lib/stores/settings.svelte';
 import { Err, Ok, type Result, partitionResults } from 'wellcrafted/result';
 import { defineMutation, queryClient } from './_client';
 import { notify } from './notify';
-import { recordings } from './recordings';
+import { loadRecordingAudio, recordings } from './recordings';
 import { rpc } from './';
 
 const transcriptionKeys = {
@@ -25,12 +30,9 @@ export const transcription = {
 		resultMutationFn: async (
 			recording: Recording,
 		): Promise<Result<string, WhisperingError>> => {
-			if (!recording.blob) {
-				return WhisperingErr({
-					title: '⚠️ Recording blob not found',
-					description: "Your recording doesn't have a blob to transcribe.",
-				});
-			}
+			const { data: audio, error: loadAudioError } =
+				await loadTranscriptionAudio(recording);
+			if (loadAudioError) return Err(loadAudioError);
 			const { error: setRecordingTranscribingError } =
 				await recordings.updateRecording.execute({
 					...recording,
@@ -48,7 +50,7 @@ export const transcription = {
 				});
 			}
 			const { data: transcribedText, error: transcribeError } =
-				await transcribeBlob(recording.blob);
+				await transcribeAudio(audio);
 			if (transcribeError) {
 				const { error: setRecordingTranscribingError } =
 					await recordings.updateRecording.execute({
@@ -95,13 +97,10 @@ export const transcription = {
 		resultMutationFn: async (recordings: Recording[]) => {
 			const results = await Promise.all(
 				recordings.map(async (recording) => {
-					if (!recording.blob) {
-						return WhisperingErr({
-							title: '⚠️ Recording blob not found',
-							description: "Your recording doesn't have a blob to transcribe.",
-						});
-					}
-					return await transcribeBlob(recording.blob);
+					const { data: audio, error: loadAudioError } =
+						await loadTranscriptionAudio(recording);
+					if (loadAudioError) return Err(loadAudioError);
+					return await transcribeAudio(audio);
 				}),
 			);
 			const partitionedResults = partitionResults(results);
@@ -110,8 +109,42 @@ export const transcription = {
 	}),
 };
 
-async function transcribeBlob(
-	blob: Blob,
+/**
+ * Audio to transcribe: a blob, or the file of a desktop recording that
+ * whisper.cpp reads directly instead of receiving its bytes over IPC.
+ */
+type TranscriptionAudio = { blob: Blob } | { filePath: string };
+
+async function loadTranscriptionAudio(
+	recording: Recording,
+): Promise<Result<TranscriptionAudio, WhisperingError>> {
+	if (
+		recording.filePath &&
+		settings.value['transcription.selectedTranscriptionService'] ===
+			'whispercpp'
+	) {
+		return Ok({ filePath: recording.filePath });
+	}
+
+	const { data: blob, error: loadAudioError } =
+		await loadRecordingAudio(recording);
+	if (loadAudioError) {
+		return fromTaggedErr(loadAudioError, {
+			title: '⚠️ Unable to read recording',
+			action: { type: 'more-details', error: loadAudioError },
+		});
+	}
+	if (!blob) {
+		return WhisperingErr({
+			title: '⚠️ Recording blob not found',
+			description: "Your recording doesn't have a blob to transcribe.",
+		});
+	}
+	return Ok({ blob });
+}
+
+async function transcribeAudio(
+	audio: TranscriptionAudio,
 ): Promise<Result<string, WhisperingError>> {
 	const selectedService =
 		settings.value['transcription.selectedTranscriptionService'];
@@ -123,6 +156,43 @@ async function transcribeBlob(
 		provider: selectedService,
 	});
 
+	const transcriptionResult =
+		'filePath' in audio
+			? await services.transcriptions.whispercpp.transcribeRecording(
+					audio.filePath,
+					{
+						outputLanguage: settings.value['transcription.outputLanguage'],
+						prompt: settings.value['transcription.prompt'],
+						temperature: settings.value['transcription.temperature'],
+						modelPath: settings.value['transcription.whispercpp.modelPath'],
+					},
+				)
+			: await transcribeBlob(audio.blob, selectedService);
+
+	// Log transcription result
+	const duration = Date.now() - startTime;
+	if (transcriptionResult.error) {
+		rpc.analytics.logEvent.execute({
+			type: 'transcription_failed',
+			provider: selectedService,
+			error_title: transcriptionResult.error.title,
+			error_description: transcriptionResult.error.description,
+		});
+	} else {
+		rpc.analytics.logEvent.execute({
+			type: 'transcription_completed',
+			provider: selectedService,
+			duration,
+		});
+	}
+
+	return transcriptionResult;
+}
+
+async function transcribeBlob(
+	blob: Blob,
+	selectedService: Settings['transcription.selectedTranscriptionService'],
+): Promise<Result<string, WhisperingError>> {
 	// Compress audio if enabled, else pass through original blob
 	let audioToTranscribe = blob;
 	if (settings.value['transcription.compressionEnabled']) {
@@ -161,98 +231,73 @@ async function transcribeBlob(
 		}
 	}
 
-	const transcriptionResult: Result<string, WhisperingError> =
-		await (async () => {
-			switch (selectedService) {
-				case 'OpenAI':
-					return await services.transcriptions.openai.transcribe(
-						audioToTranscribe,
-						{
-							outputLanguage: settings.value['transcription.outputLanguage'],
-							prompt: settings.value['transcription.prompt'],
-							temperature: settings.value['transcription.temperature'],
-							apiKey: settings.value['apiKeys.openai'],
-							modelName: settings.value['transcription.openai.model'],
-						},
-					);
-				case 'Groq':
-					return await services.transcriptions.groq.transcribe(
-						audioToTranscribe,
-						{
-							outputLanguage: settings.value['transcription.outputLanguage'],
-							prompt: settings.value['transcription.prompt'],
-							temperature: settings.value['transcription.temperature'],
-							apiKey: settings.value['apiKeys.groq'],
-							modelName: settings.value['transcription.groq.model'],
-						},
-					);
-				case 'speaches':
-					return await services.transcriptions.speaches.transcribe(
-						audioToTranscribe,
-						{
-							outputLanguage: settings.value['transcription.outputLanguage'],
-							prompt: settings.value['transcription.prompt'],
-							temperature: settings.value['transcription.temperature'],
-							modelId: settings.value['transcription.speaches.modelId'],
-							baseUrl: settings.value['transcription.speaches.baseUrl'],
-						},
-					);
-				case 'ElevenLabs':
-					return await services.transcriptions.elevenlabs.transcribe(
-						audioToTranscribe,
-						{
-							outputLanguage: settings.value['transcription.outputLanguage'],
-							prompt: settings.value['transcription.prompt'],
-							temperature: settings.value['transcription.temperature'],
-							apiKey: settings.value['apiKeys.elevenlabs'],
-							modelName: settings.value['transcription.elevenlabs.model'],
-						},
-					);
-				case 'Deepgram':
-					return await services.transcriptions.deepgram.transcribe(
-						audioToTranscribe,
-						{
-							outputLanguage: settings.value['transcription.outputLanguage'],
-							prompt: settings.value['transcription.prompt'],
-							temperature: settings.value['transcription.temperature'],
-							apiKey: settings.value['apiKeys.deepgram'],
-							modelName: settings.value['transcription.deepgram.model'],
-						},
-					);
-				case 'whispercpp':
-					return await services.transcriptions.whispercpp.transcribe(
-						audioToTranscribe,
-						{
-							outputLanguage: settings.value['transcription.outputLanguage'],
-							prompt: settings.value['transcription.prompt'],
-							temperature: settings.value['transcription.temperature'],
-							modelPath: settings.value['transcription.whispercpp.modelPath'],
-						},
-					);
-				default:
-					return WhisperingErr({
-						title: '⚠️ No transcription service selected',
-						description: 'Please select a transcription service in settings.',
-					});
-			}
-		})();
-
-	// Log transcription result
-	const duration = Date.now() - startTime;
-	if (transcriptionResult.error) {
-		rpc.analytics.logEvent.execute({
-			type: 'transcription_failed',
-			provider: selectedService,
-			error_title: transcriptionResult.error.title,
-			error_description: transcriptionResult.error.description,
-		});
-	} else {
-		rpc.analytics.logEvent.execute({
-			type: 'transcription_completed',
-			provider: selectedService,
-			duration,
-		});
+	switch (selectedService) {
+		case 'OpenAI':
+			return await services.transcriptions.openai.transcribe(
+				audioToTranscribe,
+				{
+					outputLanguage: settings.value['transcription.outputLanguage'],
+					prompt: settings.value['transcription.prompt'],
+					temperature: settings.value['transcription.temperature'],
+					apiKey: settings.value['apiKeys.openai'],
+					modelName: settings.value['transcription.openai.model'],
+				},
+			);
+		case 'Groq':
+			return await services.transcriptions.groq.transcribe(audioToTranscribe, {
+				outputLanguage: settings.value['transcription.outputLanguage'],
+				prompt: settings.value['transcription.prompt'],
+				temperature: settings.value['transcription.temperature'],
+				apiKey: settings.value['apiKeys.groq'],
+				modelName: settings.value['transcription.groq.model'],
+			});
+		case 'speaches':
+			return await services.transcriptions.speaches.transcribe(
+				audioToTranscribe,
+				{
+					outputLanguage: settings.value['transcription.outputLanguage'],
+					prompt: settings.value['transcription.prompt'],
+					temperature: settings.value['transcription.temperature'],
+					modelId: settings.value['transcription.speaches.modelId'],
+					baseUrl: settings.value['transcription.speaches.baseUrl'],
+				},
+			);
+		case 'ElevenLabs':
+			return await services.transcriptions.elevenlabs.transcribe(
+				audioToTranscribe,
+				{
+					outputLanguage: settings.value['transcription.outputLanguage'],
+					prompt: settings.value['transcription.prompt'],
+					temperature: settings.value['transcription.temperature'],
+					apiKey: settings.value['apiKeys.elevenlabs'],
+					modelName: settings.value['transcription.elevenlabs.model'],
+				},
+			);
+		case 'Deepgram':
+			return await services.transcriptions.deepgram.transcribe(
+				audioToTranscribe,
+				{
+					outputLanguage: settings.value['transcription.outputLanguage'],
+					prompt: settings.value['transcription.prompt'],
+					temperature: settings.value['transcription.temperature'],
+					apiKey: settings.value['apiKeys.deepgram'],
+					modelName: settings.value['transcription.deepgram.model'],
+				},
+			);
+		case 'whispercpp':
+			return await services.transcriptions.whispercpp.transcribe(
+				audioToTranscribe,
+				{
+					outputLanguage: settings.value['transcription.outputLanguage'],
+					prompt: settings.value['transcription.prompt'],
+					temperature: settings.value['transcription.temperature'],
+					modelPath: settings.value['transcription.whispercpp.modelPath'],
+				},
+			);
+		default:
+			return WhisperingErr({
+				title: '⚠️ No transcription service selected',
+				description: 'Please select a transcription service in settings.',
+			});
 	}
-
-	return transcriptionResult;
 }
@@ -28,8 +28,12 @@ type SettingsSection =
 export type Event =
 	// Application lifecycle
 	| { type: 'app_started' }
-	// Recording completion events - always include blob_size, duration when available
-	| { type: 'manual_recording_completed'; blob_size: number; duration?: number }
+	// Recording completion events - blob_size and duration when available
+	| {
+			type: 'manual_recording_completed';
+			blob_size?: number; // Unknown for encrypted desktop recordings
+			duration?: number;
+	  }
 	| { type: 'vad_recording_completed'; blob_size: number; duration?: number }
 	| { type: 'file_uploaded'; blob_size: number }
 	// Transcription events
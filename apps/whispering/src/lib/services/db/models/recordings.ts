@@ -7,6 +7,11 @@ export type Recording = {
 	updatedAt: string;
 	transcribedText: string;
 	blob: Blob | undefined;
+	/**
+	 * The file the desktop recorder saved the audio to. Encrypted recordings
+	 * have no blob and are decrypted in memory from this file when needed.
+	 */
+	filePath?: string;
 	/**
 	 * A recording
 	 * 1. Begins in an 'UNPROCESSED' state
@@ -2,18 +2,28 @@ import type {
 	CancelRecordingResult,
 	WhisperingRecordingState,
 } from '$lib/constants/audio';
+import { MIME_TYPE_MAP } from '$lib/constants/mime';
 import { invoke as tauriInvoke } from '@tauri-apps/api/core';
-import { readFile, remove } from '@tauri-apps/plugin-fs';
+import { remove } from '@tauri-apps/plugin-fs';
+import { type } from 'arktype';
 import { Err, Ok, type Result, tryAsync } from 'wellcrafted/result';
 import type { Device, DeviceAcquisitionOutcome } from '../types';
 import { asDeviceIdentifier } from '../types';
 import type {
 	CpalRecordingParams,
+	RecordedAudio,
 	RecorderService,
 	RecorderServiceError,
 } from './types';
 import { RecorderServiceErr } from './types';
 
+/**
+ * Typed error the Rust recorder returns for rejected recording ids and folders
+ */
+const RecordingPathErrorType = type({
+	name: "'InvalidRecordingId' | 'InvalidOutputFolder' | 'OutputFolderNotAllowed' | 'NotARecording'",
+});
+
 /**
  * Audio recording data returned from the Rust method
  */
@@ -22,6 +32,7 @@ type AudioRecording = {
 	channels: number;
 	durationSeconds: number;
 	filePath?: string;
+	encrypted: boolean;
 };
 
 export function createCpalRecorderService(): RecorderService {
@@ -71,6 +82,10 @@ export function createCpalRecorderService(): RecorderService {
 				recordingId,
 				outputFolder,
 				sampleRate,
+				encryptRecordings,
+				sampleFormat,
+				fileFormat,
+				broadcastWave,
 			}: CpalRecordingParams,
 			{ sendStatus },
 		): Promise<Result<DeviceAcquisitionOutcome, RecorderServiceError>> => {
@@ -151,9 +166,26 @@ export function createCpalRecorderService(): RecorderService {
 					recordingId,
 					outputFolder,
 					sampleRate: sampleRateNum,
+					encrypt: encryptRecordings,
+					sampleFormat,
+					format: fileFormat,
+					broadcastWave,
 				},
 			);
-			if (initRecordingSessionError)
+			if (initRecordingSessionError) {
+				const pathError = RecordingPathErrorType(
+					initRecordingSessionError.error,
+				);
+				if (
+					!(pathError instanceof type.errors) &&
+					pathError.name === 'OutputFolderNotAllowed'
+				)
+					return RecorderServiceErr({
+						message:
+							"Whispering isn't allowed to save recordings in your output folder. Please select the folder again in the recording settings.",
+						context: { outputFolder },
+						cause: initRecordingSessionError,
+					});
 				return RecorderServiceErr({
 					message:
 						'We encountered an issue while setting up your recording session. This could be because your microphone is being used by another app, your microphone permissions are denied, or the selected recording device is disconnected',
@@ -163,6 +195,7 @@ export function createCpalRecorderService(): RecorderService {
 					},
 					cause: initRecordingSessionError,
 				});
+			}
 
 			sendStatus({
 				title: '🎙️ Starting Recording',
@@ -184,7 +217,7 @@ export function createCpalRecorderService(): RecorderService {
 
 		stopRecording: async ({
 			sendStatus,
-		}): Promise<Result<Blob, RecorderServiceError>> => {
+		}): Promise<Result<RecordedAudio, RecorderServiceError>> => {
 			const { data: audioRecording, error: stopRecordingError } =
 				await invoke<AudioRecording>('stop_recording');
 			if (stopRecordingError) {
@@ -209,25 +242,20 @@ export function createCpalRecorderService(): RecorderService {
 			}
 			// audioRecording is now AudioRecordingWithFile
 
-			// Read the WAV file from disk
-			sendStatus({
-				title: '📁 Reading Recording',
-				description: 'Loading your recording from disk...',
-			});
+			// Encrypted recordings are only kept on disk, and decrypted in memory
+			// when they're played back or transcribed
+			let blob: Blob | undefined;
+			if (!audioRecording.encrypted) {
+				sendStatus({
+					title: '📁 Reading Recording',
+					description: 'Loading your recording from disk...',
+				});
 
-			const { data: blob, error: readRecordingFileError } = await tryAsync({
-				try: async () => {
-					const fileBytes = await readFile(filePath);
-					return new Blob([fileBytes], { type: 'audio/wav' });
-				},
-				catch: (error) =>
-					RecorderServiceErr({
-						message: 'Unable to read recording file. Please try again.',
-						context: { audioRecording },
-						cause: error,
-					}),
-			});
-			if (readRecordingFileError) return Err(readRecordingFileError);
+				const { data, error: readRecordingFileError } =
+					await readRecordingFile(filePath);
+				if (readRecordingFileError) return Err(readRecordingFileError);
+				blob = data;
+			}
 
 			// Close the recording session after stopping
 			sendStatus({
@@ -242,7 +270,7 @@ export function createCpalRecorderService(): RecorderService {
 				console.error('Failed to close recording session:', closeError);
 			}
 
-			return Ok(blob);
+			return Ok({ blob, filePath });
 		},
 
 		cancelRecording: async ({
@@ -313,6 +341,41 @@ export function createCpalRecorderService(): RecorderService {
 	};
 }
 
+/**
+ * Read a desktop recording into memory, decrypting it in Rust if it's encrypted.
+ */
+export async function readRecordingFile(
+	filePath: string,
+): Promise<Result<Blob, RecorderServiceError>> {
+	const { data: fileBytes, error: readRecordingError } =
+		await invoke<ArrayBuffer>('read_recording', { filePath });
+	if (readRecordingError)
+		return RecorderServiceErr({
+			message: 'Unable to read recording file. Please try again.',
+			context: { filePath },
+			cause: readRecordingError,
+		});
+	return Ok(new Blob([fileBytes], { type: recordingMimeType(filePath) }));
+}
+
+/**
+ * MIME type of a recording from its extension. Encrypted recordings end in `.enc`
+ * after the format's extension, and `read_recording` returns them decrypted.
+ */
+function recordingMimeType(filePath: string): string {
+	const fileName = filePath.split(/[\\/]/).at(-1) ?? '';
+	const extensions = fileName.toLowerCase().split('.').slice(1);
+	if (extensions.at(-1) === 'enc') extensions.pop();
+	switch (extensions.at(-1)) {
+		case 'flac':
+			return MIME_TYPE_MAP.flac;
+		case 'opus':
+			return MIME_TYPE_MAP.ogg; // Opus recordings are Ogg files
+		default:
+			return MIME_TYPE_MAP.wav;
+	}
+}
+
 /**
  * CPAL recorder service that uses the Rust CPAL method.
  * This is the CPAL audio recorder for desktop environments.
@@ -21,6 +21,7 @@ import type {
 import { asDeviceIdentifier } from '../types';
 import type {
 	FfmpegRecordingParams,
+	RecordedAudio,
 	RecorderService,
 	RecorderServiceError,
 } from './types';
@@ -373,7 +374,7 @@ export function createFfmpegRecorderService(): RecorderService {
 
 		stopRecording: async ({
 			sendStatus,
-		}): Promise<Result<Blob, RecorderServiceError>> => {
+		}): Promise<Result<RecordedAudio, RecorderServiceError>> => {
 			const child = getCurrentChild();
 			const session = sessionState.value;
 			if (!child || !session) {
@@ -489,7 +490,7 @@ export function createFfmpegRecorderService(): RecorderService {
 				});
 			}
 
-			return Ok(blob);
+			return Ok({ blob });
 		},
 
 		cancelRecording: async ({
@@ -1,3 +1,8 @@
 // Re-export types for convenience
-export type { RecorderService, RecorderServiceError } from './types';
+export type {
+	RecordedAudio,
+	RecorderService,
+	RecorderServiceError,
+} from './types';
+export { readRecordingFile } from './cpal';
 export { getDefaultRecordingsFolder } from './utils';
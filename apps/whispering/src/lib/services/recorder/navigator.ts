@@ -7,6 +7,7 @@ import {
 } from '../device-stream';
 import type {
 	NavigatorRecordingParams,
+	RecordedAudio,
 	RecorderService,
 	RecorderServiceError,
 } from './types';
@@ -127,7 +128,7 @@ export function createNavigatorRecorderService(): RecorderService {
 
 		stopRecording: async ({
 			sendStatus,
-		}): Promise<Result<Blob, RecorderServiceError>> => {
+		}): Promise<Result<RecordedAudio, RecorderServiceError>> => {
 			if (!activeRecording) {
 				return RecorderServiceErr({
 					message:
@@ -179,7 +180,7 @@ export function createNavigatorRecorderService(): RecorderService {
 				title: '✅ Recording Saved',
 				description: 'Your recording is ready for transcription!',
 			});
-			return Ok(blob);
+			return Ok({ blob });
 		},
 
 		cancelRecording: async ({
@@ -16,6 +16,15 @@ export const { RecorderServiceError, RecorderServiceErr } = createTaggedError(
 );
 export type RecorderServiceError = ReturnType<typeof RecorderServiceError>;
 
+/**
+ * Audio captured by a recorder. The desktop recorder also returns the file it
+ * saved, and no blob when the file is encrypted.
+ */
+export type RecordedAudio = {
+	blob: Blob | undefined;
+	filePath?: string;
+};
+
 /**
  * Base parameters shared across all methods
  */
@@ -31,6 +40,10 @@ export type CpalRecordingParams = BaseRecordingParams & {
 	method: 'cpal';
 	outputFolder: string;
 	sampleRate: string;
+	encryptRecordings: boolean;
+	sampleFormat: 'pcm16' | 'pcm24' | 'float32';
+	fileFormat: 'wav' | 'flac' | 'opus';
+	broadcastWave: boolean;
 };
 
 /**
@@ -86,11 +99,11 @@ export type RecorderService = {
 	): Promise<Result<DeviceAcquisitionOutcome, RecorderServiceError>>;
 
 	/**
-	 * Stop the current recording and return the audio blob
+	 * Stop the current recording and return the recorded audio
 	 */
 	stopRecording(callbacks: {
 		sendStatus: UpdateStatusMessageFn;
-	}): Promise<Result<Blob, RecorderServiceError>>;
+	}): Promise<Result<RecordedAudio, RecorderServiceError>>;
 
 	/**
 	 * Cancel the current recording without saving
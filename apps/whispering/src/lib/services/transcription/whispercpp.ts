@@ -9,154 +9,189 @@ import { invoke } from '@tauri-apps/api/core';
 import { exists } from '@tauri-apps/plugin-fs';
 import { extractErrorMessage } from 'wellcrafted/error';
 import { type } from 'arktype';
-import { rpc } from '$lib/query';
 
 const WhisperCppErrorType = type({
-	name: "'AudioReadError' | 'GpuError' | 'ModelLoadError' | 'TranscriptionError'",
+	name: "'AudioReadError' | 'FfmpegRequired' | 'GpuError' | 'ModelLoadError' | 'TranscriptionError' | 'Cancelled'",
 	message: 'string',
 });
 
+type WhisperCppOptions = {
+	prompt: string;
+	temperature: string;
+	outputLanguage: Settings['transcription.outputLanguage'];
+	modelPath: string;
+};
+
 export function createWhisperCppTranscriptionService() {
 	return {
 		async transcribe(
 			audioBlob: Blob,
-			options: {
-				prompt: string;
-				temperature: string;
-				outputLanguage: Settings['transcription.outputLanguage'];
-				modelPath: string;
-			},
+			options: WhisperCppOptions,
 		): Promise<Result<string, WhisperingError>> {
-			// Pre-validation
-			if (!options.modelPath) {
-				return WhisperingErr({
-					title: '📁 Model File Required',
-					description: 'Please select a Whisper model file in settings.',
-					action: {
-						type: 'link',
-						label: 'Configure model',
-						href: '/settings/transcription',
-					},
-				});
-			}
+			// Convert audio blob to byte array
+			const arrayBuffer = await audioBlob.arrayBuffer();
+			const audioData = Array.from(new Uint8Array(arrayBuffer));
 
-			// Check if model file exists
-			const { data: isExists } = await tryAsync({
-				try: () => exists(options.modelPath),
-				catch: () => Ok(false),
+			return await invokeWhisperCpp('transcribe_with_whisper_cpp', options, {
+				audioData,
 			});
+		},
+
+		/**
+		 * Transcribe a recording the desktop recorder saved, read by Rust from its
+		 * file instead of sending the audio over IPC.
+		 */
+		async transcribeRecording(
+			filePath: string,
+			options: WhisperCppOptions,
+		): Promise<Result<string, WhisperingError>> {
+			return await invokeWhisperCpp(
+				'transcribe_recording_with_whisper_cpp',
+				options,
+				{ filePath },
+			);
+		},
+	};
+}
+
+async function invokeWhisperCpp(
+	command:
+		| 'transcribe_with_whisper_cpp'
+		| 'transcribe_recording_with_whisper_cpp',
+	options: WhisperCppOptions,
+	audio: { audioData: number[] } | { filePath: string },
+): Promise<Result<string, WhisperingError>> {
+	// Pre-validation
+	if (!options.modelPath) {
+		return WhisperingErr({
+			title: '📁 Model File Required',
+			description: 'Please select a Whisper model file in settings.',
+			action: {
+				type: 'link',
+				label: 'Configure model',
+				href: '/settings/transcription',
+			},
+		});
+	}
 
-			if (!isExists) {
+	// Check if model file exists
+	const { data: isExists } = await tryAsync({
+		try: () => exists(options.modelPath),
+		catch: () => Ok(false),
+	});
+
+	if (!isExists) {
+		return WhisperingErr({
+			title: '❌ Model File Not Found',
+			description: `The model file "${options.modelPath}" does not exist.`,
+			action: {
+				type: 'link',
+				label: 'Select model',
+				href: '/settings/transcription',
+			},
+		});
+	}
+
+	// Call Tauri command to transcribe with whisper-cpp
+	const result = await tryAsync({
+		try: async () => {
+			const { text } = await invoke<{
+				text: string;
+				language: string | null;
+			}>(command, {
+				...audio,
+				modelPath: options.modelPath,
+				language:
+					options.outputLanguage === 'auto' ? null : options.outputLanguage,
+				prompt: options.prompt,
+				temperature: Number.parseFloat(options.temperature),
+			});
+			return text;
+		},
+		catch: (unknownError) => {
+			const result = WhisperCppErrorType(unknownError);
+			if (result instanceof type.errors) {
 				return WhisperingErr({
-					title: '❌ Model File Not Found',
-					description: `The model file "${options.modelPath}" does not exist.`,
-					action: {
-						type: 'link',
-						label: 'Select model',
-						href: '/settings/transcription',
-					},
+					title: '❌ Unexpected Whisper C++ Error',
+					description: extractErrorMessage(unknownError),
+					action: { type: 'more-details', error: unknownError },
 				});
 			}
+			const error = result;
+			switch (error.name) {
+				case 'ModelLoadError':
+					return WhisperingErr({
+						title: '🤖 Model Loading Error',
+						description: error.message,
+						action: {
+							type: 'more-details',
+							error: new Error(error.message),
+						},
+					});
 
-			// Check if FFmpeg is installed
-			const ffmpegResult = await rpc.ffmpeg.checkFfmpegInstalled.ensure();
-			if (ffmpegResult.error) return ffmpegResult;
-			if (!ffmpegResult.data) {
-				return WhisperingWarningErr({
-					title: '🛠️ Install FFmpeg',
-					description:
-						'FFmpeg is required for enhanced audio format support. Install it to transcribe non-WAV audio files with Whisper C++.',
-					action: {
-						type: 'link',
-						label: 'Install FFmpeg',
-						href: '/install-ffmpeg',
-					},
-				});
-			}
+				case 'GpuError':
+					return WhisperingErr({
+						title: '🎮 GPU Error',
+						description: error.message,
+						action: {
+							type: 'link',
+							label: 'Configure settings',
+							href: '/settings/transcription',
+						},
+					});
 
-			// Convert audio blob to byte array
-			const arrayBuffer = await audioBlob.arrayBuffer();
-			const audioData = Array.from(new Uint8Array(arrayBuffer));
+				case 'FfmpegRequired':
+					return WhisperingWarningErr({
+						title: '🛠️ Install FFmpeg',
+						description:
+							'FFmpeg is required to transcribe this audio format with Whisper C++. WAV, FLAC, MP3 and Ogg files work without it.',
+						action: {
+							type: 'link',
+							label: 'Install FFmpeg',
+							href: '/install-ffmpeg',
+						},
+					});
 
-			// Call Tauri command to transcribe with whisper-cpp
-			const result = await tryAsync({
-				try: () =>
-					invoke<string>('transcribe_with_whisper_cpp', {
-						audioData: audioData,
-						modelPath: options.modelPath,
-						language:
-							options.outputLanguage === 'auto' ? null : options.outputLanguage,
-						prompt: options.prompt,
-						temperature: Number.parseFloat(options.temperature),
-					}),
-				catch: (unknownError) => {
-					const result = WhisperCppErrorType(unknownError);
-					if (result instanceof type.errors) {
-						return WhisperingErr({
-							title: '❌ Unexpected Whisper C++ Error',
-							description: extractErrorMessage(unknownError),
-							action: { type: 'more-details', error: unknownError },
-						});
-					}
-					const error = result;
-					switch (error.name) {
-						case 'ModelLoadError':
-							return WhisperingErr({
-								title: '🤖 Model Loading Error',
-								description: error.message,
-								action: {
-									type: 'more-details',
-									error: new Error(error.message),
-								},
-							});
-
-						case 'GpuError':
-							return WhisperingErr({
-								title: '🎮 GPU Error',
-								description: error.message,
-								action: {
-									type: 'link',
-									label: 'Configure settings',
-									href: '/settings/transcription',
-								},
-							});
-
-						case 'AudioReadError':
-							return WhisperingErr({
-								title: '🔊 Audio Read Error',
-								description: error.message,
-								action: {
-									type: 'more-details',
-									error: new Error(error.message),
-								},
-							});
-
-						case 'TranscriptionError':
-							return WhisperingErr({
-								title: '❌ Transcription Error',
-								description: error.message,
-								action: {
-									type: 'more-details',
-									error: new Error(error.message),
-								},
-							});
-
-						default:
-							return WhisperingErr({
-								title: '❌ Whisper C++ Error',
-								description: 'An unexpected error occurred.',
-								action: {
-									type: 'more-details',
-									error: new Error(String(error)),
-								},
-							});
-					}
-				},
-			});
+				case 'AudioReadError':
+					return WhisperingErr({
+						title: '🔊 Audio Read Error',
+						description: error.message,
+						action: {
+							type: 'more-details',
+							error: new Error(error.message),
+						},
+					});
+
+				case 'TranscriptionError':
+					return WhisperingErr({
+						title: '❌ Transcription Error',
+						description: error.message,
+						action: {
+							type: 'more-details',
+							error: new Error(error.message),
+						},
+					});
 
-			return result;
+				case 'Cancelled':
+					return WhisperingWarningErr({
+						title: '⏹️ Transcription Cancelled',
+						description: error.message,
+					});
+
+				default:
+					return WhisperingErr({
+						title: '❌ Whisper C++ Error',
+						description: 'An unexpected error occurred.',
+						action: {
+							type: 'more-details',
+							error: new Error(String(error)),
+						},
+					});
+			}
 		},
-	};
+	});
+
+	return result;
 }
 
 export type WhisperCppTranscriptionService = ReturnType<
@@ -151,6 +151,14 @@ export const settingsSchema = z.object({
 	'recording.cpal.sampleRate': z
 		.enum(['16000', '44100', '48000'])
 		.default('16000'),
+	'recording.cpal.encryptRecordings': z.boolean().default(false), // Encrypt recordings at rest with an app-held key
+	'recording.cpal.sampleFormat': z
+		.enum(['pcm16', 'pcm24', 'float32'])
+		.default('float32'),
+	'recording.cpal.fileFormat': z
+		.enum(['wav', 'flac', 'opus'])
+		.default('wav'),
+	'recording.cpal.broadcastWave': z.boolean().default(false), // Add a BWF bext chunk with the origination time to WAVs
 
 	// FFmpeg recording settings - split into three customizable parts
 	'recording.ffmpeg.globalOptions': z
@@ -4,7 +4,7 @@ import { rpc } from '$lib/query';
 import { settings } from '$lib/stores/settings.svelte';
 
 export const RECORDING_COMPATIBILITY_MESSAGE =
-	'Whisper C++ requires audio in 16kHz WAV format. Install FFmpeg to convert your recordings or switch to CPAL 16kHz mode.';
+	'Whisper C++ can only read browser recordings (WebM) through FFmpeg. Install FFmpeg to convert your recordings or switch to CPAL recording.';
 
 export const COMPRESSION_RECOMMENDED_MESSAGE =
 	"Since you're using CPAL recording with cloud transcription, we recommend enabling audio compression to reduce file sizes and upload times.";
@@ -30,20 +30,17 @@ function isUsingWhisperCpp(): boolean {
 	);
 }
 
-function isUsing16kHz(): boolean {
-	return settings.value['recording.cpal.sampleRate'] === '16000';
-}
-
 /**
  * Checks if there's a compatibility issue between recording and transcription settings.
- * This occurs when using Whisper C++ with any recording method except CPAL at 16kHz.
+ * This occurs when using Whisper C++ with any recording method except CPAL, whose
+ * recordings are decoded without FFmpeg.
  * @returns true when there's a compatibility issue that needs to be resolved
  */
 export function hasRecordingCompatibilityIssue(): boolean {
 	if (!isUsingWhisperCpp()) return false;
 
-	if (settings.value['recording.method'] === 'cpal' && isUsing16kHz()) {
-		return false; // CPAL at 16kHz with Whisper C++ doesn't need FFmpeg
+	if (settings.value['recording.method'] === 'cpal') {
+		return false; // CPAL recordings with Whisper C++ don't need FFmpeg
 	}
 
 	return true; // All other Whisper C++ combinations need FFmpeg
@@ -64,7 +61,7 @@ export function isCompressionRecommended(): boolean {
 /**
  * Checks for FFmpeg installation and shows an appropriate toast based on current settings.
  *
- * REQUIRED: Whisper C++ + any method except CPAL at 16kHz
+ * REQUIRED: Whisper C++ + any method except CPAL
  * RECOMMENDED: When compression is recommended (CPAL + cloud transcription + compression not enabled)
  */
 export async function checkFfmpeg() {
@@ -88,7 +85,7 @@ export async function checkFfmpeg() {
 		return;
 	}
 
-	// Recording compatibility issue with Whisper C++ (except CPAL at 16kHz)
+	// Recording compatibility issue with Whisper C++ (except CPAL)
 	if (hasRecordingCompatibilityIssue()) {
 		toast.warning('Recording Settings Incompatible', {
 			description: RECORDING_COMPATIBILITY_MESSAGE,
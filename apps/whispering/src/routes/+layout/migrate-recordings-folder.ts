@@ -0,0 +1,16 @@
+import { settings } from '$lib/stores/settings.svelte';
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * Lets the desktop recorder keep writing to an output folder saved by versions
+ * that didn't keep track of picked folders. The recorder only accepts this once.
+ */
+export async function migrateRecordingsFolder() {
+	try {
+		await invoke<boolean>('migrate_recordings_folder', {
+			savedFolder: settings.value['recording.cpal.outputFolder'],
+		});
+	} catch (error) {
+		console.error('Failed to migrate recordings folder:', error);
+	}
+}